@@ -0,0 +1,121 @@
+use crate::inner_prelude::*;
+
+//Queries that can reject whole subtrees using the per subtree bounding rects.
+fn handle_bench(fg: &mut Figure) {
+    #[derive(Debug)]
+    struct Record {
+        num_bots: usize,
+        bench_construction: f64,
+        bench_rect: f64,
+        bench_raycast: f64,
+        bench_knearest: f64,
+    }
+
+    let mut records = Vec::new();
+
+    for num_bots in (0..50_000).step_by(500) {
+        let grow = 1.0;
+
+        let mut bot_inner: Vec<_> = (0..num_bots).map(|_| 0isize).collect();
+
+        let mut bb = distribute(grow, &mut bot_inner, |a| a.to_i32());
+
+        let border = match compute_border(&bb) {
+            Some(border) => border,
+            None => continue,
+        };
+
+        let (mut tree, bench_construction) = bench_closure_ret(|| broccoli::new(&mut bb));
+
+        //Thin rects along the edges of the distribution, where most subtrees can be rejected
+        //along the axis that they were not divided on.
+        let rects: Vec<_> = (0..20)
+            .map(|i| {
+                let x = border.x.start + (border.x.end - border.x.start) * i / 20;
+                rect(x, x + 10, border.y.start, border.y.start + 10)
+            })
+            .collect();
+
+        let bench_rect = bench_closure(|| {
+            for r in rects.iter() {
+                tree.for_all_intersect_rect_mut(r, |a| {
+                    **a.unpack_inner() += 1;
+                });
+            }
+        });
+
+        let bench_raycast = bench_closure(|| {
+            let mut handler = broccoli::query::raycast::default_rect_raycast(&tree);
+            for r in rects.iter() {
+                let ray = axgeom::Ray {
+                    point: vec2(r.x.start, border.y.start - 10),
+                    dir: vec2(1, 1),
+                };
+                if let axgeom::CastResult::Hit(a) = tree.raycast_mut(ray, &mut handler) {
+                    for b in a.elems.into_iter() {
+                        **b.unpack_inner() += 1;
+                    }
+                }
+            }
+        });
+
+        let bench_knearest = bench_closure(|| {
+            let mut handler = broccoli::query::knearest::default_rect_knearest(&tree);
+            for r in rects.iter() {
+                let point = vec2(r.x.start, border.y.start);
+                for a in tree.k_nearest_mut(point, 3, &mut handler).into_vec() {
+                    **a.bot.unpack_inner() += 1;
+                }
+            }
+        });
+
+        records.push(Record {
+            num_bots,
+            bench_construction,
+            bench_rect,
+            bench_raycast,
+            bench_knearest,
+        });
+    }
+
+    let rects = &mut records;
+    use gnuplot::*;
+    let x = rects.iter().map(|a| a.num_bots);
+    let y1 = rects.iter().map(|a| a.bench_construction);
+    let y2 = rects.iter().map(|a| a.bench_rect);
+    let y3 = rects.iter().map(|a| a.bench_raycast);
+    let y4 = rects.iter().map(|a| a.bench_knearest);
+
+    let ww = 1.0;
+    fg.axes2d()
+        .set_title("Queries Pruned by Subtree Bounds With abspiral(x,1.0)", &[])
+        .set_legend(Graph(1.0), Graph(1.0), &[LegendOption::Horizontal], &[])
+        .lines(
+            x.clone(),
+            y1,
+            &[Caption("construction"), Color(COLS[0]), LineWidth(ww)],
+        )
+        .lines(
+            x.clone(),
+            y2,
+            &[Caption("rect"), Color(COLS[1]), LineWidth(ww)],
+        )
+        .lines(
+            x.clone(),
+            y3,
+            &[Caption("raycast"), Color(COLS[2]), LineWidth(ww)],
+        )
+        .lines(
+            x.clone(),
+            y4,
+            &[Caption("knearest"), Color(COLS[3]), LineWidth(ww)],
+        )
+        .set_x_label("Number of Objects", &[])
+        .set_y_label("Time taken in seconds", &[]);
+}
+
+pub fn handle(fb: &mut FigureBuilder) {
+    let mut fg = fb.build("subtree_bounds");
+    handle_bench(&mut fg);
+    fb.finish(fg);
+}
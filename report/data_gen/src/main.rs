@@ -222,6 +222,8 @@ fn main() {
             run_test!(&mut fb, colfind::parallel_heur_comparison::handle);
 
             run_test!(&mut fb, colfind::height_heur_comparison::handle);
+
+            run_test!(&mut fb, colfind::subtree_bounds::handle);
             
             //nbody::theory::handle(&mut fb);
         }
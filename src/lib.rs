@@ -33,7 +33,9 @@
 //! but would have unspecified results.
 //! If using floats, it's the users responsibility to not pass `NaN` values into the tree.
 //! There is no static protection against this, though if this is desired you can use
-//! the [ordered-float](https://crates.io/crates/ordered-float) crate. The Ord trait was not
+//! [`notnan::NotNan`] or the [ordered-float](https://crates.io/crates/ordered-float) crate.
+//! [`TreeBuilder::build_checked`](build::TreeBuilder::build_checked) can also be used to
+//! check the aabbs at construction. The Ord trait was not
 //! enforced to give users the option to use primitive floats directly which can be easier to
 //! work with.
 //!
@@ -98,6 +100,11 @@ pub mod pmut;
 ///Contains node-level building block structs and visitors used for a [`Tree`].
 pub mod node;
 
+pub mod notnan;
+
+#[cfg(feature = "testing")]
+pub mod testing;
+
 ///Generic slice utility functions.
 mod util;
 
@@ -106,11 +113,15 @@ mod util;
 pub mod prelude {
     pub use crate::query::draw::DrawQuery;
 
+    pub use crate::query::closest_pair::ClosestPairQuery;
     pub use crate::query::colfind::ColfindQuery;
+    pub use crate::query::farthest::FarthestQuery;
     pub use crate::query::intersect_with::IntersectQuery;
     pub use crate::query::knearest::KnearestQuery;
+    pub use crate::query::multi::MultiQuery;
     pub use crate::query::raycast::RaycastQuery;
     pub use crate::query::rect::RectQuery;
+    pub use crate::query::segment::SegmentQuery;
     //pub use crate::query::Queries;
 }
 
@@ -211,6 +211,8 @@ pub(crate) struct NodePtr<T: Aabb> {
 
     _cont: axgeom::Range<T::Num>,
     _div: Option<T::Num>,
+    _bounds: Option<axgeom::Rect<T::Num>>,
+    _subtree_len: usize,
 }
 
 
@@ -229,4 +231,21 @@ pub struct Node<'a, T: Aabb> {
     //for leafs:
     //  value is none
     pub div: Option<T::Num>,
+
+    //the bounding rect of all the elements in this node and its descendants.
+    //none if there are no elements in this subtree.
+    pub(crate) bounds: Option<axgeom::Rect<T::Num>>,
+
+    //the number of elements in this node and its descendants.
+    pub(crate) subtree_len: usize,
+}
+
+impl<'a, T: Aabb> Node<'a, T> {
+    ///The bounding rect of all the elements in this node and its descendants.
+    ///Returns `None` if there are no elements in this subtree.
+    #[inline(always)]
+    #[must_use]
+    pub fn bounds(&self) -> Option<axgeom::Rect<T::Num>> {
+        self.bounds
+    }
 }
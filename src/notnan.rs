@@ -0,0 +1,155 @@
+//! A float wrapper that is never `NaN`
+//!
+//! [`NotNan`] implements `Ord` and can be used as the number type of a tree in place of a
+//! primitive float, so that the results of tree construction and queries are always specified.
+
+use axgeom::num_traits::{AsPrimitive, Float, Num, One, Signed, Zero};
+use core::cmp::Ordering;
+use core::fmt;
+use core::ops::{Add, Div, Mul, Neg, Rem, Sub};
+
+///A float that is never `NaN`.
+///
+///Arithmetic that would produce a `NaN` (e.g. `inf - inf` or `0 / 0`) panics.
+///
+/// # Examples
+///
+///```
+/// use broccoli::notnan::NotNan;
+/// use broccoli::{bbox,rect};
+///
+/// let n = |a: f32| NotNan::new(a).unwrap();
+/// assert!(NotNan::new(f32::NAN).is_none());
+///
+/// let mut bots = [bbox(rect(n(0.0),n(10.0),n(0.0),n(10.0)),()),bbox(rect(n(5.0),n(15.0),n(5.0),n(15.0)),())];
+/// let mut tree = broccoli::new(&mut bots);
+///
+/// let mut num = 0;
+/// use broccoli::prelude::*;
+/// tree.find_colliding_pairs_mut(|_,_|num+=1);
+/// assert_eq!(num,1);
+///```
+#[derive(Copy, Clone, Default, PartialEq)]
+pub struct NotNan<F>(F);
+
+impl<F: Float> NotNan<F> {
+    ///Returns `None` if `a` is `NaN`.
+    #[must_use]
+    pub fn new(a: F) -> Option<NotNan<F>> {
+        if a.is_nan() {
+            None
+        } else {
+            Some(NotNan(a))
+        }
+    }
+
+    ///Returns the inner float.
+    #[must_use]
+    pub fn into_inner(self) -> F {
+        self.0
+    }
+
+    fn checked(a: F) -> NotNan<F> {
+        NotNan::new(a).expect("arithmetic produced NaN")
+    }
+}
+
+impl<F: Float> Eq for NotNan<F> {}
+
+impl<F: Float> PartialOrd for NotNan<F> {
+    fn partial_cmp(&self, other: &Self) -> Option<Ordering> {
+        Some(self.cmp(other))
+    }
+}
+
+impl<F: Float> Ord for NotNan<F> {
+    fn cmp(&self, other: &Self) -> Ordering {
+        //Neither is NaN so they are always comparable.
+        self.0.partial_cmp(&other.0).unwrap()
+    }
+}
+
+impl<F: fmt::Debug> fmt::Debug for NotNan<F> {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        self.0.fmt(f)
+    }
+}
+
+impl<F: fmt::Display> fmt::Display for NotNan<F> {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        self.0.fmt(f)
+    }
+}
+
+macro_rules! impl_op {
+    ($tr:ident, $func:ident) => {
+        impl<F: Float> $tr for NotNan<F> {
+            type Output = NotNan<F>;
+            #[inline(always)]
+            fn $func(self, other: NotNan<F>) -> NotNan<F> {
+                NotNan::checked(self.0.$func(other.0))
+            }
+        }
+    };
+}
+impl_op!(Add, add);
+impl_op!(Sub, sub);
+impl_op!(Mul, mul);
+impl_op!(Div, div);
+impl_op!(Rem, rem);
+
+impl<F: Float> Neg for NotNan<F> {
+    type Output = NotNan<F>;
+    #[inline(always)]
+    fn neg(self) -> NotNan<F> {
+        NotNan(-self.0)
+    }
+}
+
+impl<F: Float> Zero for NotNan<F> {
+    fn zero() -> Self {
+        NotNan(F::zero())
+    }
+    fn is_zero(&self) -> bool {
+        self.0.is_zero()
+    }
+}
+
+impl<F: Float> One for NotNan<F> {
+    fn one() -> Self {
+        NotNan(F::one())
+    }
+}
+
+impl<F: Float> Num for NotNan<F> {
+    type FromStrRadixErr = F::FromStrRadixErr;
+
+    ///Panics if the string parses to `NaN`.
+    fn from_str_radix(s: &str, radix: u32) -> Result<Self, Self::FromStrRadixErr> {
+        F::from_str_radix(s, radix).map(NotNan::checked)
+    }
+}
+
+impl<F: Float + Signed> Signed for NotNan<F> {
+    fn abs(&self) -> Self {
+        NotNan(Signed::abs(&self.0))
+    }
+    fn abs_sub(&self, other: &Self) -> Self {
+        NotNan::checked(Signed::abs_sub(&self.0, &other.0))
+    }
+    fn signum(&self) -> Self {
+        NotNan(Signed::signum(&self.0))
+    }
+    fn is_positive(&self) -> bool {
+        Signed::is_positive(&self.0)
+    }
+    fn is_negative(&self) -> bool {
+        Signed::is_negative(&self.0)
+    }
+}
+
+impl<F: Float + AsPrimitive<B>, B: 'static + Copy> AsPrimitive<B> for NotNan<F> {
+    fn as_(self) -> B {
+        self.0.as_()
+    }
+}
@@ -0,0 +1,251 @@
+//! Per node aggregate module
+//!
+//! Compute a user defined monoid for every subtree (e.g. an element count, a bounding rect,
+//! a total mass or a maximum radius) bottom up, and then use it to prune custom queries.
+
+use crate::query::inner_prelude::*;
+
+///The functions that define an aggregate.
+///
+///`combine` must be associative for the aggregate of a subtree to be independent of the shape of the tree.
+pub trait Aggregate {
+    type T: Aabb;
+    type Agg: Default;
+
+    ///Compute the aggregate of the elements of a single node.
+    fn node(&mut self, elems: &[Self::T]) -> Self::Agg;
+
+    ///Combine two aggregates.
+    fn combine(&mut self, a: &Self::Agg, b: &Self::Agg) -> Self::Agg;
+}
+
+use crate::Tree;
+
+///Construct an object that implements [`Aggregate`] from closures.
+///We pass the tree so that we can infer the type of `T`.
+///
+/// `node` is a function that computes the aggregate of the elements in one node.
+///
+/// `combine` is a function that combines two aggregates.
+///
+/// `acc` is a user defined object that is passed to every call to either
+/// the `node` or `combine` functions.
+pub fn from_closure<Acc, T: Aabb, M: Default>(
+    _tree: &Tree<T>,
+    acc: Acc,
+    node: impl FnMut(&mut Acc, &[T]) -> M,
+    combine: impl FnMut(&mut Acc, &M, &M) -> M,
+) -> impl Aggregate<T = T, Agg = M> {
+    struct AggregateClosure<T, M, Acc, B, C> {
+        _p: PhantomData<(T, M)>,
+        acc: Acc,
+        node: B,
+        combine: C,
+    }
+
+    impl<T: Aabb, M: Default, Acc, B, C> Aggregate for AggregateClosure<T, M, Acc, B, C>
+    where
+        B: FnMut(&mut Acc, &[T]) -> M,
+        C: FnMut(&mut Acc, &M, &M) -> M,
+    {
+        type T = T;
+        type Agg = M;
+
+        fn node(&mut self, elems: &[T]) -> M {
+            (self.node)(&mut self.acc, elems)
+        }
+
+        fn combine(&mut self, a: &M, b: &M) -> M {
+            (self.combine)(&mut self.acc, a, b)
+        }
+    }
+
+    AggregateClosure {
+        _p: PhantomData,
+        acc,
+        node,
+        combine,
+    }
+}
+
+fn num_nodes<V: FixedDepthVisitor>(vistr: &V) -> usize {
+    (1 << vistr.get_height()) - 1
+}
+
+//Split a pre-order buffer into the root, and the left and right subtrees.
+fn split<M>(buffer: &mut [M]) -> (&mut M, Option<[&mut [M]; 2]>) {
+    let (first, rest) = buffer.split_first_mut().unwrap();
+    if rest.is_empty() {
+        (first, None)
+    } else {
+        let (left, right) = rest.split_at_mut(rest.len() / 2);
+        (first, Some([left, right]))
+    }
+}
+
+fn build_recc<T: Aabb, A: Aggregate<T = T>>(
+    vistr: Vistr<Node<T>>,
+    buffer: &mut [A::Agg],
+    agg: &mut A,
+) {
+    let (nn, rest) = vistr.next();
+    let (first, children) = split(buffer);
+    let own = agg.node(&nn.range);
+    *first = match (rest, children) {
+        (Some([left, right]), Some([lb, rb])) => {
+            build_recc(left, lb, agg);
+            build_recc(right, rb, agg);
+            let m = agg.combine(&lb[0], &rb[0]);
+            agg.combine(&m, &own)
+        }
+        _ => own,
+    };
+}
+
+///Compute the aggregate of every subtree and write it into `buffer`,
+///in the same pre-order as [`Tree::get_nodes`].
+///
+///Panics if the length of `buffer` does not equal the number of nodes.
+pub fn build_into<'a, Q: Queries<'a> + ?Sized, A: Aggregate<T = Q::T>>(
+    tree: &Q,
+    agg: &mut A,
+    buffer: &mut [A::Agg],
+) {
+    let vistr = tree.vistr();
+    assert_eq!(
+        buffer.len(),
+        num_nodes(&vistr),
+        "there must be exactly one aggregate per node"
+    );
+    build_recc(vistr, buffer, agg);
+}
+
+///Compute the aggregate of every subtree.
+pub fn build<'a, Q: Queries<'a> + ?Sized, A: Aggregate<T = Q::T>>(
+    tree: &Q,
+    agg: &mut A,
+) -> NodeAggregates<A::Agg> {
+    let mut inner: Vec<_> = (0..num_nodes(&tree.vistr()))
+        .map(|_| Default::default())
+        .collect();
+    build_into(tree, agg, &mut inner);
+    NodeAggregates { inner }
+}
+
+///The aggregates of every subtree of a tree, in the same pre-order as [`Tree::get_nodes`].
+///
+///The aggregates are not updated when the elements of the tree are modified.
+pub struct NodeAggregates<M> {
+    inner: Vec<M>,
+}
+
+impl<M> NodeAggregates<M> {
+    ///The aggregate of every subtree in pre-order.
+    #[must_use]
+    pub fn get(&self) -> &[M] {
+        &self.inner
+    }
+
+    ///The aggregate of the whole tree.
+    #[must_use]
+    pub fn root(&self) -> &M {
+        &self.inner[0]
+    }
+
+    ///Visit every element, skipping subtrees for which `descend` returns false.
+    ///
+    ///Panics if the tree does not have the same number of nodes as when the aggregates were built.
+    ///
+    /// # Examples
+    ///
+    ///```
+    /// use broccoli::{bbox,rect};
+    /// use broccoli::query::aggregate;
+    ///
+    /// let mut bots = [bbox(rect(0,10,0,10),8),bbox(rect(20,30,0,10),2),bbox(rect(40,50,0,10),3)];
+    /// let tree = broccoli::new(&mut bots);
+    ///
+    /// //The largest value in each subtree.
+    /// let mut handler = aggregate::from_closure(&tree,(),
+    ///     |_,elems|elems.iter().map(|a|a.inner).max().unwrap_or(0),
+    ///     |_,a,b|*a.max(b)
+    /// );
+    /// let aggs = aggregate::build(&tree,&mut handler);
+    /// assert_eq!(*aggs.root(),8);
+    ///
+    /// let mut found = Vec::new();
+    /// aggs.for_all_pruned(&tree,|max|*max>=3,|a|if a.inner>=3{ found.push(a.inner) });
+    /// found.sort();
+    /// assert_eq!(found,vec![3,8]);
+    ///```
+    pub fn for_all_pruned<'a, 'b, Q: Queries<'a> + ?Sized>(
+        &self,
+        tree: &'b Q,
+        mut descend: impl FnMut(&M) -> bool,
+        mut func: impl FnMut(&'b Q::T),
+    ) where
+        'a: 'b,
+    {
+        fn recc<'a, 'b: 'a, T: Aabb, M>(
+            vistr: Vistr<'a, Node<'b, T>>,
+            aggs: &[M],
+            descend: &mut impl FnMut(&M) -> bool,
+            func: &mut impl FnMut(&'a T),
+        ) {
+            let (first, rest) = aggs.split_first().unwrap();
+            if !descend(first) {
+                return;
+            }
+            let (nn, children) = vistr.next();
+            for a in nn.range.iter() {
+                func(a);
+            }
+            if let Some([left, right]) = children {
+                let (l, r) = rest.split_at(rest.len() / 2);
+                recc(left, l, descend, func);
+                recc(right, r, descend, func);
+            }
+        }
+        let vistr = tree.vistr();
+        assert_eq!(num_nodes(&vistr), self.inner.len());
+        recc(vistr, &self.inner, &mut descend, &mut func);
+    }
+
+    ///Visit every element mutably, skipping subtrees for which `descend` returns false.
+    ///
+    ///Panics if the tree does not have the same number of nodes as when the aggregates were built.
+    pub fn for_all_pruned_mut<'a, 'b, Q: Queries<'a> + ?Sized>(
+        &self,
+        tree: &'b mut Q,
+        mut descend: impl FnMut(&M) -> bool,
+        mut func: impl FnMut(PMut<'b, Q::T>),
+    ) where
+        'a: 'b,
+    {
+        fn recc<'a, 'b: 'a, T: Aabb, M>(
+            vistr: VistrMut<'a, Node<'b, T>>,
+            aggs: &[M],
+            descend: &mut impl FnMut(&M) -> bool,
+            func: &mut impl FnMut(PMut<'a, T>),
+        ) {
+            let (first, rest) = aggs.split_first().unwrap();
+            if !descend(first) {
+                return;
+            }
+            let (nn, children) = vistr.next();
+            for a in nn.into_range().iter_mut() {
+                func(a);
+            }
+            if let Some([left, right]) = children {
+                let (l, r) = rest.split_at(rest.len() / 2);
+                recc(left, l, descend, func);
+                recc(right, r, descend, func);
+            }
+        }
+        let vistr = tree.vistr_mut();
+        assert_eq!(num_nodes(&vistr), self.inner.len());
+        recc(vistr, &self.inner, &mut descend, &mut func);
+    }
+}
+
+use super::Queries;
@@ -0,0 +1,311 @@
+//! Closest pair query module
+
+use crate::query::inner_prelude::*;
+
+///The geometric functions that the user must provide.
+pub trait ClosestPair {
+    type T: Aabb<Num = Self::N>;
+    type N: Num;
+
+    ///User defined distance function from an element to an axis aligned line of infinite length.
+    ///It must never be bigger than the distance between the element and any element on the
+    ///other side of the line.
+    fn distance_to_aaline<A: Axis>(&mut self, a: &Self::T, axis: A, val: Self::N) -> Self::N;
+
+    ///User defined distance function between two elements.
+    fn distance_to_fine(&mut self, a: &Self::T, b: &Self::T) -> Self::N;
+}
+
+///Create a handler that treats each object as its aabb rectangle shape.
+///Distances are squared.
+pub fn default_rect_closest_pair<T: Aabb>(tree: &Tree<T>) -> impl ClosestPair<T = T, N = T::Num>
+where
+    T::Num: num_traits::Signed + num_traits::Zero,
+{
+    fn gap<N: Num + num_traits::Signed>(a: &Range<N>, b: &Range<N>) -> N {
+        if a.end < b.start {
+            b.start - a.end
+        } else if b.end < a.start {
+            a.start - b.end
+        } else {
+            N::zero()
+        }
+    }
+    fn line<N: Num + num_traits::Signed>(a: &Range<N>, val: N) -> N {
+        let d = gap(
+            a,
+            &Range {
+                start: val,
+                end: val,
+            },
+        );
+        d * d
+    }
+    from_closure(
+        tree,
+        (),
+        |_, a, b| {
+            let x = gap(&a.get().x, &b.get().x);
+            let y = gap(&a.get().y, &b.get().y);
+            x * x + y * y
+        },
+        |_, a, val| line(&a.get().x, val),
+        |_, a, val| line(&a.get().y, val),
+    )
+}
+
+use crate::Tree;
+///Construct an object that implements [`ClosestPair`] from closures.
+///We pass the tree so that we can infer the type of `T`.
+///
+/// `fine` is a function that gives the true distance between two elements.
+///
+/// `xline` is a function that gives the distance between an element and a axis aligned line
+///    that was a fixed x value and spans the y values.
+///
+/// `yline` is a function that gives the distance between an element and a axis aligned line
+///    that was a fixed y value and spans the x values.
+///
+/// `acc` is a user defined object that is passed to every call to either
+/// the `fine` or line functions.
+///
+pub fn from_closure<Acc, T: Aabb>(
+    _tree: &Tree<T>,
+    acc: Acc,
+    fine: impl FnMut(&mut Acc, &T, &T) -> T::Num,
+    xline: impl FnMut(&mut Acc, &T, T::Num) -> T::Num,
+    yline: impl FnMut(&mut Acc, &T, T::Num) -> T::Num,
+) -> impl ClosestPair<T = T, N = T::Num> {
+    ///Container of closures that implements [`ClosestPair`]
+    struct ClosestPairClosure<T: Aabb, Acc, C, D, E> {
+        pub _p: PhantomData<T>,
+        pub acc: Acc,
+        pub fine: C,
+        pub xline: D,
+        pub yline: E,
+    }
+
+    impl<T: Aabb, Acc, C, D, E> ClosestPair for ClosestPairClosure<T, Acc, C, D, E>
+    where
+        C: FnMut(&mut Acc, &T, &T) -> T::Num,
+        D: FnMut(&mut Acc, &T, T::Num) -> T::Num,
+        E: FnMut(&mut Acc, &T, T::Num) -> T::Num,
+    {
+        type T = T;
+        type N = T::Num;
+
+        fn distance_to_aaline<A: Axis>(&mut self, a: &Self::T, axis: A, val: Self::N) -> Self::N {
+            if axis.is_xaxis() {
+                (self.xline)(&mut self.acc, a, val)
+            } else {
+                (self.yline)(&mut self.acc, a, val)
+            }
+        }
+
+        fn distance_to_fine(&mut self, a: &Self::T, b: &Self::T) -> Self::N {
+            (self.fine)(&mut self.acc, a, b)
+        }
+    }
+    ClosestPairClosure {
+        _p: PhantomData,
+        acc,
+        fine,
+        xline,
+        yline,
+    }
+}
+
+/// Returned by closest_pair_mut
+#[derive(Debug)]
+pub struct ClosestPairResult<'a, T: Aabb> {
+    pub first: PMut<'a, T>,
+    pub second: PMut<'a, T>,
+    pub mag: T::Num,
+}
+
+struct Blap<'a, K: ClosestPair> {
+    knear: &'a mut K,
+    base: *const K::T,
+    //The index and distance of the closest pair found so far.
+    closest: Option<(usize, usize, K::N)>,
+}
+
+impl<'a, K: ClosestPair> Blap<'a, K> {
+    fn should_recurse<A: Axis>(&mut self, a: &K::T, line: (A, K::N)) -> bool {
+        match self.closest {
+            Some((_, _, m)) => self.knear.distance_to_aaline(a, line.0, line.1) < m,
+            None => true,
+        }
+    }
+
+    fn consider(&mut self, i: usize, a: &K::T, others: &[K::T]) {
+        for b in others.iter() {
+            let j = unsafe { (b as *const K::T).offset_from(self.base) } as usize;
+            //every pair is considered once from the element with the lower index.
+            if j <= i {
+                continue;
+            }
+            let dis = self.knear.distance_to_fine(a, b);
+            let closer = match self.closest {
+                Some((_, _, m)) => dis < m,
+                None => true,
+            };
+            if closer {
+                self.closest = Some((i, j, dis));
+            }
+        }
+    }
+}
+
+fn recc<T: Aabb, A: Axis, K: ClosestPair<N = T::Num, T = T>>(
+    axis: A,
+    vistr: Vistr<Node<T>>,
+    i: usize,
+    a: &T,
+    blap: &mut Blap<K>,
+) {
+    let (nn, rest) = vistr.next();
+    let handle_node = match rest {
+        Some([left, right]) => {
+            let div = match nn.div {
+                Some(b) => b,
+                None => return,
+            };
+
+            let line = (axis, div);
+
+            //recurse first. more likely closest is in a child.
+            if a.get().get_range(axis).start < div {
+                recc(axis.next(), left, i, a, blap);
+                if blap.should_recurse(a, line) {
+                    recc(axis.next(), right, i, a, blap);
+                }
+            } else {
+                recc(axis.next(), right, i, a, blap);
+                if blap.should_recurse(a, line) {
+                    recc(axis.next(), left, i, a, blap);
+                }
+            }
+
+            if !nn.range.is_empty() {
+                //Determine if we should handle this node or not.
+                let r = a.get().get_range(axis);
+                if r.end < nn.cont.start {
+                    blap.should_recurse(a, (axis, nn.cont.start))
+                } else if r.start > nn.cont.end {
+                    blap.should_recurse(a, (axis, nn.cont.end))
+                } else {
+                    true
+                }
+            } else {
+                false
+            }
+        }
+        None => true,
+    };
+
+    if handle_node {
+        blap.consider(i, a, &nn.range);
+    }
+}
+
+//Return the two elements at the specified indices where i<j.
+fn get_pair_mut<T>(arr: PMut<[T]>, i: usize, j: usize) -> (PMut<T>, PMut<T>) {
+    let (first, rest) = arr.truncate_from(i..).split_first_mut().unwrap();
+    (first, rest.get_index_mut(j - i - 1))
+}
+
+///Panics if a disconnect is detected between tree and naive queries.
+pub fn assert_closest_pair_mut<T: Aabb>(
+    tree: &mut Tree<T>,
+    knear: &mut impl ClosestPair<T = T, N = T::Num>,
+) where
+    T::Num: core::fmt::Debug,
+{
+    let res_naive = naive_closest_pair_mut(tree.get_elements_mut(), knear).map(|a| a.mag);
+    let res_dino = tree.closest_pair_mut(knear).map(|a| a.mag);
+    assert_eq!(res_naive, res_dino);
+}
+
+///Naive implementation
+pub fn naive_closest_pair_mut<'a, T: Aabb>(
+    elems: PMut<'a, [T]>,
+    knear: &mut impl ClosestPair<T = T, N = T::Num>,
+) -> Option<ClosestPairResult<'a, T>> {
+    let mut closest: Option<(usize, usize, T::Num)> = None;
+    for (i, a) in elems.iter().enumerate() {
+        for (j, b) in elems.iter().enumerate().skip(i + 1) {
+            let dis = knear.distance_to_fine(a, b);
+            let closer = match closest {
+                Some((_, _, m)) => dis < m,
+                None => true,
+            };
+            if closer {
+                closest = Some((i, j, dis));
+            }
+        }
+    }
+
+    closest.map(move |(i, j, mag)| {
+        let (first, second) = get_pair_mut(elems, i, j);
+        ClosestPairResult { first, second, mag }
+    })
+}
+
+use super::Queries;
+
+///Closest pair functions that can be called on a tree.
+pub trait ClosestPairQuery<'a>: Queries<'a> {
+    /// Find the two elements that are closest to each other.
+    /// Returns `None` if there are less than two elements.
+    /// In the event of ties, an arbitrary pair is returned.
+    ///
+    /// # Examples
+    ///
+    ///```
+    /// use broccoli::{prelude::*,bbox,rect};
+    ///
+    /// let mut bots = [bbox(rect(0,10,0,10),0),
+    ///               bbox(rect(40,50,40,50),1),
+    ///               bbox(rect(13,15,13,15),2)];
+    ///
+    /// let mut tree = broccoli::new(&mut bots);
+    ///
+    /// let mut handler = broccoli::query::closest_pair::default_rect_closest_pair(&tree);
+    ///
+    /// let res = tree.closest_pair_mut(&mut handler).unwrap();
+    ///
+    /// let mut inner = [*res.first.unpack_inner(),*res.second.unpack_inner()];
+    /// inner.sort();
+    /// assert_eq!(inner,[0,2]);
+    /// assert_eq!(res.mag,3*3+3*3);
+    ///```
+    #[must_use]
+    fn closest_pair_mut<'b, K: ClosestPair<T = Self::T, N = Self::Num>>(
+        &'b mut self,
+        ktrait: &mut K,
+    ) -> Option<ClosestPairResult<'b, Self::T>>
+    where
+        'a: 'b,
+    {
+        let closest = {
+            let elems = super::tools::get_elements(self.vistr());
+            let mut blap = Blap {
+                knear: ktrait,
+                base: elems.as_ptr(),
+                closest: None,
+            };
+
+            for (i, a) in elems.iter().enumerate() {
+                recc(default_axis(), self.vistr(), i, a, &mut blap);
+            }
+            blap.closest
+        };
+
+        closest.map(move |(i, j, mag)| {
+            let elems = super::tools::get_elements_mut(self.vistr_mut());
+            let (first, second) = get_pair_mut(elems, i, j);
+            ClosestPairResult { first, second, mag }
+        })
+    }
+}
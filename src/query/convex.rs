@@ -0,0 +1,367 @@
+//! Convex region query module
+
+use crate::query::inner_prelude::*;
+use num_traits::Signed;
+
+///A half-plane. A point `p` is inside of it if `normal.dot(p) <= offset`.
+#[derive(Copy, Clone, Debug)]
+pub struct HalfPlane<N> {
+    pub normal: Vec2<N>,
+    pub offset: N,
+}
+
+///A convex region made up of the intersection of a set of half-planes.
+#[derive(Clone, Debug)]
+pub struct ConvexRegion<N> {
+    planes: Vec<HalfPlane<N>>,
+    //The bounding rectangle of the region if it is bounded.
+    bound: Option<Rect<N>>,
+}
+
+impl<N: Num + Signed> ConvexRegion<N> {
+    ///Create a region from a set of half-planes. The region may be unbounded
+    ///(e.g. a cone), in which case an aabb that is near a corner of the region may be reported
+    ///as intersecting even though it is only inside of each half-plane individually.
+    pub fn from_half_planes(planes: Vec<HalfPlane<N>>) -> ConvexRegion<N> {
+        ConvexRegion {
+            planes,
+            bound: None,
+        }
+    }
+
+    ///Create a region from the vertices of a convex polygon in counter clockwise order.
+    ///The intersection test against aabbs is exact.
+    ///
+    ///Panics if no points are provided.
+    pub fn from_points(points: &[Vec2<N>]) -> ConvexRegion<N> {
+        let (first, rest) = points.split_first().expect("no points provided");
+        let mut bound = Rect {
+            x: Range {
+                start: first.x,
+                end: first.x,
+            },
+            y: Range {
+                start: first.y,
+                end: first.y,
+            },
+        };
+        for p in rest.iter() {
+            let r = Range {
+                start: p.x,
+                end: p.x,
+            };
+            super::tools::grow_range(&mut bound.x, &r);
+            let r = Range {
+                start: p.y,
+                end: p.y,
+            };
+            super::tools::grow_range(&mut bound.y, &r);
+        }
+
+        let planes = points
+            .iter()
+            .zip(points.iter().cycle().skip(1))
+            .map(|(a, b)| {
+                let normal = vec2(b.y - a.y, a.x - b.x);
+                HalfPlane {
+                    normal,
+                    offset: normal.x * a.x + normal.y * a.y,
+                }
+            })
+            .collect();
+
+        ConvexRegion {
+            planes,
+            bound: Some(bound),
+        }
+    }
+
+    ///The half-planes that make up this region.
+    pub fn planes(&self) -> &[HalfPlane<N>] {
+        &self.planes
+    }
+
+    ///Returns true if the rectangle touches the region.
+    pub fn intersects_rect(&self, rect: &Rect<N>) -> bool {
+        self.intersects_bounds(&Bounds {
+            x: (Some(rect.x.start), Some(rect.x.end)),
+            y: (Some(rect.y.start), Some(rect.y.end)),
+        })
+    }
+
+    fn intersects_bounds(&self, b: &Bounds<N>) -> bool {
+        if let Some(r) = &self.bound {
+            for (r, b) in [(&r.x, &b.x), (&r.y, &b.y)].iter() {
+                if let Some(end) = b.1 {
+                    if r.start > end {
+                        return false;
+                    }
+                }
+                if let Some(start) = b.0 {
+                    if r.end < start {
+                        return false;
+                    }
+                }
+            }
+        }
+
+        //The smallest value of `n*p` for a `p` in the range.
+        //None if the range is unbounded in that direction.
+        fn min_along<N: Num + Signed>(n: N, r: (Option<N>, Option<N>)) -> Option<N> {
+            if n.is_positive() {
+                r.0.map(|s| n * s)
+            } else if n.is_negative() {
+                r.1.map(|e| n * e)
+            } else {
+                Some(N::zero())
+            }
+        }
+
+        for p in self.planes.iter() {
+            if let (Some(a), Some(b)) = (min_along(p.normal.x, b.x), min_along(p.normal.y, b.y)) {
+                if a + b > p.offset {
+                    return false;
+                }
+            }
+        }
+        true
+    }
+}
+
+//A rectangle whose sides can be unbounded.
+#[derive(Copy, Clone)]
+struct Bounds<N> {
+    x: (Option<N>, Option<N>),
+    y: (Option<N>, Option<N>),
+}
+impl<N> Bounds<N> {
+    fn unbounded() -> Bounds<N> {
+        Bounds {
+            x: (None, None),
+            y: (None, None),
+        }
+    }
+    fn get_range_mut(&mut self, axis: impl Axis) -> &mut (Option<N>, Option<N>) {
+        if axis.is_xaxis() {
+            &mut self.x
+        } else {
+            &mut self.y
+        }
+    }
+}
+
+macro_rules! convex {
+    ($iterator:ty,$colsingle:ty,$get_bots:ident) => {
+        //Returns the bounds of the node's elements, and of its left and right children.
+        fn split_bounds<T: Aabb, A: Axis>(
+            axis: A,
+            node: &Node<T>,
+            bounds: Bounds<T::Num>,
+        ) -> (Bounds<T::Num>, Option<[Bounds<T::Num>; 2]>) {
+            let mut node_bounds = bounds;
+            *node_bounds.get_range_mut(axis) = (Some(node.cont.start), Some(node.cont.end));
+
+            let children = node.div.map(|div| {
+                let mut left = bounds;
+                left.get_range_mut(axis).1 = Some(div);
+                let mut right = bounds;
+                right.get_range_mut(axis).0 = Some(div);
+                [left, right]
+            });
+            (node_bounds, children)
+        }
+
+        fn handle_node<'a, T: Aabb, F: FnMut($colsingle)>(
+            nn: <$iterator as Visitor>::Item,
+            node_bounds: &Bounds<T::Num>,
+            region: &ConvexRegion<T::Num>,
+            func: &mut F,
+        ) where
+            T::Num: Signed,
+        {
+            if !nn.range.is_empty() && region.intersects_bounds(node_bounds) {
+                for i in $get_bots(nn) {
+                    if region.intersects_rect(i.get()) {
+                        func(i);
+                    }
+                }
+            }
+        }
+
+        fn convex_recurse<'a, A: Axis, T: Aabb, F: FnMut($colsingle)>(
+            axis: A,
+            m: $iterator,
+            region: &ConvexRegion<T::Num>,
+            bounds: Bounds<T::Num>,
+            func: &mut F,
+        ) where
+            T::Num: Signed,
+        {
+            let (nn, rest) = m.next();
+            let (node_bounds, children) = split_bounds(axis, &nn, bounds);
+            handle_node(nn, &node_bounds, region, func);
+
+            if let (Some([left, right]), Some([lb, rb])) = (rest, children) {
+                if region.intersects_bounds(&lb) {
+                    convex_recurse(axis.next(), left, region, lb, func);
+                }
+                if region.intersects_bounds(&rb) {
+                    convex_recurse(axis.next(), right, region, rb, func);
+                }
+            }
+        }
+
+        fn convex_recurse_par<'a, A: Axis, T: Aabb + Send + Sync, JJ: par::Joiner>(
+            axis: A,
+            par: JJ,
+            m: $iterator,
+            region: &ConvexRegion<T::Num>,
+            bounds: Bounds<T::Num>,
+            func: &(impl Fn($colsingle) + Send + Sync),
+        ) where
+            T::Num: Signed + Send + Sync,
+        {
+            let (nn, rest) = m.next();
+            let (node_bounds, children) = split_bounds(axis, &nn, bounds);
+            handle_node(nn, &node_bounds, region, &mut |a| func(a));
+
+            if let (Some([left, right]), Some([lb, rb])) = (rest, children) {
+                let l = region.intersects_bounds(&lb);
+                let r = region.intersects_bounds(&rb);
+                match par.next() {
+                    par::ParResult::Parallel([dleft, dright]) => {
+                        if l && r {
+                            rayon::join(
+                                || convex_recurse_par(axis.next(), dleft, left, region, lb, func),
+                                || convex_recurse_par(axis.next(), dright, right, region, rb, func),
+                            );
+                        } else if l {
+                            convex_recurse_par(axis.next(), dleft, left, region, lb, func);
+                        } else if r {
+                            convex_recurse_par(axis.next(), dright, right, region, rb, func);
+                        }
+                    }
+                    par::ParResult::Sequential(_) => {
+                        if l {
+                            convex_recurse(axis.next(), left, region, lb, &mut |a| func(a));
+                        }
+                        if r {
+                            convex_recurse(axis.next(), right, region, rb, &mut |a| func(a));
+                        }
+                    }
+                }
+            }
+        }
+    };
+}
+
+pub(super) mod mutable {
+    use super::*;
+    fn foo<'a, 'b: 'a, T: Aabb>(node: PMut<'a, Node<'b, T>>) -> PMut<'a, [T]> {
+        node.into_range()
+    }
+    convex!(VistrMut<'a, Node<T>>, PMut<'a, T>, foo);
+
+    pub fn for_all_intersect_convex_mut<'a, 'b: 'a, A: Axis, T: Aabb>(
+        axis: A,
+        vistr: VistrMut<'a, Node<'b, T>>,
+        region: &ConvexRegion<T::Num>,
+        mut closure: impl FnMut(PMut<'a, T>),
+    ) where
+        T::Num: Signed,
+    {
+        convex_recurse(axis, vistr, region, Bounds::unbounded(), &mut |a| {
+            closure(a)
+        });
+    }
+
+    pub fn for_all_intersect_convex_par_mut<'a, 'b: 'a, A: Axis, T: Aabb + Send + Sync>(
+        axis: A,
+        vistr: VistrMut<'a, Node<'b, T>>,
+        region: &ConvexRegion<T::Num>,
+        closure: impl Fn(PMut<'a, T>) + Send + Sync,
+    ) where
+        T::Num: Signed + Send + Sync,
+    {
+        let par = par::ParallelBuilder::new().build_for_tree_of_height(vistr.get_height());
+        convex_recurse_par(axis, par, vistr, region, Bounds::unbounded(), &closure);
+    }
+}
+
+pub(super) mod constant {
+    use super::*;
+    fn foo<'a, 'b: 'a, T: Aabb>(node: &'a Node<'b, T>) -> &'a [T] {
+        &node.range
+    }
+    convex!(Vistr<'a, Node<T>>, &'a T, foo);
+
+    pub fn for_all_intersect_convex<'a, 'b: 'a, A: Axis, T: Aabb>(
+        axis: A,
+        vistr: Vistr<'a, Node<'b, T>>,
+        region: &ConvexRegion<T::Num>,
+        mut closure: impl FnMut(&'a T),
+    ) where
+        T::Num: Signed,
+    {
+        convex_recurse(axis, vistr, region, Bounds::unbounded(), &mut |a| {
+            closure(a)
+        });
+    }
+
+    pub fn for_all_intersect_convex_par<'a, 'b: 'a, A: Axis, T: Aabb + Send + Sync>(
+        axis: A,
+        vistr: Vistr<'a, Node<'b, T>>,
+        region: &ConvexRegion<T::Num>,
+        closure: impl Fn(&'a T) + Send + Sync,
+    ) where
+        T::Num: Signed + Send + Sync,
+    {
+        let par = par::ParallelBuilder::new()
+            .build_for_tree_of_height(compt::FixedDepthVisitor::get_height(&vistr));
+        convex_recurse_par(axis, par, vistr, region, Bounds::unbounded(), &closure);
+    }
+}
+
+///Naive implementation
+pub fn naive_for_all_intersect_convex_mut<'a, T: Aabb>(
+    bots: PMut<'a, [T]>,
+    region: &ConvexRegion<T::Num>,
+    mut closure: impl FnMut(PMut<'a, T>),
+) where
+    T::Num: Signed,
+{
+    for b in bots.iter_mut() {
+        if region.intersects_rect(b.get()) {
+            closure(b);
+        }
+    }
+}
+
+///Panics if a disconnect is detected between tree and naive queries.
+pub fn assert_for_all_intersect_convex_mut<T: Aabb>(
+    tree: &mut crate::Tree<T>,
+    region: &ConvexRegion<T::Num>,
+) where
+    T::Num: Signed,
+{
+    use crate::query::rect::RectQuery;
+    use core::ops::Deref;
+    fn into_ptr_usize<T>(a: &T) -> usize {
+        a as *const T as usize
+    }
+    let mut res_dino = Vec::new();
+    tree.for_all_intersect_convex_mut(region, |a| {
+        res_dino.push(into_ptr_usize(a.deref()));
+    });
+
+    let mut res_naive = Vec::new();
+    naive_for_all_intersect_convex_mut(tree.get_elements_mut(), region, |a| {
+        res_naive.push(into_ptr_usize(a.deref()));
+    });
+
+    res_dino.sort_unstable();
+    res_naive.sort_unstable();
+
+    assert_eq!(res_naive.len(), res_dino.len());
+    assert!(res_naive.iter().eq(res_dino.iter()));
+}
@@ -0,0 +1,274 @@
+//! Farthest query module
+
+use crate::query::inner_prelude::*;
+
+///The geometric functions that the user must provide.
+pub trait Farthest {
+    type T: Aabb<Num = Self::N>;
+    type N: Num;
+
+    ///User defined distance function from a point to the farthest point of a rectangle.
+    ///It must never be smaller than the distance between the point and any element inside the rectangle.
+    fn distance_to_rect_far(&mut self, point: Vec2<Self::N>, rect: &Rect<Self::N>) -> Self::N;
+
+    ///User defined expensive distance function. Here the user can return fine-grained distance
+    ///of the shape contained in T instead of its bounding box.
+    fn distance_to_fine(&mut self, point: Vec2<Self::N>, a: PMut<Self::T>) -> Self::N;
+}
+
+///Create a handler that treats each object as its aabb rectangle shape.
+///Distances are squared and measured to the farthest corner of each rectangle.
+pub fn default_rect_farthest<T: Aabb>(tree: &Tree<T>) -> impl Farthest<T = T, N = T::Num>
+where
+    T::Num: num_traits::Signed,
+{
+    fn far<N: Num + num_traits::Signed>(point: Vec2<N>, rect: &Rect<N>) -> N {
+        fn max<N: Num + num_traits::Signed>(a: N, r: &Range<N>) -> N {
+            let x = (a - r.start).abs();
+            let y = (a - r.end).abs();
+            if x > y {
+                x
+            } else {
+                y
+            }
+        }
+        let x = max(point.x, &rect.x);
+        let y = max(point.y, &rect.y);
+        x * x + y * y
+    }
+    from_closure(
+        tree,
+        (),
+        |_, point, rect| far(point, rect),
+        |_, point, a| far(point, a.get()),
+    )
+}
+
+use crate::Tree;
+///Construct an object that implements [`Farthest`] from closures.
+///We pass the tree so that we can infer the type of `T`.
+///
+/// `far` is a function that gives the distance between the `point`
+/// and the farthest point of a axis aligned rectangle. This function
+/// is used as a conservative estimate to prune out whole subtrees.
+///
+/// `fine` is a function that gives the true distance between the `point`
+/// and the specified tree element.
+///
+/// `acc` is a user defined object that is passed to every call to either
+/// the `fine` or `far` functions.
+///
+pub fn from_closure<Acc, T: Aabb>(
+    _tree: &Tree<T>,
+    acc: Acc,
+    far: impl FnMut(&mut Acc, Vec2<T::Num>, &Rect<T::Num>) -> T::Num,
+    fine: impl FnMut(&mut Acc, Vec2<T::Num>, PMut<T>) -> T::Num,
+) -> impl Farthest<T = T, N = T::Num> {
+    ///Container of closures that implements [`Farthest`]
+    struct FarthestClosure<T: Aabb, Acc, B, C> {
+        pub _p: PhantomData<T>,
+        pub acc: Acc,
+        pub far: B,
+        pub fine: C,
+    }
+
+    impl<T: Aabb, Acc, B, C> Farthest for FarthestClosure<T, Acc, B, C>
+    where
+        B: FnMut(&mut Acc, Vec2<T::Num>, &Rect<T::Num>) -> T::Num,
+        C: FnMut(&mut Acc, Vec2<T::Num>, PMut<T>) -> T::Num,
+    {
+        type T = T;
+        type N = T::Num;
+
+        fn distance_to_rect_far(&mut self, point: Vec2<Self::N>, rect: &Rect<Self::N>) -> Self::N {
+            (self.far)(&mut self.acc, point, rect)
+        }
+
+        fn distance_to_fine(&mut self, point: Vec2<Self::N>, bot: PMut<Self::T>) -> Self::N {
+            (self.fine)(&mut self.acc, point, bot)
+        }
+    }
+    FarthestClosure {
+        _p: PhantomData,
+        acc,
+        far,
+        fine,
+    }
+}
+
+/// Returned by farthest_mut
+#[derive(Debug)]
+pub struct FarthestResult<'a, T: Aabb> {
+    pub bot: PMut<'a, T>,
+    pub mag: T::Num,
+}
+
+struct Blap<'a, 'b, K: Farthest> {
+    knear: &'b mut K,
+    point: Vec2<K::N>,
+    farthest: Option<FarthestResult<'a, K::T>>,
+}
+
+impl<'a, 'b, K: Farthest> Blap<'a, 'b, K> {
+    fn should_recurse(&mut self, rect: &Rect<K::N>) -> bool {
+        match &self.farthest {
+            Some(f) => {
+                let m = f.mag;
+                self.knear.distance_to_rect_far(self.point, rect) > m
+            }
+            None => true,
+        }
+    }
+
+    fn consider(&mut self, mut bot: PMut<'a, K::T>) {
+        let dis = self.knear.distance_to_fine(self.point, bot.borrow_mut());
+        let farther = match &self.farthest {
+            Some(f) => dis > f.mag,
+            None => true,
+        };
+        if farther {
+            self.farthest = Some(FarthestResult { bot, mag: dis });
+        }
+    }
+}
+
+fn recc<'a, 'b: 'a, T: Aabb, A: Axis, K: Farthest<N = T::Num, T = T>>(
+    axis: A,
+    vistr: VistrMut<'a, Node<'b, T>>,
+    rect: Rect<T::Num>,
+    blap: &mut Blap<'a, '_, K>,
+) {
+    let (nn, rest) = vistr.next();
+    match rest {
+        Some([left, right]) => {
+            let div = match nn.div {
+                Some(b) => b,
+                None => return,
+            };
+
+            let (lrect, rrect) = rect.subdivide(axis, div);
+
+            //recurse first on the side further away from the point.
+            if *blap.point.get_axis(axis) < div {
+                if blap.should_recurse(&rrect) {
+                    recc(axis.next(), right, rrect, blap);
+                }
+                if blap.should_recurse(&lrect) {
+                    recc(axis.next(), left, lrect, blap);
+                }
+            } else {
+                if blap.should_recurse(&lrect) {
+                    recc(axis.next(), left, lrect, blap);
+                }
+                if blap.should_recurse(&rrect) {
+                    recc(axis.next(), right, rrect, blap);
+                }
+            }
+
+            if !nn.range.is_empty() {
+                let mut node_rect = rect;
+                *node_rect.get_range_mut(axis) = nn.cont;
+                if blap.should_recurse(&node_rect) {
+                    for bot in nn.into_range().iter_mut() {
+                        blap.consider(bot);
+                    }
+                }
+            }
+        }
+        None => {
+            for bot in nn.into_range().iter_mut() {
+                blap.consider(bot);
+            }
+        }
+    }
+}
+
+///Panics if a disconnect is detected between tree and naive queries.
+pub fn assert_farthest_mut<T: Aabb>(
+    tree: &mut Tree<T>,
+    point: Vec2<T::Num>,
+    knear: &mut impl Farthest<T = T, N = T::Num>,
+) where
+    T::Num: core::fmt::Debug,
+{
+    let res_naive = naive_farthest_mut(tree.get_elements_mut(), point, knear).map(|a| a.mag);
+    let res_dino = tree.farthest_mut(point, knear).map(|a| a.mag);
+    assert_eq!(res_naive, res_dino);
+}
+
+///Naive implementation
+pub fn naive_farthest_mut<'a, T: Aabb>(
+    elems: PMut<'a, [T]>,
+    point: Vec2<T::Num>,
+    knear: &mut impl Farthest<T = T, N = T::Num>,
+) -> Option<FarthestResult<'a, T>> {
+    let mut blap = Blap {
+        knear,
+        point,
+        farthest: None,
+    };
+    for b in elems.iter_mut() {
+        blap.consider(b);
+    }
+    blap.farthest
+}
+
+use super::Queries;
+
+///Farthest functions that can be called on a tree.
+pub trait FarthestQuery<'a>: Queries<'a> {
+    /// Find the element farthest away from the specified `point`.
+    /// Returns `None` if the tree is empty.
+    /// In the event of ties, an arbitrary element is returned.
+    ///
+    /// # Examples
+    ///
+    ///```
+    /// use broccoli::{prelude::*,bbox,rect};
+    /// use axgeom::vec2;
+    ///
+    /// let mut bots = [bbox(rect(0,10,0,10),0),
+    ///               bbox(rect(40,50,40,50),1),
+    ///               bbox(rect(13,15,13,15),2)];
+    ///
+    /// let mut tree = broccoli::new(&mut bots);
+    ///
+    /// let mut handler = broccoli::query::farthest::default_rect_farthest(&tree);
+    ///
+    /// let res = tree.farthest_mut(vec2(0,0),&mut handler).unwrap();
+    ///
+    /// assert_eq!(*res.bot.unpack_inner(),1);
+    /// assert_eq!(res.mag,50*50+50*50);
+    ///```
+    #[must_use]
+    fn farthest_mut<'b, K: Farthest<T = Self::T, N = Self::Num>>(
+        &'b mut self,
+        point: Vec2<Self::Num>,
+        ktrait: &mut K,
+    ) -> Option<FarthestResult<'b, Self::T>>
+    where
+        'a: 'b,
+    {
+        //The dividers only bound a subtree on one side,
+        //so start off with a rectangle that bounds every element.
+        let rect = {
+            let mut elems = super::tools::get_elements(self.vistr()).iter();
+            let mut rect = *elems.next()?.get();
+            for a in elems {
+                super::tools::grow_range(&mut rect.x, &a.get().x);
+                super::tools::grow_range(&mut rect.y, &a.get().y);
+            }
+            rect
+        };
+
+        let mut blap = Blap {
+            knear: ktrait,
+            point,
+            farthest: None,
+        };
+
+        recc(default_axis(), self.vistr_mut(), rect, &mut blap);
+
+        blap.farthest
+    }
+}
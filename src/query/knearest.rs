@@ -168,18 +168,28 @@ struct ClosestCand<'a, T: Aabb> {
     curr_num: usize,
     //The max number of different distances.
     num: usize,
+    //Bots further away than this are never considered.
+    max_distance: Option<T::Num>,
 }
 impl<'a, T: Aabb> ClosestCand<'a, T> {
     //First is the closest
     fn into_sorted(self) -> Vec<KnearestResult<'a, T>> {
         self.bots
     }
-    fn new(num: usize) -> ClosestCand<'a, T> {
+    fn new(num: usize, max_distance: Option<T::Num>) -> ClosestCand<'a, T> {
         let bots = Vec::with_capacity(num);
         ClosestCand {
             bots,
             num,
             curr_num: 0,
+            max_distance,
+        }
+    }
+
+    fn is_out_of_range(&self, dis: T::Num) -> bool {
+        match self.max_distance {
+            Some(max) => dis > max,
+            None => false,
         }
     }
 
@@ -190,6 +200,9 @@ impl<'a, T: Aabb> ClosestCand<'a, T> {
         mut curr_bot: PMut<'a, T>,
     ) -> bool {
         if let Some(long_dis) = knear.distance_to_broad(*point, curr_bot.borrow_mut()) {
+            if self.is_out_of_range(long_dis) {
+                return false;
+            }
             if self.curr_num == self.num {
                 if let Some(l) = self.bots.last() {
                     if long_dis > l.mag {
@@ -200,6 +213,10 @@ impl<'a, T: Aabb> ClosestCand<'a, T> {
         }
         let curr_dis = knear.distance_to_fine(*point, curr_bot.borrow_mut());
 
+        if self.is_out_of_range(curr_dis) {
+            return false;
+        }
+
         if self.curr_num < self.num {
             let arr = &mut self.bots;
 
@@ -226,8 +243,8 @@ impl<'a, T: Aabb> ClosestCand<'a, T> {
             for i in 0..arr.len() {
                 if curr_dis < arr[i].mag {
                     let v = arr.pop().unwrap();
-                    loop {
-                        if arr[arr.len() - 1].mag == v.mag {
+                    while let Some(l) = arr.last() {
+                        if l.mag == v.mag {
                             arr.pop().unwrap();
                         } else {
                             break;
@@ -277,30 +294,57 @@ impl<'a, T: Aabb> ClosestCand<'a, T> {
     }
 }
 
-struct Blap<'a, K: Knearest> {
+struct Blap<'a, K: Knearest, F> {
     knear: K,
     point: Vec2<K::N>,
     closest: ClosestCand<'a, K::T>,
+    filter: F,
 }
 
-impl<'a, K: Knearest> Blap<'a, K> {
+impl<'a, K: Knearest, F> Blap<'a, K, F> {
     fn should_recurse<A: Axis>(&mut self, line: (A, K::N)) -> bool {
-        if let Some(m) = self.closest.full_and_max_distance() {
-            let dis = self.knear.distance_to_aaline(self.point, line.0, line.1);
-            dis < m
-        } else {
-            true
+        let full = self.closest.full_and_max_distance();
+        if full.is_none() && self.closest.max_distance.is_none() {
+            return true;
+        }
+
+        let dis = self.knear.distance_to_aaline(self.point, line.0, line.1);
+        let closer = match full {
+            Some(m) => dis < m,
+            None => true,
+        };
+        closer && !self.closest.is_out_of_range(dis)
+    }
+
+    //Returns false if no element inside of the subtree bounds can be closer.
+    fn should_recurse_bounds(&mut self, bounds: &Option<Rect<K::N>>) -> bool {
+        let b = match bounds {
+            Some(b) => *b,
+            None => return false,
+        };
+        self.should_recurse_range(axgeom::XAXIS, &b.x)
+            && self.should_recurse_range(axgeom::YAXIS, &b.y)
+    }
+
+    fn should_recurse_range<A: Axis>(&mut self, axis: A, range: &Range<K::N>) -> bool {
+        match range.contains_ext(*self.point.get_axis(axis)) {
+            core::cmp::Ordering::Less => self.should_recurse((axis, range.start)),
+            core::cmp::Ordering::Greater => self.should_recurse((axis, range.end)),
+            core::cmp::Ordering::Equal => true,
         }
     }
 }
 
-fn recc<'a, 'b: 'a, T: Aabb, A: Axis, K: Knearest<N = T::Num, T = T>>(
+fn recc<'a, 'b: 'a, T: Aabb, A: Axis, K: Knearest<N = T::Num, T = T>, F: FnMut(&T) -> bool>(
     axis: A,
     stuff: LevelIter<VistrMut<'a, Node<'b, T>>>,
-    blap: &mut Blap<'a, K>,
+    blap: &mut Blap<'a, K, F>,
 ) {
     let ((_depth, nn), rest) = stuff.next();
     //let nn = nn.get_mut();
+    if !blap.should_recurse_bounds(&nn.bounds) {
+        return;
+    }
     let handle_node = match rest {
         Some([left, right]) => {
             let div = match nn.div {
@@ -340,7 +384,9 @@ fn recc<'a, 'b: 'a, T: Aabb, A: Axis, K: Knearest<N = T::Num, T = T>>(
     if handle_node {
         for bot in nn.into_range().iter_mut() {
             //let dis_sqr = blap.knear.didstance_to_fine(blap.point, bot.as_ref());
-            blap.closest.consider(&blap.point, &mut blap.knear, bot);
+            if (blap.filter)(&bot) {
+                blap.closest.consider(&blap.point, &mut blap.knear, bot);
+            }
         }
     }
 }
@@ -352,6 +398,11 @@ pub struct KResult<'a, T: Aabb> {
 }
 
 impl<'a, T: Aabb> KResult<'a, T> {
+    //`inner` is sorted closest first, and has `num_entires` unique distances.
+    pub(crate) fn new(num_entires: usize, inner: Vec<KnearestResult<'a, T>>) -> Self {
+        KResult { num_entires, inner }
+    }
+
     ///Iterators over each group of ties starting with the closest.
     ///All the elements in one group have the same distance.
     #[inline(always)]
@@ -391,6 +442,18 @@ pub fn assert_k_nearest_mut<T: Aabb>(
     point: Vec2<T::Num>,
     num: usize,
     knear: &mut impl Knearest<T = T, N = T::Num>,
+) {
+    assert_k_nearest_filter_mut(tree, point, num, knear, |_| true, None)
+}
+
+///Panics if a disconnect is detected between tree and naive queries.
+pub fn assert_k_nearest_filter_mut<T: Aabb>(
+    tree: &mut Tree<T>,
+    point: Vec2<T::Num>,
+    num: usize,
+    knear: &mut impl Knearest<T = T, N = T::Num>,
+    mut filter: impl FnMut(&T) -> bool,
+    max_distance: Option<T::Num>,
 ) {
     let bots = tree.get_elements_mut();
     use core::ops::Deref;
@@ -398,13 +461,14 @@ pub fn assert_k_nearest_mut<T: Aabb>(
     fn into_ptr_usize<T>(a: &T) -> usize {
         a as *const T as usize
     }
-    let mut res_naive = naive_k_nearest_mut(bots, point, num, knear)
-        .into_vec()
-        .drain(..)
-        .map(|a| (into_ptr_usize(a.bot.deref()), a.mag))
-        .collect::<Vec<_>>();
+    let mut res_naive =
+        naive_k_nearest_filter_mut(bots, point, num, knear, &mut filter, max_distance)
+            .into_vec()
+            .drain(..)
+            .map(|a| (into_ptr_usize(a.bot.deref()), a.mag))
+            .collect::<Vec<_>>();
 
-    let r = tree.k_nearest_mut(point, num, knear);
+    let r = tree.k_nearest_filter_mut(point, num, knear, filter, max_distance);
     let mut res_dino: Vec<_> = r
         .into_vec()
         .drain(..)
@@ -425,10 +489,24 @@ pub fn naive_k_nearest_mut<'a, T: Aabb>(
     num: usize,
     k: &mut impl Knearest<T = T, N = T::Num>,
 ) -> KResult<'a, T> {
-    let mut closest = ClosestCand::new(num);
+    naive_k_nearest_filter_mut(elems, point, num, k, |_| true, None)
+}
+
+///Naive implementation
+pub fn naive_k_nearest_filter_mut<'a, T: Aabb>(
+    elems: PMut<'a, [T]>,
+    point: Vec2<T::Num>,
+    num: usize,
+    k: &mut impl Knearest<T = T, N = T::Num>,
+    mut filter: impl FnMut(&T) -> bool,
+    max_distance: Option<T::Num>,
+) -> KResult<'a, T> {
+    let mut closest = ClosestCand::new(num, max_distance);
 
     for b in elems.iter_mut() {
-        closest.consider(&point, k, b);
+        if filter(&b) {
+            closest.consider(&point, k, b);
+        }
     }
 
     let num_entires = closest.curr_num;
@@ -440,6 +518,405 @@ pub fn naive_k_nearest_mut<'a, T: Aabb>(
 
 use super::Queries;
 
+//The k closest other elements found so far for one element, closest first.
+struct Neighbours<N> {
+    found: Vec<(N, usize)>,
+}
+impl<N: Num> Neighbours<N> {
+    fn consider(&mut self, k: usize, dis: N, index: usize) {
+        if self.found.len() == k {
+            match self.found.last() {
+                Some(&(m, _)) if dis < m => {
+                    self.found.pop();
+                }
+                _ => return,
+            }
+        }
+        let i = self
+            .found
+            .iter()
+            .position(|a| dis < a.0)
+            .unwrap_or(self.found.len());
+        self.found.insert(i, (dis, index));
+    }
+
+    //The distance of the kth neighbour, if k neighbours were found.
+    fn furthest(&self, k: usize) -> Option<N> {
+        if self.found.len() == k {
+            self.found.last().map(|a| a.0)
+        } else {
+            None
+        }
+    }
+}
+
+//The larger of two bounds, where None means no bound at all.
+fn max_bound<N: Num>(a: Option<N>, b: Option<N>) -> Option<N> {
+    match (a, b) {
+        (Some(a), Some(b)) => Some(if a >= b { a } else { b }),
+        _ => None,
+    }
+}
+
+fn grow_rect<N: Num>(a: &mut Option<Rect<N>>, b: &Rect<N>) {
+    match a {
+        Some(a) => {
+            super::tools::grow_range(&mut a.x, &b.x);
+            super::tools::grow_range(&mut a.y, &b.y);
+        }
+        None => *a = Some(*b),
+    }
+}
+
+//A node of the tree, flattened so that any two nodes can be looked at together.
+struct KnnNode<N> {
+    //The elements of the node, as indices into the elements of the tree.
+    range: core::ops::Range<usize>,
+    children: Option<[usize; 2]>,
+    //Bounds of the elements of the node, and of the whole subtree.
+    own_bounds: Option<Rect<N>>,
+    bounds: Option<Rect<N>>,
+    //Bounds of the query points of the node, and of the whole subtree.
+    own_points: Option<Rect<N>>,
+    points: Option<Rect<N>>,
+    //The distance beyond which no element of the node, or of the whole subtree,
+    //can find a closer neighbour. None if some element still has less than k neighbours.
+    own_bound: Option<N>,
+    bound: Option<N>,
+}
+
+//Either the elements of one node, or the elements of a whole subtree.
+#[derive(Copy, Clone)]
+enum KnnPart {
+    Own(usize),
+    Sub(usize),
+}
+
+struct KnnGraph<'a, K: Knearest> {
+    k: usize,
+    knear: K,
+    nodes: Vec<KnnNode<K::N>>,
+    points: Vec<Vec2<K::N>>,
+    elems: PMut<'a, [K::T]>,
+    neighbours: Vec<Neighbours<K::N>>,
+}
+
+impl<'a, K: Knearest> KnnGraph<'a, K> {
+    fn flatten(
+        vistr: Vistr<Node<K::T>>,
+        points: &[Vec2<K::N>],
+        nodes: &mut Vec<KnnNode<K::N>>,
+        start: &mut usize,
+    ) -> usize {
+        let (nn, rest) = vistr.next();
+        let range = *start..*start + nn.range.len();
+        *start = range.end;
+
+        let mut own_bounds = None;
+        for a in nn.range.iter() {
+            grow_rect(&mut own_bounds, a.get());
+        }
+        let mut own_points = None;
+        for p in points[range.clone()].iter() {
+            grow_rect(&mut own_points, &Rect::new(p.x, p.x, p.y, p.y));
+        }
+
+        let index = nodes.len();
+        nodes.push(KnnNode {
+            range,
+            children: None,
+            own_bounds,
+            bounds: nn.bounds,
+            own_points,
+            points: own_points,
+            own_bound: None,
+            bound: None,
+        });
+
+        if let Some([left, right]) = rest {
+            let left = Self::flatten(left, points, nodes, start);
+            let right = Self::flatten(right, points, nodes, start);
+            for c in [left, right].iter() {
+                if let Some(p) = nodes[*c].points {
+                    grow_rect(&mut nodes[index].points, &p);
+                }
+            }
+            nodes[index].children = Some([left, right]);
+        }
+        index
+    }
+
+    fn points(&self, a: KnnPart) -> Option<Rect<K::N>> {
+        match a {
+            KnnPart::Own(n) => self.nodes[n].own_points,
+            KnnPart::Sub(n) => self.nodes[n].points,
+        }
+    }
+
+    fn bounds(&self, a: KnnPart) -> Option<Rect<K::N>> {
+        match a {
+            KnnPart::Own(n) => self.nodes[n].own_bounds,
+            KnnPart::Sub(n) => self.nodes[n].bounds,
+        }
+    }
+
+    fn bound(&self, a: KnnPart) -> Option<K::N> {
+        match a {
+            KnnPart::Own(n) => self.nodes[n].own_bound,
+            KnnPart::Sub(n) => self.nodes[n].bound,
+        }
+    }
+
+    //The parts that make up a subtree.
+    fn parts(&self, n: usize) -> ([KnnPart; 3], usize) {
+        match self.nodes[n].children {
+            Some([l, r]) => ([KnnPart::Own(n), KnnPart::Sub(l), KnnPart::Sub(r)], 3),
+            None => ([KnnPart::Own(n); 3], 1),
+        }
+    }
+
+    //A lower bound of the distance between any query point of `a` and any element of `b`.
+    //None if they overlap along both axes, in which case nothing is known.
+    fn distance(&mut self, a: KnnPart, b: KnnPart) -> Option<K::N> {
+        let (points, bounds) = match (self.points(a), self.bounds(b)) {
+            (Some(p), Some(b)) => (p, b),
+            _ => return None,
+        };
+        let x = self.distance_along(axgeom::XAXIS, &points, &bounds);
+        let y = self.distance_along(axgeom::YAXIS, &points, &bounds);
+        match (x, y) {
+            (Some(x), Some(y)) => Some(if x >= y { x } else { y }),
+            (Some(x), None) => Some(x),
+            (None, y) => y,
+        }
+    }
+
+    fn distance_along<A: Axis>(
+        &mut self,
+        axis: A,
+        points: &Rect<K::N>,
+        bounds: &Rect<K::N>,
+    ) -> Option<K::N> {
+        let p = points.get_range(axis);
+        let b = bounds.get_range(axis);
+        let (edge, line) = if p.end < b.start {
+            (p.end, b.start)
+        } else if b.end < p.start {
+            (p.start, b.end)
+        } else {
+            return None;
+        };
+        let mut point = vec2(points.x.start, points.y.start);
+        *point.get_axis_mut(axis) = edge;
+        Some(self.knear.distance_to_aaline(point, axis, line))
+    }
+
+    //Returns true if no element of `b` can be a neighbour of any element of `a`.
+    fn is_far(&mut self, a: KnnPart, b: KnnPart) -> bool {
+        if self.points(a).is_none() || self.bounds(b).is_none() {
+            return true;
+        }
+        match (self.bound(a), self.distance(a, b)) {
+            (Some(bound), Some(dis)) => dis >= bound,
+            _ => false,
+        }
+    }
+
+    //Find the neighbours of the elements of `a` among the elements of `b`.
+    //Both parts are split until they are the elements of a single node,
+    //and pairs of parts that are too far apart are skipped as a whole.
+    fn recc(&mut self, a: KnnPart, b: KnnPart) {
+        if self.is_far(a, b) {
+            return;
+        }
+        match (a, b) {
+            (KnnPart::Own(q), KnnPart::Own(r)) => self.handle(q, r),
+            (KnnPart::Own(_), KnnPart::Sub(r)) => self.recc_ref(a, r),
+            (KnnPart::Sub(q), _) => {
+                let (parts, num) = self.parts(q);
+                for &pa in parts[..num].iter() {
+                    match b {
+                        KnnPart::Own(_) => self.recc(pa, b),
+                        KnnPart::Sub(r) => self.recc_ref(pa, r),
+                    }
+                }
+                let mut bound = self.nodes[q].own_bound;
+                if let Some(children) = self.nodes[q].children {
+                    for &c in children.iter() {
+                        bound = max_bound(bound, self.nodes[c].bound);
+                    }
+                }
+                self.nodes[q].bound = bound;
+            }
+        }
+    }
+
+    //Split the reference subtree `r`, visiting the parts closest to `a` first.
+    fn recc_ref(&mut self, a: KnnPart, r: usize) {
+        let (mut parts, num) = self.parts(r);
+        let mut dis = [None; 3];
+        for (d, &pb) in dis.iter_mut().zip(parts[..num].iter()) {
+            *d = self.distance(a, pb);
+        }
+        let mut order = [0, 1, 2];
+        order[..num].sort_by(|&i, &j| match (dis[i], dis[j]) {
+            (Some(x), Some(y)) => x.partial_cmp(&y).unwrap_or(core::cmp::Ordering::Equal),
+            (Some(_), None) => core::cmp::Ordering::Greater,
+            (None, Some(_)) => core::cmp::Ordering::Less,
+            (None, None) => core::cmp::Ordering::Equal,
+        });
+        let unsorted = parts;
+        for (p, &i) in parts.iter_mut().zip(order.iter()) {
+            *p = unsorted[i];
+        }
+        for &pb in parts[..num].iter() {
+            self.recc(a, pb);
+        }
+    }
+
+    fn handle(&mut self, q: usize, r: usize) {
+        let k = self.k;
+        for i in self.nodes[q].range.clone() {
+            let point = self.points[i];
+            for j in self.nodes[r].range.clone() {
+                if i == j {
+                    continue;
+                }
+                let mut b = self.elems.borrow_mut().get_index_mut(j);
+                if let Some(m) = self.neighbours[i].furthest(k) {
+                    if let Some(d) = self.knear.distance_to_broad(point, b.borrow_mut()) {
+                        if d >= m {
+                            continue;
+                        }
+                    }
+                }
+                let d = self.knear.distance_to_fine(point, b);
+                self.neighbours[i].consider(k, d, j);
+            }
+        }
+
+        let mut bound = Some(Default::default());
+        for n in self.neighbours[self.nodes[q].range.clone()].iter() {
+            bound = max_bound(bound, n.furthest(k));
+        }
+        self.nodes[q].own_bound = bound;
+    }
+}
+
+fn knn_graph_mut<'a, Q: Queries<'a> + ?Sized, K: Knearest<T = Q::T, N = Q::Num>>(
+    tree: &mut Q,
+    k: usize,
+    knear: &mut K,
+    mut point: impl FnMut(&Q::T) -> Vec2<Q::Num>,
+) -> Vec<Neighbours<Q::Num>> {
+    let points: Vec<_> = super::tools::get_elements(tree.vistr())
+        .iter()
+        .map(&mut point)
+        .collect();
+
+    let neighbours = (0..points.len())
+        .map(|_| Neighbours {
+            found: Vec::with_capacity(k),
+        })
+        .collect();
+
+    if k == 0 {
+        return neighbours;
+    }
+
+    let mut nodes = Vec::new();
+    KnnGraph::<KnearestBorrow<K>>::flatten(tree.vistr(), &points, &mut nodes, &mut 0);
+
+    let mut graph = KnnGraph {
+        k,
+        knear: KnearestBorrow(knear),
+        nodes,
+        points,
+        elems: super::tools::get_elements_mut(tree.vistr_mut()),
+        neighbours,
+    };
+    graph.recc(KnnPart::Sub(0), KnnPart::Sub(0));
+    graph.neighbours
+}
+
+fn naive_knn_graph_inner<T: Aabb>(
+    mut elems: PMut<[T]>,
+    k: usize,
+    knear: &mut impl Knearest<T = T, N = T::Num>,
+    mut point: impl FnMut(&T) -> Vec2<T::Num>,
+) -> Vec<Neighbours<T::Num>> {
+    let points: Vec<_> = elems.iter().map(&mut point).collect();
+    points
+        .iter()
+        .enumerate()
+        .map(|(i, &p)| {
+            let mut n = Neighbours {
+                found: Vec::with_capacity(k),
+            };
+            if k > 0 {
+                for j in 0..points.len() {
+                    if i != j {
+                        let b = elems.borrow_mut().get_index_mut(j);
+                        n.consider(k, knear.distance_to_fine(p, b), j);
+                    }
+                }
+            }
+            n
+        })
+        .collect()
+}
+
+fn into_indices<N>(neighbours: Vec<Neighbours<N>>) -> Vec<Vec<usize>> {
+    neighbours
+        .into_iter()
+        .map(|n| n.found.into_iter().map(|a| a.1).collect())
+        .collect()
+}
+
+///Panics if a disconnect is detected between tree and naive queries.
+///Since ties can be broken arbitrarily, the distances to the neighbours
+///of each element are compared instead of their indices.
+pub fn assert_knn_graph_mut<T: Aabb>(
+    tree: &mut Tree<T>,
+    k: usize,
+    knear: &mut impl Knearest<T = T, N = T::Num>,
+    mut point: impl FnMut(&T) -> Vec2<T::Num>,
+) where
+    T::Num: core::fmt::Debug,
+{
+    let distances = |graph: Vec<Neighbours<T::Num>>| -> Vec<Vec<T::Num>> {
+        graph
+            .into_iter()
+            .enumerate()
+            .map(|(i, n)| {
+                n.found
+                    .into_iter()
+                    .map(|(d, j)| {
+                        assert_ne!(i, j);
+                        d
+                    })
+                    .collect()
+            })
+            .collect()
+    };
+
+    let bots = tree.get_elements_mut();
+    let res_naive = distances(naive_knn_graph_inner(bots, k, knear, &mut point));
+    let res_dino = distances(knn_graph_mut(tree, k, knear, &mut point));
+
+    assert_eq!(res_naive, res_dino);
+}
+
+///Naive implementation
+pub fn naive_knn_graph_mut<T: Aabb>(
+    elems: PMut<[T]>,
+    k: usize,
+    knear: &mut impl Knearest<T = T, N = T::Num>,
+    point: impl FnMut(&T) -> Vec2<T::Num>,
+) -> Vec<Vec<usize>> {
+    into_indices(naive_knn_graph_inner(elems, k, knear, point))
+}
+
 ///Knearest functions that can be called on a tree.
 pub trait KnearestQuery<'a>: Queries<'a> {
     /// Find the closest `num` elements to the specified `point`.
@@ -502,6 +979,55 @@ pub trait KnearestQuery<'a>: Queries<'a> {
         num: usize,
         ktrait: &mut K,
     ) -> KResult<Self::T>
+    where
+        'a: 'b,
+    {
+        self.k_nearest_filter_mut(point, num, ktrait, |_| true, None)
+    }
+
+    /// Like [`KnearestQuery::k_nearest_mut`], but only elements for which `filter`
+    /// returns true are candidates, so filtered out elements do not use up any of the `num` slots.
+    ///
+    /// If `max_distance` is specified, elements further away than it are ignored and
+    /// subtrees that are further away than it are never visited.
+    /// It must be in the same units as the distances returned by the [`Knearest`] functions.
+    /// (e.g. squared if they return squared distances)
+    ///
+    /// # Examples
+    ///
+    ///```
+    /// use broccoli::{prelude::*,bbox,rect};
+    /// use axgeom::vec2;
+    ///
+    /// let mut bots = [bbox(rect(0,10,0,10),true),
+    ///               bbox(rect(12,14,12,14),false),
+    ///               bbox(rect(40,50,40,50),true)];
+    ///
+    /// let mut tree = broccoli::new(&mut bots);
+    ///
+    /// let mut handler = broccoli::query::knearest::default_rect_knearest(&tree);
+    ///
+    /// //Only consider elements marked true that are within a distance of 20.
+    /// let mut res = tree.k_nearest_filter_mut(
+    ///       vec2(15, 15),
+    ///       2,
+    ///       &mut handler,
+    ///       |a| a.inner,
+    ///       Some(20*20)
+    /// );
+    ///
+    /// assert_eq!(res.len(),1);
+    /// assert_eq!(res.iter().next().unwrap()[0].bot.rect,rect(0,10,0,10));
+    ///```
+    #[must_use]
+    fn k_nearest_filter_mut<'b, K: Knearest<T = Self::T, N = Self::Num>>(
+        &'b mut self,
+        point: Vec2<Self::Num>,
+        num: usize,
+        ktrait: &mut K,
+        filter: impl FnMut(&Self::T) -> bool,
+        max_distance: Option<Self::Num>,
+    ) -> KResult<Self::T>
     where
         'a: 'b,
     {
@@ -509,20 +1035,67 @@ pub trait KnearestQuery<'a>: Queries<'a> {
 
         let knear = KnearestBorrow(ktrait);
 
-        let closest = ClosestCand::new(num);
+        let closest = ClosestCand::new(num, max_distance);
 
         let mut blap = Blap {
             knear,
             point,
             closest,
+            filter,
         };
 
         recc(default_axis(), dt, &mut blap);
 
         let num_entires = blap.closest.curr_num;
-        KResult {
-            num_entires,
-            inner: blap.closest.into_sorted(),
-        }
+        KResult::new(num_entires, blap.closest.into_sorted())
+    }
+
+    /// Find the `k` closest other elements of every element in the tree.
+    ///
+    /// The distance from an element `a` to another element `b` is the distance
+    /// that the [`Knearest`] handler returns between `point(a)` and `b`, so
+    /// the neighbours of every element are the ones [`KnearestQuery::k_nearest_mut`]
+    /// would find from its point, other than itself.
+    ///
+    /// Returns an adjacency list. Both the outer vec and the indices it contains
+    /// refer to the order of [`Tree::get_elements`]. The neighbours of each element
+    /// are sorted closest first. Ties are broken arbitrarily.
+    ///
+    /// This is faster than calling [`KnearestQuery::k_nearest_mut`] for every element.
+    /// The tree is traversed against itself, so pairs of subtrees that are too far
+    /// apart are skipped together instead of once for every element.
+    ///
+    /// # Examples
+    ///
+    ///```
+    /// use broccoli::{prelude::*,rect};
+    /// use axgeom::vec2;
+    ///
+    /// let mut bots = [rect(0,10,0,10),
+    ///               rect(12,14,12,14),
+    ///               rect(40,50,40,50)];
+    ///
+    /// let mut tree = broccoli::new(&mut bots);
+    /// let mut handler = broccoli::query::knearest::default_rect_knearest(&tree);
+    ///
+    /// //Measure from the top left corner of each element.
+    /// let graph = tree.knn_graph_mut(1, &mut handler, |a| vec2(a.x.start, a.y.start));
+    ///
+    /// let elems = tree.get_elements();
+    /// for (i,n) in graph.iter().enumerate(){
+    ///     let closest = elems[n[0]];
+    ///     if elems[i] == rect(40,50,40,50){
+    ///         assert_eq!(closest,rect(12,14,12,14));
+    ///     }
+    /// }
+    ///```
+    #[must_use]
+    fn knn_graph_mut<K: Knearest<T = Self::T, N = Self::Num>>(
+        &mut self,
+        k: usize,
+        ktrait: &mut K,
+        point: impl FnMut(&Self::T) -> Vec2<Self::Num>,
+    ) -> Vec<Vec<usize>> {
+        into_indices(knn_graph_mut(self, k, ktrait, point))
     }
 }
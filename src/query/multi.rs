@@ -0,0 +1,402 @@
+//! Multi query sessions
+//!
+//! Like [`RectQuery::multi_rect`](crate::query::rect::RectQuery::multi_rect),
+//! a session lets the user hold mutable references returned by several queries at once,
+//! but for any query shape. Instead of requiring the query shapes to be disjoint, the session
+//! remembers every element it has returned. A circle or rectangle query that would return
+//! an element a second time fails before any element is handed out, while the segment and
+//! k nearest queries skip the elements that were already returned.
+
+use crate::query::inner_prelude::*;
+use crate::query::knearest::{KResult, Knearest, KnearestQuery, KnearestResult};
+use crate::query::rect::RectQuery;
+use crate::query::segment::{Segment, SegmentIntersect, SegmentQuery};
+use crate::query::Queries;
+use num_traits::Signed;
+
+///A circle.
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub struct Circle<N> {
+    pub center: Vec2<N>,
+    pub radius: N,
+}
+
+impl<N: Num + Signed> Circle<N> {
+    ///The smallest rectangle that contains the circle.
+    pub fn bounding_rect(&self) -> Rect<N> {
+        let Circle { center, radius } = *self;
+        rect(
+            center.x - radius,
+            center.x + radius,
+            center.y - radius,
+            center.y + radius,
+        )
+    }
+
+    ///Returns true if the circle touches the rectangle.
+    #[must_use]
+    pub fn intersects_rect(&self, rect: &Rect<N>) -> bool {
+        fn dis<N: Num + Signed>(r: &Range<N>, a: N) -> N {
+            if a < r.start {
+                r.start - a
+            } else if a > r.end {
+                a - r.end
+            } else {
+                N::zero()
+            }
+        }
+        let x = dis(&rect.x, self.center.x);
+        let y = dis(&rect.y, self.center.y);
+        x * x + y * y <= self.radius * self.radius
+    }
+}
+
+///Indicates that the user supplied a query that would return an element
+///that was already returned by a previous query in the session.
+#[derive(Eq, PartialEq, Copy, Clone, Debug)]
+pub struct AlreadyQueriedErr;
+
+#[derive(Copy, Clone)]
+struct Ptr<T>(*mut T);
+unsafe impl<T> Send for Ptr<T> {}
+unsafe impl<T> Sync for Ptr<T> {}
+
+//The index of an element of the tree, given a pointer to the first element.
+fn index_of<T>(base: *const T, a: *const T) -> usize {
+    (unsafe { a.offset_from(base) }) as usize
+}
+
+//Rejects the elements that were already taken before the user's handler sees them,
+//since the user might hold a mutable reference to them.
+struct SkipTaken<'b, K: SegmentIntersect> {
+    handler: &'b mut K,
+    base: *const K::T,
+    taken: &'b [bool],
+}
+
+impl<'b, K: SegmentIntersect> SegmentIntersect for SkipTaken<'b, K> {
+    type T = K::T;
+    type N = K::N;
+
+    fn intersects_fine(&mut self, seg: &Segment<Self::N>, a: &Self::T) -> bool {
+        !self.taken[index_of(self.base, a)] && self.handler.intersects_fine(seg, a)
+    }
+}
+
+fn circle_recurse<'a, 'b: 'a, T: Aabb, A: Axis>(
+    this_axis: A,
+    vistr: VistrMut<'a, Node<'b, T>>,
+    circle: &Circle<T::Num>,
+    rect: &Rect<T::Num>,
+    func: &mut impl FnMut(PMut<'a, T>),
+) where
+    T::Num: Signed,
+{
+    let (nn, rest) = vistr.next();
+    if !crate::query::tools::bounds_touch(&nn.bounds, rect) {
+        return;
+    }
+    let div = nn.div;
+    for a in nn.into_range().iter_mut() {
+        if circle.intersects_rect(a.get()) {
+            func(a);
+        }
+    }
+    if let Some([left, right]) = rest {
+        let div = match div {
+            Some(b) => b,
+            None => return,
+        };
+
+        let rr = rect.get_range(this_axis);
+        if div >= rr.start {
+            circle_recurse(this_axis.next(), left, circle, rect, func);
+        }
+        if div <= rr.end {
+            circle_recurse(this_axis.next(), right, circle, rect, func);
+        }
+    }
+}
+
+///See the [`MultiQuery::multi_query`] function.
+pub struct MultiQuerySession<'c, 'a: 'c, Q: Queries<'a>> {
+    tree: &'c mut Q,
+    base: *const Q::T,
+    taken: Vec<bool>,
+    _p: PhantomData<&'a ()>,
+}
+
+impl<'c, 'a: 'c, Q: Queries<'a>> MultiQuerySession<'c, 'a, Q> {
+    fn new(tree: &'c mut Q) -> Self {
+        let elems = crate::query::tools::get_elements_mut(tree.vistr_mut());
+        let base = elems.as_ptr();
+        let taken = vec![false; elems.len()];
+        MultiQuerySession {
+            tree,
+            base,
+            taken,
+            _p: PhantomData,
+        }
+    }
+
+    //Mark the elements of every region as taken, unless one of them was already taken,
+    //either by a previous query or by another region.
+    fn take(&mut self, regions: &[Vec<Ptr<Q::T>>]) -> Result<(), AlreadyQueriedErr> {
+        let base = self.base;
+        let index = |a: &Ptr<Q::T>| index_of(base, a.0);
+
+        let all = regions.iter().flat_map(|a| a.iter());
+        for (i, a) in all.clone().enumerate() {
+            let j = index(a);
+            if self.taken[j] {
+                for a in all.take(i) {
+                    self.taken[index(a)] = false;
+                }
+                return Err(AlreadyQueriedErr);
+            }
+            self.taken[j] = true;
+        }
+        Ok(())
+    }
+
+    //Mark the elements of a query that skipped the taken elements.
+    fn mark(&mut self, ptrs: &[Ptr<Q::T>]) {
+        for a in ptrs {
+            let j = index_of(self.base, a.0);
+            debug_assert!(!self.taken[j]);
+            self.taken[j] = true;
+        }
+    }
+
+    //Hand out the elements of a query that was just taken.
+    //This is only safe to do because the user is unable to mutate the bounding box,
+    //and each element is handed out by at most one query of the session.
+    fn hand_out(ptrs: Vec<Ptr<Q::T>>, mut func: impl FnMut(PMut<'c, Q::T>)) {
+        for a in ptrs {
+            func(PMut::new(unsafe { &mut *a.0 }));
+        }
+    }
+
+    fn collect_circle(&mut self, circle: &Circle<Q::Num>) -> Vec<Ptr<Q::T>>
+    where
+        Q::Num: Signed,
+    {
+        let mut ptrs = Vec::new();
+        circle_recurse(
+            default_axis(),
+            self.tree.vistr_mut(),
+            circle,
+            &circle.bounding_rect(),
+            &mut |a| ptrs.push(Ptr(unsafe { a.into_inner() } as *mut _)),
+        );
+        ptrs
+    }
+
+    fn collect_rect(&mut self, rect: &Rect<Q::Num>) -> Vec<Ptr<Q::T>>
+    where
+        Q: RectQuery<'a>,
+    {
+        let mut ptrs = Vec::new();
+        self.tree.for_all_intersect_rect_mut(rect, |a| {
+            ptrs.push(Ptr(unsafe { a.into_inner() } as *mut _))
+        });
+        ptrs
+    }
+
+    ///Visit every element that touches the circle.
+    pub fn for_all_intersect_circle_mut(
+        &mut self,
+        circle: &Circle<Q::Num>,
+        func: impl FnMut(PMut<'c, Q::T>),
+    ) -> Result<(), AlreadyQueriedErr>
+    where
+        Q::Num: Signed,
+    {
+        let ptrs = self.collect_circle(circle);
+        self.take(core::slice::from_ref(&ptrs))?;
+        Self::hand_out(ptrs, func);
+        Ok(())
+    }
+
+    ///Visit every element that intersects the rectangle.
+    pub fn for_all_intersect_rect_mut(
+        &mut self,
+        rect: &Rect<Q::Num>,
+        func: impl FnMut(PMut<'c, Q::T>),
+    ) -> Result<(), AlreadyQueriedErr>
+    where
+        Q: RectQuery<'a>,
+    {
+        let ptrs = self.collect_rect(rect);
+        self.take(core::slice::from_ref(&ptrs))?;
+        Self::hand_out(ptrs, func);
+        Ok(())
+    }
+
+    ///Visit every element that the segment intersects,
+    ///skipping the elements returned by a previous query.
+    ///See [`SegmentQuery::for_all_intersect_segment_mut`].
+    ///
+    ///The elements that were already returned are never passed to `handler`.
+    pub fn for_all_intersect_segment_mut<K: SegmentIntersect<T = Q::T, N = Q::Num>>(
+        &mut self,
+        seg: &Segment<Q::Num>,
+        handler: &mut K,
+        func: impl FnMut(PMut<'c, Q::T>),
+    ) where
+        Q: SegmentQuery<'a>,
+        Q::Num: Signed,
+    {
+        let mut handler = SkipTaken {
+            handler,
+            base: self.base,
+            taken: &self.taken,
+        };
+        let mut ptrs = Vec::new();
+        self.tree
+            .for_all_intersect_segment_mut(seg, &mut handler, |a| {
+                ptrs.push(Ptr(unsafe { a.into_inner() } as *mut _))
+            });
+        self.mark(&ptrs);
+        Self::hand_out(ptrs, func);
+    }
+
+    ///Find the closest `num` elements to the specified `point`,
+    ///including ties, skipping the elements returned by a previous query.
+    ///See [`KnearestQuery::k_nearest_mut`].
+    ///
+    ///The elements that were already returned are never passed to `handler`.
+    pub fn k_nearest_mut<K: Knearest<T = Q::T, N = Q::Num>>(
+        &mut self,
+        point: Vec2<Q::Num>,
+        num: usize,
+        handler: &mut K,
+    ) -> KResult<'c, Q::T>
+    where
+        Q: KnearestQuery<'a>,
+    {
+        let base = self.base;
+        let taken = &self.taken;
+        let res = self.tree.k_nearest_filter_mut(
+            point,
+            num,
+            handler,
+            |a| !taken[index_of(base, a)],
+            None,
+        );
+        let num_entires = res.len();
+        let found: Vec<_> = res
+            .into_vec()
+            .into_iter()
+            .map(|a| (Ptr(unsafe { a.bot.into_inner() } as *mut _), a.mag))
+            .collect();
+
+        let ptrs: Vec<_> = found.iter().map(|a| Ptr((a.0).0)).collect();
+        self.mark(&ptrs);
+
+        //Same as hand_out.
+        let inner = found
+            .into_iter()
+            .map(|(a, mag)| KnearestResult {
+                bot: PMut::new(unsafe { &mut *a.0 }),
+                mag,
+            })
+            .collect();
+        KResult::new(num_entires, inner)
+    }
+}
+
+impl<'c, 'a: 'c, Q: Queries<'a>> MultiQuerySession<'c, 'a, Q>
+where
+    Q::T: Send + Sync,
+    Q::Num: Signed + Send + Sync,
+{
+    //Hand out the elements of every region on a different thread.
+    fn hand_out_par(
+        regions: Vec<Vec<Ptr<Q::T>>>,
+        func: impl Fn(usize, PMut<'c, Q::T>) + Send + Sync,
+    ) {
+        use rayon::prelude::*;
+        regions.into_par_iter().enumerate().for_each(|(i, ptrs)| {
+            Self::hand_out(ptrs, |a| func(i, a));
+        });
+    }
+
+    ///Visit every element that touches each circle, processing each circle
+    ///on a different thread. `func` is passed the index of the circle.
+    ///
+    ///Fails without visiting any element if two circles touch the same element,
+    ///or if a circle touches an element returned by a previous query.
+    pub fn for_all_intersect_circles_par(
+        &mut self,
+        circles: &[Circle<Q::Num>],
+        func: impl Fn(usize, PMut<'c, Q::T>) + Send + Sync,
+    ) -> Result<(), AlreadyQueriedErr> {
+        let regions: Vec<_> = circles.iter().map(|c| self.collect_circle(c)).collect();
+        self.take(&regions)?;
+        Self::hand_out_par(regions, func);
+        Ok(())
+    }
+
+    ///Visit every element that intersects each rectangle, processing each rectangle
+    ///on a different thread. `func` is passed the index of the rectangle.
+    ///
+    ///Fails without visiting any element if two rectangles intersect the same element,
+    ///or if a rectangle intersects an element returned by a previous query.
+    pub fn for_all_intersect_rects_par(
+        &mut self,
+        rects: &[Rect<Q::Num>],
+        func: impl Fn(usize, PMut<'c, Q::T>) + Send + Sync,
+    ) -> Result<(), AlreadyQueriedErr>
+    where
+        Q: RectQuery<'a>,
+    {
+        let regions: Vec<_> = rects.iter().map(|r| self.collect_rect(r)).collect();
+        self.take(&regions)?;
+        Self::hand_out_par(regions, func);
+        Ok(())
+    }
+}
+
+///Multi query functions that can be called on a tree.
+pub trait MultiQuery<'a>: Queries<'a> + Sized {
+    /// Start a session within which the user can make multiple queries
+    /// and hold on to the mutable references they return at the same time,
+    /// as long as no element is returned by more than one query.
+    ///
+    /// # Safety
+    ///
+    /// Unsafe code is used. We unsafely convert the references returned by each query
+    /// to have the lifetime of the session. This is safe since the session remembers every
+    /// element it has returned, and never returns an element twice.
+    ///
+    /// # Examples
+    ///
+    ///```
+    /// use broccoli::{prelude::*,bbox,rect};
+    /// use broccoli::query::multi::{Circle,AlreadyQueriedErr};
+    /// use axgeom::vec2;
+    ///
+    /// let mut bots = [bbox(rect(0,10,0,10),0u8),bbox(rect(20,30,20,30),0u8)];
+    /// let mut tree = broccoli::new(&mut bots);
+    /// let mut handler = broccoli::query::knearest::default_rect_knearest(&tree);
+    /// let mut multi = tree.multi_query();
+    ///
+    /// let mut first = Vec::new();
+    /// multi.for_all_intersect_circle_mut(&Circle{center:vec2(0,0),radius:5},|a|first.push(a)).unwrap();
+    ///
+    /// let mut second = multi.k_nearest_mut(vec2(40,40),1,&mut handler);
+    ///
+    /// //The first element was already returned.
+    /// let res = multi.for_all_intersect_rect_mut(&rect(5,15,5,15),|_|{});
+    /// assert_eq!(res,Err(AlreadyQueriedErr));
+    ///
+    /// //Both sets of references can be used at the same time.
+    /// *first[0].borrow_mut().unpack_inner()+=1;
+    /// *second.iter().next().unwrap()[0].bot.borrow_mut().unpack_inner()+=1;
+    ///```
+    #[must_use]
+    fn multi_query<'c>(&'c mut self) -> MultiQuerySession<'c, 'a, Self> {
+        MultiQuerySession::new(self)
+    }
+}
@@ -1,10 +1,12 @@
 //!
-//! Experimental nbody approximate solver
+//! Nbody approximate solver
 //!
 //! The user can choose the distance at which to fallback on approximate solutions.
 //! The algorithm works similar to a Barnes–Hut simulation, but uses a kdtree instead of a quad tree.
 //!
-//! The user defines some geometric functions and their ideal accuracy.
+//! The user defines some geometric functions and their ideal accuracy by implementing [`Nbody`].
+//! Alternatively, [`NbodyBuilder`] provides a higher level interface where the user only
+//! supplies a force law and an opening angle. See [`gravity`] and [`coulomb`].
 //!
 use super::*;
 
@@ -23,6 +25,8 @@ pub trait Nbody {
     //return the position of the center of mass
     fn compute_center_of_mass(&mut self, a: &[Self::T]) -> Self::Mass;
 
+    //`line` is the closest edge along the axis of the elements the mass is compared against.
+    //All of those elements lie on the other side of it.
     fn is_close(&mut self, a: &Self::Mass, line: Self::N, a: impl Axis) -> bool;
 
     fn is_close_half(&mut self, a: &Self::Mass, line: Self::N, a: impl Axis) -> bool;
@@ -41,8 +45,11 @@ use compt::dfs_order::PreOrder;
 use compt::dfs_order::VistrMut;
 
 struct NodeWrapper<'a, T: Aabb, M> {
-    node: Node<'a, T>,
-    mass: M,
+    range: PMut<'a, [T]>,
+    cont: Range<T::Num>,
+    div: Option<T::Num>,
+    bounds: Option<Rect<T::Num>>,
+    mass: &'a mut M,
 }
 
 ///Naive version simply visits every pair.
@@ -50,22 +57,17 @@ pub fn naive_mut<T: Aabb>(bots: PMut<[T]>, func: impl FnMut(PMut<T>, PMut<T>)) {
     tools::for_every_pair(bots, func);
 }
 
-fn build_masses2<N: Nbody>(
-    vistr: VistrMut<NodeWrapper<N::T, N::Mass>, PreOrder>,
-    no: &mut N,
-) -> N::Mass {
-    let (nn, rest) = vistr.next();
-    let mass = no.compute_center_of_mass(&nn.node.range);
-    let mass = if let Some([left, right]) = rest {
-        let a = build_masses2(left, no);
-        let b = build_masses2(right, no);
-        let m = no.combine_two_masses(&a, &b);
-        no.combine_two_masses(&m, &mass)
-    } else {
-        mass
-    };
-    nn.mass = mass;
-    mass
+//Computes the mass of every subtree using the generic per node aggregate.
+struct MassAggregate<'a, N>(&'a mut N);
+impl<'a, N: Nbody> super::aggregate::Aggregate for MassAggregate<'a, N> {
+    type T = N::T;
+    type Agg = N::Mass;
+    fn node(&mut self, elems: &[N::T]) -> N::Mass {
+        self.0.compute_center_of_mass(elems)
+    }
+    fn combine(&mut self, a: &N::Mass, b: &N::Mass) -> N::Mass {
+        self.0.combine_two_masses(a, b)
+    }
 }
 
 fn collect_masses<'a, 'b, N: Nbody>(
@@ -79,12 +81,12 @@ fn collect_masses<'a, 'b, N: Nbody>(
 ) {
     let (nn, rest) = vistr.next();
 
-    if !no.is_close_half(&nn.mass, root_div, root_axis) {
+    if !no.is_close_half(nn.mass, root_div, root_axis) {
         func1(nn, no);
         return;
     }
 
-    func2(&mut nn.node.range, no);
+    func2(&mut nn.range, no);
 
     if let Some([left, right]) = rest {
         collect_masses(root_div, root_axis, root, left, no, func1, func2);
@@ -93,7 +95,7 @@ fn collect_masses<'a, 'b, N: Nbody>(
 }
 
 fn pre_recc<N: Nbody>(
-    root_div: N::N,
+    root_cont: Range<N::N>,
     root_axis: impl Axis,
     root: &mut NodeWrapper<N::T, N::Mass>,
     vistr: VistrMut<NodeWrapper<N::T, N::Mass>, PreOrder>,
@@ -101,22 +103,40 @@ fn pre_recc<N: Nbody>(
 ) {
     let (nn, rest) = vistr.next();
 
-    if !no.is_close(&nn.mass, root_div, root_axis) {
-        no.gravitate(
-            GravEnum::Bot(root.node.range.borrow_mut()),
-            GravEnum::Mass(&mut nn.mass),
-        );
-        return;
+    //The root elements straddle the divider, so the subtree is compared against
+    //the closest edge of them. If it overlaps them it has to be visited.
+    let line = match &nn.bounds {
+        Some(bounds) => {
+            let r = bounds.get_range(root_axis);
+            if r.end < root_cont.start {
+                Some(root_cont.start)
+            } else if r.start > root_cont.end {
+                Some(root_cont.end)
+            } else {
+                None
+            }
+        }
+        None => None,
+    };
+
+    if let Some(line) = line {
+        if !no.is_close(nn.mass, line, root_axis) {
+            no.gravitate(
+                GravEnum::Bot(root.range.borrow_mut()),
+                GravEnum::Mass(nn.mass),
+            );
+            return;
+        }
     }
 
     no.gravitate(
-        GravEnum::Bot(root.node.range.borrow_mut()),
-        GravEnum::Bot(nn.node.range.borrow_mut()),
+        GravEnum::Bot(root.range.borrow_mut()),
+        GravEnum::Bot(nn.range.borrow_mut()),
     );
 
     if let Some([left, right]) = rest {
-        pre_recc(root_div, root_axis, root, left, no);
-        pre_recc(root_div, root_axis, root, right, no);
+        pre_recc(root_cont, root_axis, root, left, no);
+        pre_recc(root_cont, root_axis, root, right, no);
     }
 }
 
@@ -127,20 +147,31 @@ fn recc_common<'a, 'b, N: Nbody>(
 ) -> Option<[VistrMut<'a, NodeWrapper<'b, N::T, N::Mass>, PreOrder>; 2]> {
     let (nn, rest) = vistr.next();
 
-    no.gravitate_self(nn.node.range.borrow_mut());
+    no.gravitate_self(nn.range.borrow_mut());
 
     if let Some([mut left, mut right]) = rest {
-        if let Some(div) = nn.node.div {
-            pre_recc(div, axis, nn, left.borrow_mut(), no);
-            pre_recc(div, axis, nn, right.borrow_mut(), no);
+        if nn.div.is_some() {
+            if !nn.range.is_empty() {
+                let cont = nn.cont;
+                pre_recc(cont, axis, nn, left.borrow_mut(), no);
+                pre_recc(cont, axis, nn, right.borrow_mut(), no);
+            }
+
+            //Each side is compared against the closest edge of the bounds of the other side.
+            let left_bounds = left.borrow_mut().next().0.bounds;
+            let right_bounds = right.borrow_mut().next().0.bounds;
+            let (left_end, right_start) = match (left_bounds, right_bounds) {
+                (Some(l), Some(r)) => (l.get_range(axis).end, r.get_range(axis).start),
+                _ => return Some([left, right]),
+            };
 
             let mut finished_masses = Vec::new();
             let mut finished_bots = Vec::new();
 
             collect_masses(
-                div,
+                right_start,
                 axis,
-                &nn.mass,
+                &*nn.mass,
                 left.borrow_mut(),
                 no,
                 &mut |a, _| finished_masses.push(a),
@@ -151,9 +182,9 @@ fn recc_common<'a, 'b, N: Nbody>(
             let mut finished_bots2 = Vec::new();
 
             collect_masses(
-                div,
+                left_end,
                 axis,
-                &nn.mass,
+                &*nn.mass,
                 right.borrow_mut(),
                 no,
                 &mut |a, _| finished_masses2.push(a),
@@ -165,16 +196,16 @@ fn recc_common<'a, 'b, N: Nbody>(
 
             for a in finished_masses.into_iter() {
                 for b in finished_masses2.iter_mut() {
-                    no.gravitate(GravEnum::Mass(&mut a.mass), GravEnum::Mass(&mut b.mass));
+                    no.gravitate(GravEnum::Mass(a.mass), GravEnum::Mass(b.mass));
                 }
 
                 for b in finished_bots2.iter_mut() {
-                    no.gravitate(GravEnum::Mass(&mut a.mass), GravEnum::Bot(b.borrow_mut()));
+                    no.gravitate(GravEnum::Mass(a.mass), GravEnum::Bot(b.borrow_mut()));
                 }
             }
             for a in finished_bots.into_iter() {
                 for b in finished_masses2.iter_mut() {
-                    no.gravitate(GravEnum::Bot(a.borrow_mut()), GravEnum::Mass(&mut b.mass));
+                    no.gravitate(GravEnum::Bot(a.borrow_mut()), GravEnum::Mass(b.mass));
                 }
                 for b in finished_bots2.iter_mut() {
                     no.gravitate(GravEnum::Bot(a.borrow_mut()), GravEnum::Bot(b.borrow_mut()));
@@ -242,16 +273,16 @@ fn get_bots_from_vistr<'a, T: Aabb, N>(
 
     vistr.dfs_preorder(|a| {
         if let Some(s) = new_slice.take() {
-            new_slice = Some(crate::pmut::combine_slice(s, a.node.range.borrow_mut()));
+            new_slice = Some(crate::pmut::combine_slice(s, a.range.borrow_mut()));
         } else {
-            new_slice = Some(a.node.range.borrow_mut());
+            new_slice = Some(a.range.borrow_mut());
         }
     });
     new_slice.unwrap()
 }
 fn apply_tree<N: Nbody>(mut vistr: VistrMut<NodeWrapper<N::T, N::Mass>, PreOrder>, no: &mut N) {
     {
-        let mass = vistr.borrow_mut().next().0.mass;
+        let mass = *vistr.borrow_mut().next().0.mass;
 
         let new_slice = get_bots_from_vistr(vistr.borrow_mut());
 
@@ -266,78 +297,508 @@ fn apply_tree<N: Nbody>(mut vistr: VistrMut<NodeWrapper<N::T, N::Mass>, PreOrder
     }
 }
 
-type TreeInner<T> = CompleteTreeContainer<T, PreOrder>;
-
-fn convert_tree_into_wrapper<T: Aabb, M: Default>(
-    tree: TreeInner<Node<T>>,
-) -> TreeInner<NodeWrapper<T, M>> {
+fn build_wrapper<'a, 'b: 'a, T: Aabb, M>(
+    tree: &'a mut crate::Tree<'b, T>,
+    masses: &'a mut [M],
+) -> CompleteTreeContainer<NodeWrapper<'a, T, M>, PreOrder> {
     let k = tree
-        .into_nodes()
-        .into_vec()
-        .into_iter()
-        .map(|node| NodeWrapper {
-            node,
-            mass: Default::default(),
+        .get_nodes_mut()
+        .iter_mut()
+        .zip(masses.iter_mut())
+        .map(|(node, mass)| NodeWrapper {
+            cont: node.cont,
+            div: node.div,
+            bounds: node.bounds,
+            range: node.into_range(),
+            mass,
         })
         .collect();
 
     CompleteTreeContainer::from_preorder(k).unwrap()
 }
-fn convert_wrapper_into_tree<T: Aabb, M: Default>(
-    tree: TreeInner<NodeWrapper<T, M>>,
-) -> TreeInner<Node<T>> {
-    let nt: Vec<_> = tree
-        .into_nodes()
-        .into_vec()
-        .into_iter()
-        .map(|node| node.node)
-        .collect();
 
-    CompleteTreeContainer::from_preorder(nt).unwrap()
+///Perform nbody in parallel without taking ownership of the tree.
+///
+///`masses` is a buffer with one entry per node, in the same pre-order as [`crate::Tree::get_nodes`].
+///It is overwritten, and after the call contains the aggregated mass of each subtree.
+///
+///Panics if the length of `masses` does not equal the number of nodes.
+pub fn nbody_with_masses_mut_par<N: Nbody>(
+    tree: &mut crate::Tree<N::T>,
+    masses: &mut [N::Mass],
+    no: &mut N,
+) where
+    N: Send + Sync + Splitter,
+    N::T: Send + Sync,
+    <N::T as Aabb>::Num: Send + Sync,
+    N::Mass: Send + Sync,
+{
+    //calculate node masses of each node.
+    super::aggregate::build_into(tree, &mut MassAggregate(no), masses);
+
+    let mut newtree = build_wrapper(tree, masses);
+
+    let par = par::ParallelBuilder::new().build_for_tree_of_height(newtree.get_height());
+
+    recc_par(default_axis(), par, newtree.vistr_mut(), no);
+
+    apply_tree(newtree.vistr_mut(), no);
 }
 
-///Perform nbody
-///The tree is taken by value so that its nodes can be expended to include more data.
-pub fn nbody_mut_par<'a, N: Nbody>(tree: crate::Tree<'a, N::T>, no: &mut N) -> crate::Tree<'a, N::T>
+///Perform nbody without taking ownership of the tree.
+///
+///`masses` is a buffer with one entry per node, in the same pre-order as [`crate::Tree::get_nodes`].
+///It is overwritten, and after the call contains the aggregated mass of each subtree.
+///
+///Panics if the length of `masses` does not equal the number of nodes.
+pub fn nbody_with_masses_mut<N: Nbody>(
+    tree: &mut crate::Tree<N::T>,
+    masses: &mut [N::Mass],
+    no: &mut N,
+) {
+    //calculate node masses of each node.
+    super::aggregate::build_into(tree, &mut MassAggregate(no), masses);
+
+    let mut newtree = build_wrapper(tree, masses);
+
+    recc(default_axis(), newtree.vistr_mut(), no);
+
+    apply_tree(newtree.vistr_mut(), no);
+}
+
+///Perform nbody in parallel.
+///The tree is taken by value for backwards compatibility. See [`nbody_with_masses_mut_par`].
+pub fn nbody_mut_par<'a, N: Nbody>(
+    mut tree: crate::Tree<'a, N::T>,
+    no: &mut N,
+) -> crate::Tree<'a, N::T>
 where
     N: Send + Sync + Splitter,
     N::T: Send + Sync,
     <N::T as Aabb>::Num: Send + Sync,
     N::Mass: Send + Sync,
 {
-    let num_aabbs=tree.num_aabbs();
+    let mut masses = vec![N::Mass::default(); tree.num_nodes()];
+    nbody_with_masses_mut_par(&mut tree, &mut masses, no);
+    tree
+}
 
-    let mut newtree = convert_tree_into_wrapper(tree.into_inner());
+///Perform nbody.
+///The tree is taken by value for backwards compatibility. See [`nbody_with_masses_mut`].
+pub fn nbody_mut<'a, N: Nbody>(
+    mut tree: crate::Tree<'a, N::T>,
+    no: &mut N,
+) -> crate::Tree<'a, N::T> {
+    let mut masses = vec![N::Mass::default(); tree.num_nodes()];
+    nbody_with_masses_mut(&mut tree, &mut masses, no);
+    tree
+}
 
-    //calculate node masses of each node.
-    build_masses2(newtree.vistr_mut(), no);
+///Number types that can be used with [`NbodyBuilder`].
+pub trait NbodyNum: Num + num_traits::Signed + core::fmt::Debug {
+    ///The square root.
+    fn sqrt(self) -> Self;
+}
+impl NbodyNum for f32 {
+    #[inline(always)]
+    fn sqrt(self) -> Self {
+        f32::sqrt(self)
+    }
+}
+impl NbodyNum for f64 {
+    #[inline(always)]
+    fn sqrt(self) -> Self {
+        f64::sqrt(self)
+    }
+}
 
+///The per node aggregate used by [`NbodyBuilder`].
+///The center and bounds of a node are weighted by the absolute value
+///of each mass so that signed masses like electric charges are supported.
+#[derive(Copy, Clone, Debug)]
+pub struct BodyMass<N> {
+    pub center: Vec2<N>,
+    pub mass: N,
+    weight: N,
+    bounds: Option<Rect<N>>,
+    //The force per unit mass that every element in the subtree experiences.
+    field: Vec2<N>,
+}
+impl<N: NbodyNum> Default for BodyMass<N> {
+    fn default() -> Self {
+        BodyMass {
+            center: vec2(N::zero(), N::zero()),
+            mass: N::zero(),
+            weight: N::zero(),
+            bounds: None,
+            field: vec2(N::zero(), N::zero()),
+        }
+    }
+}
 
-    let par=par::ParallelBuilder::new().build_for_tree_of_height(newtree.get_height());
+///A high level Barnes–Hut style interface on top of [`Nbody`].
+///
+///A subtree is approximated as a single body once the ratio between its size
+///and its distance to the bounds of the bodies it is being compared against is less than `theta`.
+///Only the distance along the axis of the divider between them is considered,
+///so this is more conservative than the usual Barnes–Hut criterion.
+///A `theta` of zero computes the exact result.
+///
+///Each element is treated as a point body whose position is returned by the user.
+///The position should lie inside its aabb, and the aabbs should be small relative
+///to the distances between bodies for the approximation to be accurate.
+#[derive(Copy, Clone)]
+pub struct NbodyBuilder<N, F> {
+    theta: N,
+    force: F,
+}
 
-    recc_par(default_axis(), par, newtree.vistr_mut(), no);
+///Create a [`NbodyBuilder`] from a force function.
+///
+///`force(offset, ma, mb)` returns the force a body with mass `mb` at `offset` exerts on a
+///body with mass `ma` at the origin. It must be proportional to `ma`.
+pub fn builder<N: NbodyNum, F: Fn(Vec2<N>, N, N) -> Vec2<N> + Clone>(
+    force: F,
+) -> NbodyBuilder<N, F> {
+    NbodyBuilder {
+        theta: N::one(),
+        force,
+    }
+}
 
-    apply_tree(newtree.vistr_mut(), no);
+fn inverse_square<N: NbodyNum>(
+    constant: N,
+    softening: N,
+    offset: Vec2<N>,
+    ma: N,
+    mb: N,
+) -> Vec2<N> {
+    let dis2 = offset.x * offset.x + offset.y * offset.y + softening * softening;
+    let mag = constant * ma * mb / (dis2 * dis2.sqrt());
+    vec2(offset.x * mag, offset.y * mag)
+}
 
-    unsafe{
-        crate::Tree::from_raw_parts(convert_wrapper_into_tree(newtree),num_aabbs)
+///Create a [`NbodyBuilder`] where bodies attract with the specified gravitational constant.
+///The `softening` length is added to every distance to avoid singularities.
+pub fn gravity<N: NbodyNum>(
+    g: N,
+    softening: N,
+) -> NbodyBuilder<N, impl Fn(Vec2<N>, N, N) -> Vec2<N> + Copy + Send + Sync>
+where
+    N: Send + Sync,
+{
+    builder(move |offset, ma, mb| inverse_square(g, softening, offset, ma, mb))
+}
+
+///Create a [`NbodyBuilder`] where bodies with the same sign repel and bodies with different signs attract
+///with the specified Coulomb constant.
+///The `softening` length is added to every distance to avoid singularities.
+pub fn coulomb<N: NbodyNum>(
+    k: N,
+    softening: N,
+) -> NbodyBuilder<N, impl Fn(Vec2<N>, N, N) -> Vec2<N> + Copy + Send + Sync>
+where
+    N: Send + Sync,
+{
+    builder(move |offset, ma, mb| inverse_square(-k, softening, offset, ma, mb))
+}
+
+impl<N: NbodyNum, F: Fn(Vec2<N>, N, N) -> Vec2<N> + Clone> NbodyBuilder<N, F> {
+    ///Set the opening angle. The default is `1`.
+    #[must_use]
+    pub fn with_theta(mut self, theta: N) -> Self {
+        self.theta = theta;
+        self
+    }
+
+    ///Perform nbody.
+    ///
+    ///`body` returns the position and mass of an element.
+    ///`apply` is called with the total force exerted on an element.
+    ///It may be called multiple times for the same element.
+    pub fn nbody_mut<'a, T: Aabb<Num = N>>(
+        self,
+        tree: crate::Tree<'a, T>,
+        body: impl Fn(&T) -> (Vec2<N>, N) + Clone,
+        apply: impl Fn(PMut<T>, Vec2<N>) + Clone,
+    ) -> crate::Tree<'a, T> {
+        nbody_mut(tree, &mut self.into_handler(body, apply))
+    }
+
+    ///The parallel version of [`NbodyBuilder::nbody_mut`].
+    pub fn nbody_mut_par<'a, T: Aabb<Num = N> + Send + Sync>(
+        self,
+        tree: crate::Tree<'a, T>,
+        body: impl Fn(&T) -> (Vec2<N>, N) + Clone + Send + Sync,
+        apply: impl Fn(PMut<T>, Vec2<N>) + Clone + Send + Sync,
+    ) -> crate::Tree<'a, T>
+    where
+        N: Send + Sync,
+        F: Send + Sync,
+    {
+        nbody_mut_par(tree, &mut self.into_handler(body, apply))
+    }
+
+    ///Perform nbody without taking ownership of the tree. See [`nbody_with_masses_mut`].
+    ///
+    /// # Examples
+    ///
+    ///```
+    /// use broccoli::{bbox,rect};
+    /// use broccoli::query::nbody;
+    /// use axgeom::vec2;
+    ///
+    /// let mut bots = [bbox(rect(0.0f64, 1.0, 0.0, 1.0), 0.0f64), bbox(rect(5.0, 6.0, 0.0, 1.0), 0.0)];
+    /// let mut tree = broccoli::new(&mut bots);
+    ///
+    /// let mut masses = vec![Default::default(); tree.num_nodes()];
+    /// nbody::gravity(1.0, 0.0).nbody_with_masses_mut(
+    ///     &mut tree,
+    ///     &mut masses,
+    ///     |a| (vec2(a.rect.x.start, a.rect.y.start), 1.0),
+    ///     |a, f| *a.unpack_inner() += f.x,
+    /// );
+    ///
+    /// assert_eq!(masses[0].mass, 2.0);
+    ///
+    /// //The bodies are pulled towards each other.
+    /// for b in bots.iter() {
+    ///     assert_eq!(b.inner, if b.rect.x.start == 0.0 { 0.04 } else { -0.04 });
+    /// }
+    ///```
+    pub fn nbody_with_masses_mut<T: Aabb<Num = N>>(
+        self,
+        tree: &mut crate::Tree<T>,
+        masses: &mut [BodyMass<N>],
+        body: impl Fn(&T) -> (Vec2<N>, N) + Clone,
+        apply: impl Fn(PMut<T>, Vec2<N>) + Clone,
+    ) {
+        nbody_with_masses_mut(tree, masses, &mut self.into_handler(body, apply))
+    }
+
+    ///The parallel version of [`NbodyBuilder::nbody_with_masses_mut`].
+    pub fn nbody_with_masses_mut_par<T: Aabb<Num = N> + Send + Sync>(
+        self,
+        tree: &mut crate::Tree<T>,
+        masses: &mut [BodyMass<N>],
+        body: impl Fn(&T) -> (Vec2<N>, N) + Clone + Send + Sync,
+        apply: impl Fn(PMut<T>, Vec2<N>) + Clone + Send + Sync,
+    ) where
+        N: Send + Sync,
+        F: Send + Sync,
+    {
+        nbody_with_masses_mut_par(tree, masses, &mut self.into_handler(body, apply))
+    }
+
+    ///Compute the exact forces by visiting every pair using [`naive_mut`].
+    pub fn naive_mut<T: Aabb<Num = N>>(
+        self,
+        bots: PMut<[T]>,
+        body: impl Fn(&T) -> (Vec2<N>, N),
+        apply: impl Fn(PMut<T>, Vec2<N>),
+    ) {
+        let force = self.force;
+        naive_mut(bots, |a, b| {
+            let (pa, ma) = body(&a);
+            let (pb, mb) = body(&b);
+            let f = force(vec2(pb.x - pa.x, pb.y - pa.y), ma, mb);
+            apply(a, f);
+            apply(b, vec2(-f.x, -f.y));
+        });
+    }
+
+    fn into_handler<T: Aabb<Num = N>, B, A>(self, body: B, apply: A) -> BarnesHut<T, N, F, B, A> {
+        BarnesHut {
+            _p: PhantomData,
+            theta: self.theta,
+            force: self.force,
+            body,
+            apply,
+        }
     }
 }
 
-///Perform nbody
-///The tree is taken by value so that its nodes can be expended to include more data.
-pub fn nbody_mut<'a, N: Nbody>(tree: crate::Tree<'a, N::T>, no: &mut N) -> crate::Tree<'a, N::T> {
-    let num_aabbs=tree.num_aabbs();
+struct BarnesHut<T, N, F, B, A> {
+    _p: PhantomData<T>,
+    theta: N,
+    force: F,
+    body: B,
+    apply: A,
+}
 
-    let mut newtree = convert_tree_into_wrapper(tree.into_inner());
+impl<T, N: Copy, F: Clone, B: Clone, A: Clone> Splitter for BarnesHut<T, N, F, B, A> {
+    fn div(&mut self) -> (Self, Self) {
+        let f = || BarnesHut {
+            _p: PhantomData,
+            theta: self.theta,
+            force: self.force.clone(),
+            body: self.body.clone(),
+            apply: self.apply.clone(),
+        };
+        (f(), f())
+    }
+    fn add(&mut self, _: Self, _: Self) {}
+}
 
-    //calculate node masses of each node.
-    build_masses2(newtree.vistr_mut(), no);
+impl<T: Aabb<Num = N>, N: NbodyNum, F, B, A> BarnesHut<T, N, F, B, A>
+where
+    F: Fn(Vec2<N>, N, N) -> Vec2<N>,
+    B: Fn(&T) -> (Vec2<N>, N),
+    A: Fn(PMut<T>, Vec2<N>),
+{
+    fn is_far(&self, a: &BodyMass<N>, line: N, axis: impl Axis) -> bool {
+        match &a.bounds {
+            Some(bounds) => {
+                let r = bounds.get_range(axis);
+                let dis = if line < r.start {
+                    r.start - line
+                } else if line > r.end {
+                    line - r.end
+                } else {
+                    return false;
+                };
+                let w = bounds.x.end - bounds.x.start;
+                let h = bounds.y.end - bounds.y.start;
+                let size = if w > h { w } else { h };
+                size < self.theta * dis
+            }
+            None => true,
+        }
+    }
 
-    recc(default_axis(), newtree.vistr_mut(), no);
+    fn body_body(&self, a: PMut<T>, b: PMut<T>) {
+        let (pa, ma) = (self.body)(&a);
+        let (pb, mb) = (self.body)(&b);
+        let f = (self.force)(vec2(pb.x - pa.x, pb.y - pa.y), ma, mb);
+        (self.apply)(a, f);
+        (self.apply)(b, vec2(-f.x, -f.y));
+    }
 
-    apply_tree(newtree.vistr_mut(), no);
-    unsafe{
-        crate::Tree::from_raw_parts(convert_wrapper_into_tree(newtree),num_aabbs)
+    fn body_mass(&self, a: PMut<T>, b: &mut BodyMass<N>) {
+        if b.weight == N::zero() {
+            return;
+        }
+        let (pa, ma) = (self.body)(&a);
+        let offset = vec2(b.center.x - pa.x, b.center.y - pa.y);
+        let f = (self.force)(offset, ma, b.mass);
+        (self.apply)(a, f);
+        let g = (self.force)(vec2(-offset.x, -offset.y), N::one(), ma);
+        b.field = vec2(b.field.x + g.x, b.field.y + g.y);
+    }
+}
+
+impl<T: Aabb<Num = N>, N: NbodyNum, F, B, A> Nbody for BarnesHut<T, N, F, B, A>
+where
+    F: Fn(Vec2<N>, N, N) -> Vec2<N>,
+    B: Fn(&T) -> (Vec2<N>, N),
+    A: Fn(PMut<T>, Vec2<N>),
+{
+    type T = T;
+    type N = N;
+    type Mass = BodyMass<N>;
+
+    fn compute_center_of_mass(&mut self, a: &[T]) -> BodyMass<N> {
+        let mut m = BodyMass::default();
+        for b in a.iter() {
+            let (pos, mass) = (self.body)(b);
+            let single = BodyMass {
+                center: pos,
+                mass,
+                weight: mass.abs(),
+                bounds: Some(Rect {
+                    x: Range {
+                        start: pos.x,
+                        end: pos.x,
+                    },
+                    y: Range {
+                        start: pos.y,
+                        end: pos.y,
+                    },
+                }),
+                field: vec2(N::zero(), N::zero()),
+            };
+            m = self.combine_two_masses(&m, &single);
+        }
+        m
+    }
+
+    fn is_close(&mut self, a: &BodyMass<N>, line: N, axis: impl Axis) -> bool {
+        !self.is_far(a, line, axis)
+    }
+
+    fn is_close_half(&mut self, a: &BodyMass<N>, line: N, axis: impl Axis) -> bool {
+        !self.is_far(a, line, axis)
+    }
+
+    fn gravitate(&mut self, a: GravEnum<T, BodyMass<N>>, b: GravEnum<T, BodyMass<N>>) {
+        match (a, b) {
+            (GravEnum::Mass(a), GravEnum::Mass(b)) => {
+                if a.weight == N::zero() || b.weight == N::zero() {
+                    return;
+                }
+                let offset = vec2(b.center.x - a.center.x, b.center.y - a.center.y);
+                let f = (self.force)(offset, N::one(), b.mass);
+                a.field = vec2(a.field.x + f.x, a.field.y + f.y);
+                let f = (self.force)(vec2(-offset.x, -offset.y), N::one(), a.mass);
+                b.field = vec2(b.field.x + f.x, b.field.y + f.y);
+            }
+            (GravEnum::Mass(a), GravEnum::Bot(b)) | (GravEnum::Bot(b), GravEnum::Mass(a)) => {
+                for b in b.iter_mut() {
+                    self.body_mass(b, a);
+                }
+            }
+            (GravEnum::Bot(mut a), GravEnum::Bot(mut b)) => {
+                for a in a.borrow_mut().iter_mut() {
+                    let mut a = a;
+                    for b in b.borrow_mut().iter_mut() {
+                        self.body_body(a.borrow_mut(), b);
+                    }
+                }
+            }
+        }
+    }
+
+    fn gravitate_self(&mut self, a: PMut<[T]>) {
+        tools::for_every_pair(a, |a, b| self.body_body(a, b));
+    }
+
+    fn apply_a_mass(&mut self, mass: BodyMass<N>, i: PMut<[T]>) {
+        if mass.field.x == N::zero() && mass.field.y == N::zero() {
+            return;
+        }
+        for b in i.iter_mut() {
+            let (_, m) = (self.body)(&b);
+            (self.apply)(b, vec2(mass.field.x * m, mass.field.y * m));
+        }
+    }
+
+    fn combine_two_masses(&mut self, a: &BodyMass<N>, b: &BodyMass<N>) -> BodyMass<N> {
+        let weight = a.weight + b.weight;
+        if weight == N::zero() {
+            return BodyMass {
+                mass: a.mass + b.mass,
+                ..BodyMass::default()
+            };
+        }
+        let center = vec2(
+            (a.center.x * a.weight + b.center.x * b.weight) / weight,
+            (a.center.y * a.weight + b.center.y * b.weight) / weight,
+        );
+        let bounds = match (a.bounds, b.bounds) {
+            (Some(mut x), Some(y)) => {
+                tools::grow_range(&mut x.x, &y.x);
+                tools::grow_range(&mut x.y, &y.y);
+                Some(x)
+            }
+            (x, None) => x,
+            (None, y) => y,
+        };
+        BodyMass {
+            center,
+            mass: a.mass + b.mass,
+            weight,
+            bounds,
+            field: vec2(N::zero(), N::zero()),
+        }
     }
 }
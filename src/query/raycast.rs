@@ -237,6 +237,24 @@ impl<'a, R: RayCast> Blap<'a, R> {
             axgeom::CastResult::NoHit => false,
         }
     }
+
+    //Returns false if the ray can't reach a closer element inside of the subtree bounds.
+    fn should_recurse_bounds(&mut self, bounds: &Option<Rect<R::N>>) -> bool {
+        let b = match bounds {
+            Some(b) => *b,
+            None => return false,
+        };
+        self.should_recurse_range(axgeom::XAXIS, &b.x)
+            && self.should_recurse_range(axgeom::YAXIS, &b.y)
+    }
+
+    fn should_recurse_range<A: Axis>(&mut self, axis: A, range: &Range<R::N>) -> bool {
+        match range.contains_ext(*self.ray.point.get_axis(axis)) {
+            core::cmp::Ordering::Less => self.should_recurse((axis, range.start)),
+            core::cmp::Ordering::Greater => self.should_recurse((axis, range.end)),
+            core::cmp::Ordering::Equal => true,
+        }
+    }
 }
 
 //Returns the first object that touches the ray.
@@ -246,6 +264,9 @@ fn recc<'a, 'b: 'a, A: Axis, T: Aabb, R: RayCast<N = T::Num, T = T>>(
     blap: &mut Blap<'a, R>,
 ) {
     let ((_depth, nn), rest) = stuff.next();
+    if !blap.should_recurse_bounds(&nn.bounds) {
+        return;
+    }
     let handle_curr = if let Some([left, right]) = rest {
         let axis_next = axis.next();
 
@@ -12,6 +12,12 @@ macro_rules! rect {
         ) {
             let (nn, rest) = m.next();
             //let nn = nn.$get_node();
+
+            //no element in this subtree can touch the rect.
+            if !crate::query::tools::bounds_touch(&nn.bounds, rect) {
+                return;
+            }
+
             match rest {
                 Some([left, right]) => {
                     let div = match nn.div {
@@ -50,6 +56,101 @@ macro_rules! rect {
                 }
             }
         }
+
+        fn rect_recurse_par<
+            'a,
+            A: Axis,
+            T: Aabb + Send + Sync,
+            JJ: par::Joiner,
+            F: Fn($colsingle) + Send + Sync,
+        >(
+            this_axis: A,
+            par: JJ,
+            m: $iterator,
+            rect: &Rect<T::Num>,
+            func: &F,
+        ) where
+            T::Num: Send + Sync,
+        {
+            let (nn, rest) = m.next();
+
+            //no element in this subtree can touch the rect.
+            if !crate::query::tools::bounds_touch(&nn.bounds, rect) {
+                return;
+            }
+
+            match rest {
+                Some([left, right]) => {
+                    let div = match nn.div {
+                        Some(b) => b,
+                        None => return,
+                    };
+
+                    let sl = $get_section(
+                        this_axis.next(),
+                        $get_bots(nn),
+                        *rect.get_range(this_axis.next()),
+                    );
+
+                    for i in sl {
+                        func(i);
+                    }
+                    let rr = rect.get_range(this_axis);
+                    let l = div >= rr.start;
+                    let r = div <= rr.end;
+
+                    match par.next() {
+                        par::ParResult::Parallel([dleft, dright]) => {
+                            if l && r {
+                                rayon::join(
+                                    || {
+                                        self::rect_recurse_par(
+                                            this_axis.next(),
+                                            dleft,
+                                            left,
+                                            rect,
+                                            func,
+                                        )
+                                    },
+                                    || {
+                                        self::rect_recurse_par(
+                                            this_axis.next(),
+                                            dright,
+                                            right,
+                                            rect,
+                                            func,
+                                        )
+                                    },
+                                );
+                            } else if l {
+                                self::rect_recurse_par(this_axis.next(), dleft, left, rect, func);
+                            } else if r {
+                                self::rect_recurse_par(this_axis.next(), dright, right, rect, func);
+                            }
+                        }
+                        par::ParResult::Sequential(_) => {
+                            if l {
+                                self::rect_recurse(this_axis.next(), left, rect, &mut |a| func(a));
+                            }
+                            if r {
+                                self::rect_recurse(this_axis.next(), right, rect, &mut |a| func(a));
+                            }
+                        }
+                    }
+                }
+                None => {
+                    let sl = $get_section(
+                        this_axis.next(),
+                        $get_bots(nn),
+                        *rect.get_range(this_axis.next()),
+                    );
+
+                    for i in sl {
+                        func(i);
+                    }
+                }
+            }
+        }
     };
 }
 
@@ -66,62 +167,216 @@ pub fn naive_for_all_not_in_rect_mut<'a, T: Aabb>(
     }
 }
 
-fn for_all_not_in_rect_mut<'a, 'b: 'a, A: Axis, T: Aabb>(
-    axis: A,
-    vistr: VistrMut<'a, Node<'b, T>>,
-    rect: &Rect<T::Num>,
-    closure: impl FnMut(PMut<'a, T>),
-) {
-    fn rect_recurse<'a, 'b: 'a, A: Axis, T: Aabb, F: FnMut(PMut<'a, T>)>(
-        axis: A,
-        it: VistrMut<'a, Node<'b, T>>,
-        rect: &Rect<T::Num>,
-        mut closure: F,
-    ) -> F {
-        let (nn, rest) = it.next();
+macro_rules! not_in_rect {
+    ($iterator:ty,$colsingle:ty,$get_bots:ident) => {
+        fn not_in_rect_recurse<'a, A: Axis, T: Aabb, F: FnMut($colsingle)>(
+            axis: A,
+            it: $iterator,
+            rect: &Rect<T::Num>,
+            mut closure: F,
+        ) -> F {
+            let (nn, rest) = it.next();
+            let div = nn.div;
 
-        let NodeRef { div, range, .. } = nn.into_node_ref();
+            for a in $get_bots(nn) {
+                if !rect.contains_rect(a.get()) {
+                    closure(a);
+                }
+            }
 
-        for a in range.iter_mut() {
-            if !rect.contains_rect(a.get()) {
-                closure(a);
+            match (rest, div) {
+                (Some([left, right]), Some(div)) => {
+                    self::not_in_rect_children(axis, div, left, right, rect, closure)
+                }
+                _ => closure,
             }
         }
 
-        match rest {
-            Some([left, right]) => {
-                let div = match div {
-                    Some(b) => b,
-                    None => return closure,
-                };
-
-                match rect.get_range(axis).contains_ext(*div) {
-                    core::cmp::Ordering::Greater => {
-                        for a in right.into_slice() {
-                            for b in a.into_range().iter_mut() {
-                                closure(b)
-                            }
+        fn not_in_rect_children<'a, A: Axis, T: Aabb, F: FnMut($colsingle)>(
+            axis: A,
+            div: T::Num,
+            left: $iterator,
+            right: $iterator,
+            rect: &Rect<T::Num>,
+            mut closure: F,
+        ) -> F {
+            match rect.get_range(axis).contains_ext(div) {
+                core::cmp::Ordering::Greater => {
+                    for a in right.into_slice() {
+                        for b in $get_bots(a) {
+                            closure(b)
                         }
-                        rect_recurse(axis.next(), left, rect, closure)
                     }
-                    core::cmp::Ordering::Less => {
-                        for a in left.into_slice() {
-                            for b in a.into_range().iter_mut() {
-                                closure(b)
-                            }
+                    self::not_in_rect_recurse(axis.next(), left, rect, closure)
+                }
+                core::cmp::Ordering::Less => {
+                    for a in left.into_slice() {
+                        for b in $get_bots(a) {
+                            closure(b)
                         }
-                        rect_recurse(axis.next(), right, rect, closure)
                     }
-                    core::cmp::Ordering::Equal => {
-                        let closure = rect_recurse(axis.next(), left, rect, closure);
-                        rect_recurse(axis.next(), right, rect, closure)
+                    self::not_in_rect_recurse(axis.next(), right, rect, closure)
+                }
+                core::cmp::Ordering::Equal => {
+                    let closure = self::not_in_rect_recurse(axis.next(), left, rect, closure);
+                    self::not_in_rect_recurse(axis.next(), right, rect, closure)
+                }
+            }
+        }
+
+        fn not_in_rect_recurse_par<
+            'a,
+            A: Axis,
+            T: Aabb + Send + Sync,
+            JJ: par::Joiner,
+            F: Fn($colsingle) + Send + Sync,
+        >(
+            axis: A,
+            par: JJ,
+            it: $iterator,
+            rect: &Rect<T::Num>,
+            closure: &F,
+        ) where
+            T::Num: Send + Sync,
+        {
+            let (nn, rest) = it.next();
+            let div = nn.div;
+
+            for a in $get_bots(nn) {
+                if !rect.contains_rect(a.get()) {
+                    closure(a);
+                }
+            }
+
+            let (left, right, div) = match (rest, div) {
+                (Some([left, right]), Some(div)) => (left, right, div),
+                _ => return,
+            };
+
+            //Every element of a subtree that is entirely outside of the rect is visited.
+            let all = |it: $iterator| {
+                for a in it.into_slice() {
+                    for b in $get_bots(a) {
+                        closure(b)
+                    }
+                }
+            };
+
+            match par.next() {
+                par::ParResult::Parallel([dleft, dright]) => {
+                    match rect.get_range(axis).contains_ext(div) {
+                        core::cmp::Ordering::Greater => {
+                            rayon::join(
+                                || all(right),
+                                || {
+                                    self::not_in_rect_recurse_par(
+                                        axis.next(),
+                                        dleft,
+                                        left,
+                                        rect,
+                                        closure,
+                                    )
+                                },
+                            );
+                        }
+                        core::cmp::Ordering::Less => {
+                            rayon::join(
+                                || all(left),
+                                || {
+                                    self::not_in_rect_recurse_par(
+                                        axis.next(),
+                                        dright,
+                                        right,
+                                        rect,
+                                        closure,
+                                    )
+                                },
+                            );
+                        }
+                        core::cmp::Ordering::Equal => {
+                            rayon::join(
+                                || {
+                                    self::not_in_rect_recurse_par(
+                                        axis.next(),
+                                        dleft,
+                                        left,
+                                        rect,
+                                        closure,
+                                    )
+                                },
+                                || {
+                                    self::not_in_rect_recurse_par(
+                                        axis.next(),
+                                        dright,
+                                        right,
+                                        rect,
+                                        closure,
+                                    )
+                                },
+                            );
+                        }
                     }
                 }
+                par::ParResult::Sequential(_) => {
+                    self::not_in_rect_children(axis, div, left, right, rect, closure);
+                }
             }
-            None => closure,
         }
-    }
-    rect_recurse(axis, vistr, rect, closure);
+    };
+}
+
+macro_rules! point {
+    ($iterator:ty,$colsingle:ty,$get_section:ident,$get_bots:ident) => {
+        fn point_recurse<'a, A: Axis, T: Aabb, F: FnMut($colsingle)>(
+            this_axis: A,
+            m: $iterator,
+            point: Vec2<T::Num>,
+            func: &mut F,
+        ) {
+            let (nn, rest) = m.next();
+            let p = *point.get_axis(this_axis);
+            let next = match rest {
+                Some([left, right]) => {
+                    let div = match nn.div {
+                        Some(b) => b,
+                        None => return,
+                    };
+
+                    //Elements in the left and right children end before or start after
+                    //the divider, so at most one of them can contain the point.
+                    let next = if p < div {
+                        Some(left)
+                    } else if p > div {
+                        Some(right)
+                    } else {
+                        None
+                    };
+
+                    if !nn.cont.contains(p) {
+                        if let Some(next) = next {
+                            self::point_recurse(this_axis.next(), next, point, func);
+                        }
+                        return;
+                    }
+                    next
+                }
+                None => None,
+            };
+
+            let q = *point.get_axis(this_axis.next());
+            let sl = $get_section(this_axis.next(), $get_bots(nn), Range { start: q, end: q });
+
+            for i in sl {
+                if i.get().contains_point(point) {
+                    func(i);
+                }
+            }
+
+            if let Some(next) = next {
+                self::point_recurse(this_axis.next(), next, point, func);
+            }
+        }
+    };
 }
 
 use constant::*;
@@ -134,6 +389,83 @@ mod mutable {
         node.into_range()
     }
     rect!(VistrMut<'a, Node<T>>, PMut<'a, T>, get_section_mut, foo);
+    point!(VistrMut<'a, Node<T>>, PMut<'a, T>, get_section_mut, foo);
+    not_in_rect!(VistrMut<'a, Node<T>>, PMut<'a, T>, foo);
+
+    pub(super) fn for_all_not_in_rect_mut<'a, 'b: 'a, A: Axis, T: Aabb>(
+        axis: A,
+        vistr: VistrMut<'a, Node<'b, T>>,
+        rect: &Rect<T::Num>,
+        closure: impl FnMut(PMut<'a, T>),
+    ) {
+        self::not_in_rect_recurse(axis, vistr, rect, closure);
+    }
+
+    pub(super) fn for_all_not_in_rect_par_mut<'a, 'b: 'a, A: Axis, T: Aabb + Send + Sync>(
+        axis: A,
+        vistr: VistrMut<'a, Node<'b, T>>,
+        rect: &Rect<T::Num>,
+        closure: impl Fn(PMut<'a, T>) + Send + Sync,
+    ) where
+        T::Num: Send + Sync,
+    {
+        let par = par::ParallelBuilder::new().build_for_tree_of_height(vistr.get_height());
+        self::not_in_rect_recurse_par(axis, par, vistr, rect, &closure);
+    }
+
+    pub(super) fn for_all_intersect_rect_par_mut<'a, 'b: 'a, A: Axis, T: Aabb + Send + Sync>(
+        axis: A,
+        vistr: VistrMut<'a, Node<'b, T>>,
+        rect: &Rect<T::Num>,
+        closure: impl Fn(PMut<'a, T>) + Send + Sync,
+    ) where
+        T::Num: Send + Sync,
+    {
+        let par = par::ParallelBuilder::new().build_for_tree_of_height(vistr.get_height());
+        self::rect_recurse_par(axis, par, vistr, rect, &|a: PMut<'a, T>| {
+            if rect.get_intersect_rect(a.get()).is_some() {
+                closure(a);
+            }
+        });
+    }
+
+    pub(super) fn for_all_in_rect_par_mut<'a, 'b: 'a, A: Axis, T: Aabb + Send + Sync>(
+        axis: A,
+        vistr: VistrMut<'a, Node<'b, T>>,
+        rect: &Rect<T::Num>,
+        closure: impl Fn(PMut<'a, T>) + Send + Sync,
+    ) where
+        T::Num: Send + Sync,
+    {
+        let par = par::ParallelBuilder::new().build_for_tree_of_height(vistr.get_height());
+        self::rect_recurse_par(axis, par, vistr, rect, &|a: PMut<'a, T>| {
+            if rect.contains_rect(a.get()) {
+                closure(a);
+            }
+        });
+    }
+    pub(super) fn for_all_containing_point_mut<'a, 'b: 'a, A: Axis, T: Aabb>(
+        axis: A,
+        vistr: VistrMut<'a, Node<'b, T>>,
+        point: Vec2<T::Num>,
+        mut closure: impl FnMut(PMut<'a, T>),
+    ) {
+        self::point_recurse(axis, vistr, point, &mut |a| closure(a));
+    }
+
+    ///Naive implementation
+    pub fn naive_for_all_containing_point_mut<'a, T: Aabb>(
+        bots: PMut<'a, [T]>,
+        point: Vec2<T::Num>,
+        mut closure: impl FnMut(PMut<'a, T>),
+    ) {
+        for b in bots.iter_mut() {
+            if b.get().contains_point(point) {
+                closure(b);
+            }
+        }
+    }
+
     pub(super) fn for_all_intersect_rect_mut<'a, 'b: 'a, A: Axis, T: Aabb>(
         axis: A,
         vistr: VistrMut<'a, Node<'b, T>>,
@@ -194,6 +526,139 @@ mod constant {
         &node.range
     }
     rect!(Vistr<'a, Node<T>>, &'a T, get_section, foo);
+    point!(Vistr<'a, Node<T>>, &'a T, get_section, foo);
+    not_in_rect!(Vistr<'a, Node<T>>, &'a T, foo);
+
+    pub(super) fn for_all_not_in_rect<'a, 'b: 'a, A: Axis, T: Aabb>(
+        axis: A,
+        vistr: Vistr<'a, Node<'b, T>>,
+        rect: &Rect<T::Num>,
+        closure: impl FnMut(&'a T),
+    ) {
+        self::not_in_rect_recurse(axis, vistr, rect, closure);
+    }
+
+    pub(super) fn for_all_not_in_rect_par<'a, 'b: 'a, A: Axis, T: Aabb + Send + Sync>(
+        axis: A,
+        vistr: Vistr<'a, Node<'b, T>>,
+        rect: &Rect<T::Num>,
+        closure: impl Fn(&'a T) + Send + Sync,
+    ) where
+        T::Num: Send + Sync,
+    {
+        let par = par::ParallelBuilder::new()
+            .build_for_tree_of_height(compt::FixedDepthVisitor::get_height(&vistr));
+        self::not_in_rect_recurse_par(axis, par, vistr, rect, &closure);
+    }
+
+    pub(super) fn for_all_intersect_rect_par<'a, 'b: 'a, A: Axis, T: Aabb + Send + Sync>(
+        axis: A,
+        vistr: Vistr<'a, Node<'b, T>>,
+        rect: &Rect<T::Num>,
+        closure: impl Fn(&'a T) + Send + Sync,
+    ) where
+        T::Num: Send + Sync,
+    {
+        let par = par::ParallelBuilder::new()
+            .build_for_tree_of_height(compt::FixedDepthVisitor::get_height(&vistr));
+        self::rect_recurse_par(axis, par, vistr, rect, &|a: &'a T| {
+            if rect.get_intersect_rect(a.get()).is_some() {
+                closure(a);
+            }
+        });
+    }
+
+    pub(super) fn for_all_in_rect_par<'a, 'b: 'a, A: Axis, T: Aabb + Send + Sync>(
+        axis: A,
+        vistr: Vistr<'a, Node<'b, T>>,
+        rect: &Rect<T::Num>,
+        closure: impl Fn(&'a T) + Send + Sync,
+    ) where
+        T::Num: Send + Sync,
+    {
+        let par = par::ParallelBuilder::new()
+            .build_for_tree_of_height(compt::FixedDepthVisitor::get_height(&vistr));
+        self::rect_recurse_par(axis, par, vistr, rect, &|a: &'a T| {
+            if rect.contains_rect(a.get()) {
+                closure(a);
+            }
+        });
+    }
+
+    pub(super) fn count_in_rect<A: Axis, T: Aabb>(
+        this_axis: A,
+        m: Vistr<Node<T>>,
+        rect: &Rect<T::Num>,
+    ) -> usize {
+        let (nn, rest) = m.next();
+
+        let bounds = match &nn.bounds {
+            Some(b) => b,
+            None => return 0,
+        };
+
+        //every element in this subtree is in the rect.
+        if rect.contains_rect(bounds) {
+            return nn.subtree_len;
+        }
+
+        if !crate::query::tools::bounds_touch(&nn.bounds, rect) {
+            return 0;
+        }
+
+        let sl = get_section(
+            this_axis.next(),
+            &nn.range,
+            *rect.get_range(this_axis.next()),
+        );
+        let mut count = sl.iter().filter(|a| rect.contains_rect(a.get())).count();
+
+        if let Some([left, right]) = rest {
+            count += self::count_in_rect(this_axis.next(), left, rect);
+            count += self::count_in_rect(this_axis.next(), right, rect);
+        }
+        count
+    }
+
+    pub(super) fn find_first_intersect_rect<'a, 'b: 'a, A: Axis, T: Aabb>(
+        this_axis: A,
+        m: Vistr<'a, Node<'b, T>>,
+        rect: &Rect<T::Num>,
+    ) -> Option<&'a T> {
+        let (nn, rest) = m.next();
+
+        //no element in this subtree can touch the rect.
+        if !crate::query::tools::bounds_touch(&nn.bounds, rect) {
+            return None;
+        }
+
+        let sl = get_section(
+            this_axis.next(),
+            &nn.range,
+            *rect.get_range(this_axis.next()),
+        );
+        if let Some(a) = sl
+            .iter()
+            .find(|a| rect.get_intersect_rect(a.get()).is_some())
+        {
+            return Some(a);
+        }
+
+        match rest {
+            Some([left, right]) => self::find_first_intersect_rect(this_axis.next(), left, rect)
+                .or_else(|| self::find_first_intersect_rect(this_axis.next(), right, rect)),
+            None => None,
+        }
+    }
+
+    pub(super) fn for_all_containing_point<'a, 'b: 'a, A: Axis, T: Aabb>(
+        axis: A,
+        vistr: Vistr<'a, Node<'b, T>>,
+        point: Vec2<T::Num>,
+        mut closure: impl FnMut(&'a T),
+    ) {
+        self::point_recurse(axis, vistr, point, &mut |a| closure(a));
+    }
 
     pub(super) fn for_all_intersect_rect<'a, 'b: 'a, A: Axis, T: Aabb>(
         axis: A,
@@ -222,6 +687,80 @@ mod constant {
     }
 }
 
+//Decides if an element's rect satisfies the query rect.
+type RectTest<N> = fn(&Rect<N>, &Rect<N>) -> bool;
+
+///Iterator over the elements of a tree that satisfy a rect query.
+///See [`RectQuery::iter_in_rect`] and [`RectQuery::iter_intersect_rect`].
+///
+///Instead of recursing, the nodes that still need to be visited are kept
+///on an explicit stack, so that the query can be paused between elements.
+pub struct RectIter<'a, 'b: 'a, T: Aabb> {
+    rect: Rect<T::Num>,
+    test: RectTest<T::Num>,
+    stack: Vec<(AxisDyn, Vistr<'a, Node<'b, T>>)>,
+    current: core::slice::Iter<'a, T>,
+}
+
+impl<'a, 'b: 'a, T: Aabb> RectIter<'a, 'b, T> {
+    fn new(
+        vistr: Vistr<'a, Node<'b, T>>,
+        rect: &Rect<T::Num>,
+        test: RectTest<T::Num>,
+    ) -> Self {
+        RectIter {
+            rect: *rect,
+            test,
+            stack: vec![(default_axis().to_dyn(), vistr)],
+            current: [].iter(),
+        }
+    }
+}
+
+impl<'a, 'b: 'a, T: Aabb> Iterator for RectIter<'a, 'b, T> {
+    type Item = &'a T;
+    fn next(&mut self) -> Option<&'a T> {
+        use crate::query::tools::get_section;
+        loop {
+            let (rect, test) = (&self.rect, self.test);
+            if let Some(a) = self.current.find(|a| test(rect, a.get())) {
+                return Some(a);
+            }
+
+            let (this_axis, m) = self.stack.pop()?;
+            let (nn, rest) = m.next();
+
+            //no element in this subtree can touch the rect.
+            if !crate::query::tools::bounds_touch(&nn.bounds, rect) {
+                continue;
+            }
+
+            let range: &'a [T] = &nn.range;
+            self.current = match this_axis {
+                AxisDyn::X => get_section(YAXIS, range, rect.y),
+                AxisDyn::Y => get_section(XAXIS, range, rect.x),
+            }
+            .iter();
+
+            if let Some([left, right]) = rest {
+                let div = match nn.div {
+                    Some(b) => b,
+                    None => continue,
+                };
+                let rr = this_axis.map_val(&rect.x, &rect.y);
+
+                //push the right child first so that the left child is visited first.
+                if div <= rr.end {
+                    self.stack.push((this_axis.next(), right));
+                }
+                if div >= rr.start {
+                    self.stack.push((this_axis.next(), left));
+                }
+            }
+        }
+    }
+}
+
 ///Indicates that the user supplied a rectangle
 ///that intersects with a another one previously queries
 ///in the session.
@@ -335,6 +874,30 @@ pub fn assert_for_all_in_rect_mut<T: Aabb>(tree: &mut crate::Tree<T>, rect: &axg
     assert!(res_naive.iter().eq(res_dino.iter()));
 }
 
+///Panics if a disconnect is detected between tree and naive queries.
+pub fn assert_for_all_containing_point_mut<T: Aabb>(
+    tree: &mut crate::Tree<T>,
+    point: Vec2<T::Num>,
+) {
+    let mut res_dino = Vec::new();
+    tree.for_all_containing_point_mut(point, |a| {
+        res_dino.push(into_ptr_usize(a.deref()));
+    });
+
+    let mut res_naive = Vec::new();
+    naive_for_all_containing_point_mut(tree.get_elements_mut(), point, |a| {
+        res_naive.push(into_ptr_usize(a.deref()));
+    });
+
+    res_dino.sort_unstable();
+    res_naive.sort_unstable();
+
+    assert_eq!(res_naive.len(), res_dino.len());
+    assert!(res_naive.iter().eq(res_dino.iter()));
+}
+
+use super::convex;
+use super::convex::ConvexRegion;
 use super::Queries;
 
 ///Rect functions that can be called on a tree.
@@ -451,6 +1014,492 @@ pub trait RectQuery<'a>: Queries<'a> {
         self::for_all_not_in_rect_mut(default_axis(), self.vistr_mut(), rect, move |a| (func)(a));
     }
 
+    /// # Examples
+    ///
+    ///```
+    /// use broccoli::{prelude::*,bbox,rect};
+    /// let mut bots = [rect(0,10,0,10),rect(20,30,20,30)];
+    /// let mut tree = broccoli::new(&mut bots);
+    /// let mut test = Vec::new();
+    /// tree.for_all_not_in_rect(&rect(0,15,0,15),|a|{
+    ///    test.push(a);
+    /// });
+    ///
+    /// assert_eq!(test,vec![&rect(20,30,20,30)]);
+    ///
+    ///```
+    fn for_all_not_in_rect<'b>(&'b self, rect: &Rect<Self::Num>, func: impl FnMut(&'b Self::T))
+    where
+        'a: 'b,
+    {
+        self::for_all_not_in_rect(default_axis(), self.vistr(), rect, func);
+    }
+
+    /// The parallel version of [`RectQuery::for_all_intersect_rect`].
+    ///
+    /// # Examples
+    ///
+    ///```
+    /// use broccoli::{prelude::*,bbox,rect};
+    /// use std::sync::atomic::{AtomicUsize,Ordering};
+    /// let mut bots = [rect(0,10,0,10),rect(20,30,20,30)];
+    /// let mut tree = broccoli::new(&mut bots);
+    /// let count = AtomicUsize::new(0);
+    /// tree.for_all_intersect_rect_par(&rect(9,19,9,19),|_|{
+    ///    count.fetch_add(1,Ordering::Relaxed);
+    /// });
+    ///
+    /// assert_eq!(count.into_inner(),1);
+    ///
+    ///```
+    fn for_all_intersect_rect_par<'b>(
+        &'b self,
+        rect: &Rect<Self::Num>,
+        func: impl Fn(&'b Self::T) + Send + Sync,
+    ) where
+        'a: 'b,
+        Self::T: Send + Sync,
+        Self::Num: Send + Sync,
+    {
+        self::for_all_intersect_rect_par(default_axis(), self.vistr(), rect, func);
+    }
+
+    /// The parallel version of [`RectQuery::for_all_intersect_rect_mut`].
+    ///
+    /// # Examples
+    ///
+    ///```
+    /// use broccoli::{prelude::*,bbox,rect};
+    /// let mut bots = [bbox(rect(0,10,0,10),0u8)];
+    /// let mut tree = broccoli::new(&mut bots);
+    /// tree.for_all_intersect_rect_par_mut(&rect(9,20,9,20),|a|{
+    ///    *a.unpack_inner()+=1;
+    /// });
+    ///
+    /// assert_eq!(bots[0].inner,1);
+    ///
+    ///```
+    fn for_all_intersect_rect_par_mut<'b>(
+        &'b mut self,
+        rect: &Rect<Self::Num>,
+        func: impl Fn(PMut<'b, Self::T>) + Send + Sync,
+    ) where
+        'a: 'b,
+        Self::T: Send + Sync,
+        Self::Num: Send + Sync,
+    {
+        self::for_all_intersect_rect_par_mut(default_axis(), self.vistr_mut(), rect, func);
+    }
+
+    /// The parallel version of [`RectQuery::for_all_in_rect`].
+    ///
+    /// # Examples
+    ///
+    ///```
+    /// use broccoli::{prelude::*,bbox,rect};
+    /// use std::sync::atomic::{AtomicUsize,Ordering};
+    /// let mut bots = [rect(0,10,0,10),rect(20,30,20,30)];
+    /// let mut tree = broccoli::new(&mut bots);
+    /// let count = AtomicUsize::new(0);
+    /// tree.for_all_in_rect_par(&rect(0,20,0,20),|_|{
+    ///    count.fetch_add(1,Ordering::Relaxed);
+    /// });
+    ///
+    /// assert_eq!(count.into_inner(),1);
+    ///
+    ///```
+    fn for_all_in_rect_par<'b>(
+        &'b self,
+        rect: &Rect<Self::Num>,
+        func: impl Fn(&'b Self::T) + Send + Sync,
+    ) where
+        'a: 'b,
+        Self::T: Send + Sync,
+        Self::Num: Send + Sync,
+    {
+        self::for_all_in_rect_par(default_axis(), self.vistr(), rect, func);
+    }
+
+    /// The parallel version of [`RectQuery::for_all_in_rect_mut`].
+    ///
+    /// # Examples
+    ///
+    ///```
+    /// use broccoli::{prelude::*,bbox,rect};
+    /// let mut bots = [bbox(rect(0,10,0,10),0u8)];
+    /// let mut tree = broccoli::new(&mut bots);
+    /// tree.for_all_in_rect_par_mut(&rect(0,10,0,10),|a|{
+    ///    *a.unpack_inner()+=1;
+    /// });
+    ///
+    /// assert_eq!(bots[0].inner,1);
+    ///
+    ///```
+    fn for_all_in_rect_par_mut<'b>(
+        &'b mut self,
+        rect: &Rect<Self::Num>,
+        func: impl Fn(PMut<'b, Self::T>) + Send + Sync,
+    ) where
+        'a: 'b,
+        Self::T: Send + Sync,
+        Self::Num: Send + Sync,
+    {
+        self::for_all_in_rect_par_mut(default_axis(), self.vistr_mut(), rect, func);
+    }
+
+    /// The parallel version of [`RectQuery::for_all_not_in_rect`].
+    ///
+    /// # Examples
+    ///
+    ///```
+    /// use broccoli::{prelude::*,bbox,rect};
+    /// use std::sync::atomic::{AtomicUsize,Ordering};
+    /// let mut bots = [rect(0,10,0,10),rect(20,30,20,30)];
+    /// let mut tree = broccoli::new(&mut bots);
+    /// let count = AtomicUsize::new(0);
+    /// tree.for_all_not_in_rect_par(&rect(0,15,0,15),|_|{
+    ///    count.fetch_add(1,Ordering::Relaxed);
+    /// });
+    ///
+    /// assert_eq!(count.into_inner(),1);
+    ///
+    ///```
+    fn for_all_not_in_rect_par<'b>(
+        &'b self,
+        rect: &Rect<Self::Num>,
+        func: impl Fn(&'b Self::T) + Send + Sync,
+    ) where
+        'a: 'b,
+        Self::T: Send + Sync,
+        Self::Num: Send + Sync,
+    {
+        self::for_all_not_in_rect_par(default_axis(), self.vistr(), rect, func);
+    }
+
+    /// The parallel version of [`RectQuery::for_all_not_in_rect_mut`].
+    ///
+    /// # Examples
+    ///
+    ///```
+    /// use broccoli::{prelude::*,bbox,rect};
+    /// let mut bots = [bbox(rect(0,10,0,10),0u8)];
+    /// let mut tree = broccoli::new(&mut bots);
+    /// tree.for_all_not_in_rect_par_mut(&rect(10,20,10,20),|a|{
+    ///    *a.unpack_inner()+=1;
+    /// });
+    ///
+    /// assert_eq!(bots[0].inner,1);
+    ///
+    ///```
+    fn for_all_not_in_rect_par_mut<'b>(
+        &'b mut self,
+        rect: &Rect<Self::Num>,
+        func: impl Fn(PMut<'b, Self::T>) + Send + Sync,
+    ) where
+        'a: 'b,
+        Self::T: Send + Sync,
+        Self::Num: Send + Sync,
+    {
+        self::for_all_not_in_rect_par_mut(default_axis(), self.vistr_mut(), rect, func);
+    }
+
+    /// Returns the number of elements that are completely inside the rect.
+    ///
+    /// Subtrees whose bounds are completely inside the rect are counted
+    /// without visiting their nodes or elements, since every node stores
+    /// the number of elements in its subtree.
+    ///
+    /// # Examples
+    ///
+    ///```
+    /// use broccoli::{prelude::*,bbox,rect};
+    /// let mut bots = [rect(0,10,0,10),rect(20,30,20,30),rect(5,25,5,25)];
+    /// let mut tree = broccoli::new(&mut bots);
+    ///
+    /// assert_eq!(tree.count_in_rect(&rect(0,20,0,20)),1);
+    /// assert_eq!(tree.count_in_rect(&rect(0,30,0,30)),3);
+    ///
+    ///```
+    fn count_in_rect(&self, rect: &Rect<Self::Num>) -> usize {
+        self::count_in_rect(default_axis(), self.vistr(), rect)
+    }
+
+    /// Returns true if any element intersects the rect.
+    /// Stops as soon as one is found.
+    ///
+    /// # Examples
+    ///
+    ///```
+    /// use broccoli::{prelude::*,bbox,rect};
+    /// let mut bots = [rect(0,10,0,10),rect(20,30,20,30)];
+    /// let mut tree = broccoli::new(&mut bots);
+    ///
+    /// assert!(tree.any_intersect_rect(&rect(9,12,9,12)));
+    /// assert!(!tree.any_intersect_rect(&rect(12,18,12,18)));
+    ///
+    ///```
+    fn any_intersect_rect(&self, rect: &Rect<Self::Num>) -> bool {
+        self.find_first_intersect_rect(rect).is_some()
+    }
+
+    /// Returns the first element found that intersects the rect.
+    /// Stops as soon as one is found.
+    ///
+    /// # Examples
+    ///
+    ///```
+    /// use broccoli::{prelude::*,bbox,rect};
+    /// let mut bots = [rect(0,10,0,10),rect(20,30,20,30)];
+    /// let mut tree = broccoli::new(&mut bots);
+    ///
+    /// assert_eq!(tree.find_first_intersect_rect(&rect(15,25,15,25)),Some(&rect(20,30,20,30)));
+    /// assert_eq!(tree.find_first_intersect_rect(&rect(12,18,12,18)),None);
+    ///
+    ///```
+    fn find_first_intersect_rect<'b>(&'b self, rect: &Rect<Self::Num>) -> Option<&'b Self::T>
+    where
+        'a: 'b,
+    {
+        self::find_first_intersect_rect(default_axis(), self.vistr(), rect)
+    }
+
+    /// Returns an iterator over the elements that are completely inside the rect.
+    /// Unlike [`RectQuery::for_all_in_rect`], the query can be stopped early or combined with other iterators.
+    ///
+    /// # Examples
+    ///
+    ///```
+    /// use broccoli::{prelude::*,bbox,rect};
+    /// let mut bots = [rect(0,10,0,10),rect(20,30,20,30),rect(5,25,5,25)];
+    /// let mut tree = broccoli::new(&mut bots);
+    ///
+    /// let test: Vec<_> = tree.iter_in_rect(&rect(0,20,0,20)).collect();
+    /// assert_eq!(test,vec![&rect(0,10,0,10)]);
+    ///
+    /// assert_eq!(tree.iter_in_rect(&rect(0,30,0,30)).take(2).count(),2);
+    ///
+    ///```
+    fn iter_in_rect<'b>(&'b self, rect: &Rect<Self::Num>) -> RectIter<'b, 'a, Self::T>
+    where
+        'a: 'b,
+    {
+        RectIter::new(self.vistr(), rect, |rect, a| rect.contains_rect(a))
+    }
+
+    /// Returns an iterator over the elements that intersect the rect.
+    /// Unlike [`RectQuery::for_all_intersect_rect`], the query can be stopped early or combined with other iterators.
+    ///
+    /// # Examples
+    ///
+    ///```
+    /// use broccoli::{prelude::*,bbox,rect};
+    /// let mut bots = [rect(0,10,0,10),rect(20,30,20,30)];
+    /// let mut tree = broccoli::new(&mut bots);
+    ///
+    /// let test: Vec<_> = tree.iter_intersect_rect(&rect(9,19,9,19)).collect();
+    /// assert_eq!(test,vec![&rect(0,10,0,10)]);
+    ///
+    ///```
+    fn iter_intersect_rect<'b>(&'b self, rect: &Rect<Self::Num>) -> RectIter<'b, 'a, Self::T>
+    where
+        'a: 'b,
+    {
+        RectIter::new(self.vistr(), rect, |rect, a| {
+            rect.get_intersect_rect(a).is_some()
+        })
+    }
+
+    /// Find all elements whose aabb contains the specified `point`.
+    /// Only the nodes along the path to the point are visited.
+    ///
+    /// # Examples
+    ///
+    ///```
+    /// use broccoli::{prelude::*,bbox,rect};
+    /// use axgeom::vec2;
+    /// let mut bots = [rect(0,10,0,10),rect(20,30,20,30)];
+    /// let mut tree = broccoli::new(&mut bots);
+    /// let mut test = Vec::new();
+    /// tree.for_all_containing_point(vec2(5,5),|a|{
+    ///    test.push(a);
+    /// });
+    ///
+    /// assert_eq!(test,vec![&rect(0,10,0,10)]);
+    ///
+    ///```
+    fn for_all_containing_point<'b>(
+        &'b self,
+        point: Vec2<Self::Num>,
+        func: impl FnMut(&'b Self::T),
+    ) where
+        'a: 'b,
+    {
+        self::for_all_containing_point(default_axis(), self.vistr(), point, func);
+    }
+
+    /// # Examples
+    ///
+    ///```
+    /// use broccoli::{prelude::*,bbox,rect};
+    /// use axgeom::vec2;
+    /// let mut bots = [bbox(rect(0,10,0,10),0u8)];
+    /// let mut tree = broccoli::new(&mut bots);
+    /// tree.for_all_containing_point_mut(vec2(5,5),|a|{
+    ///    *a.unpack_inner()+=1;    
+    /// });
+    ///
+    /// assert_eq!(bots[0].inner,1);
+    ///
+    ///```
+    fn for_all_containing_point_mut<'b>(
+        &'b mut self,
+        point: Vec2<Self::Num>,
+        mut func: impl FnMut(PMut<'b, Self::T>),
+    ) where
+        'a: 'b,
+    {
+        self::for_all_containing_point_mut(default_axis(), self.vistr_mut(), point, move |a| {
+            (func)(a)
+        });
+    }
+
+    /// Find all elements whose aabb intersects the specified convex region.
+    /// Subtrees that are on the other side of a divider from the region are skipped.
+    ///
+    /// # Examples
+    ///
+    ///```
+    /// use broccoli::{prelude::*,bbox,rect};
+    /// use broccoli::query::convex::ConvexRegion;
+    /// use axgeom::vec2;
+    /// let mut bots = [rect(0,10,0,10),rect(20,30,20,30),rect(0,5,20,30)];
+    /// let mut tree = broccoli::new(&mut bots);
+    ///
+    /// //A triangle
+    /// let region = ConvexRegion::from_points(&[vec2(0,0),vec2(40,0),vec2(40,40)]);
+    ///
+    /// let mut test = Vec::new();
+    /// tree.for_all_intersect_convex(&region,|a|{
+    ///    test.push(a);
+    /// });
+    ///
+    /// test.sort_by_key(|a|a.x.start);
+    /// assert_eq!(test,vec![&rect(0,10,0,10),&rect(20,30,20,30)]);
+    ///
+    ///```
+    fn for_all_intersect_convex<'b>(
+        &'b self,
+        region: &ConvexRegion<Self::Num>,
+        func: impl FnMut(&'b Self::T),
+    ) where
+        'a: 'b,
+        Self::Num: num_traits::Signed,
+    {
+        convex::constant::for_all_intersect_convex(default_axis(), self.vistr(), region, func);
+    }
+
+    /// # Examples
+    ///
+    ///```
+    /// use broccoli::{prelude::*,bbox,rect};
+    /// use broccoli::query::convex::ConvexRegion;
+    /// use axgeom::vec2;
+    /// let mut bots = [bbox(rect(0,10,0,10),0u8)];
+    /// let mut tree = broccoli::new(&mut bots);
+    /// let region = ConvexRegion::from_points(&[vec2(5,5),vec2(20,5),vec2(20,20)]);
+    /// tree.for_all_intersect_convex_mut(&region,|a|{
+    ///    *a.unpack_inner()+=1;    
+    /// });
+    ///
+    /// assert_eq!(bots[0].inner,1);
+    ///
+    ///```
+    fn for_all_intersect_convex_mut<'b>(
+        &'b mut self,
+        region: &ConvexRegion<Self::Num>,
+        mut func: impl FnMut(PMut<'b, Self::T>),
+    ) where
+        'a: 'b,
+        Self::Num: num_traits::Signed,
+    {
+        convex::mutable::for_all_intersect_convex_mut(
+            default_axis(),
+            self.vistr_mut(),
+            region,
+            move |a| (func)(a),
+        );
+    }
+
+    /// The parallel version of [`RectQuery::for_all_intersect_convex`].
+    ///
+    /// # Examples
+    ///
+    ///```
+    /// use broccoli::{prelude::*,bbox,rect};
+    /// use broccoli::query::convex::{ConvexRegion,HalfPlane};
+    /// use axgeom::vec2;
+    /// use std::sync::atomic::{AtomicUsize,Ordering};
+    /// let mut bots = [rect(0,10,0,10),rect(20,30,20,30)];
+    /// let mut tree = broccoli::new(&mut bots);
+    ///
+    /// //Everything to the right of x=15.
+    /// let region = ConvexRegion::from_half_planes(vec![HalfPlane{normal:vec2(-1,0),offset:-15}]);
+    ///
+    /// let count = AtomicUsize::new(0);
+    /// tree.for_all_intersect_convex_par(&region,|_|{
+    ///    count.fetch_add(1,Ordering::Relaxed);
+    /// });
+    ///
+    /// assert_eq!(count.into_inner(),1);
+    ///
+    ///```
+    fn for_all_intersect_convex_par<'b>(
+        &'b self,
+        region: &ConvexRegion<Self::Num>,
+        func: impl Fn(&'b Self::T) + Send + Sync,
+    ) where
+        'a: 'b,
+        Self::T: Send + Sync,
+        Self::Num: num_traits::Signed + Send + Sync,
+    {
+        convex::constant::for_all_intersect_convex_par(default_axis(), self.vistr(), region, func);
+    }
+
+    /// The parallel version of [`RectQuery::for_all_intersect_convex_mut`].
+    ///
+    /// # Examples
+    ///
+    ///```
+    /// use broccoli::{prelude::*,bbox,rect};
+    /// use broccoli::query::convex::ConvexRegion;
+    /// use axgeom::vec2;
+    /// let mut bots = [bbox(rect(0,10,0,10),0u8)];
+    /// let mut tree = broccoli::new(&mut bots);
+    /// let region = ConvexRegion::from_points(&[vec2(5,5),vec2(20,5),vec2(20,20)]);
+    /// tree.for_all_intersect_convex_par_mut(&region,|a|{
+    ///    *a.unpack_inner()+=1;    
+    /// });
+    ///
+    /// assert_eq!(bots[0].inner,1);
+    ///
+    ///```
+    fn for_all_intersect_convex_par_mut<'b>(
+        &'b mut self,
+        region: &ConvexRegion<Self::Num>,
+        func: impl Fn(PMut<'b, Self::T>) + Send + Sync,
+    ) where
+        'a: 'b,
+        Self::T: Send + Sync,
+        Self::Num: num_traits::Signed + Send + Sync,
+    {
+        convex::mutable::for_all_intersect_convex_par_mut(
+            default_axis(),
+            self.vistr_mut(),
+            region,
+            func,
+        );
+    }
+
     /// If we have two non intersecting rectangles, it is safe to return to the user two sets of mutable references
     /// of the bots strictly inside each rectangle since it is impossible for a bot to belong to both sets.
     ///
@@ -0,0 +1,353 @@
+//! Segment query module
+
+use crate::query::inner_prelude::*;
+use num_traits::Signed;
+
+///A line segment between two points.
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub struct Segment<N> {
+    pub a: Vec2<N>,
+    pub b: Vec2<N>,
+}
+
+impl<N: Num + Signed> Segment<N> {
+    ///The smallest rectangle that contains the segment.
+    pub fn bounding_rect(&self) -> Rect<N> {
+        fn range<N: Num>(a: N, b: N) -> Range<N> {
+            if a < b {
+                Range { start: a, end: b }
+            } else {
+                Range { start: b, end: a }
+            }
+        }
+        Rect {
+            x: range(self.a.x, self.b.x),
+            y: range(self.a.y, self.b.y),
+        }
+    }
+
+    ///Returns true if the segment touches the rectangle.
+    pub fn intersects_rect(&self, rect: &Rect<N>) -> bool {
+        let b = self.bounding_rect();
+        if b.x.start > rect.x.end
+            || b.x.end < rect.x.start
+            || b.y.start > rect.y.end
+            || b.y.end < rect.y.start
+        {
+            return false;
+        }
+
+        //The rectangle does not touch the line the segment lies on
+        //if all its corners are strictly on one side of it.
+        let d = vec2(self.b.x - self.a.x, self.b.y - self.a.y);
+        let side = |x: N, y: N| d.x * (y - self.a.y) - d.y * (x - self.a.x);
+        let corners = [
+            side(rect.x.start, rect.y.start),
+            side(rect.x.start, rect.y.end),
+            side(rect.x.end, rect.y.start),
+            side(rect.x.end, rect.y.end),
+        ];
+        !(corners.iter().all(|a| a.is_positive()) || corners.iter().all(|a| a.is_negative()))
+    }
+}
+
+///The geometric functions that the user must provide.
+pub trait SegmentIntersect {
+    type T: Aabb<Num = Self::N>;
+    type N: Num;
+
+    ///Return true if the segment touches the shape contained in T.
+    ///Only called for elements whose aabb is touched by the segment.
+    fn intersects_fine(&mut self, seg: &Segment<Self::N>, a: &Self::T) -> bool;
+}
+
+///Create a handler that treats each object as its aabb rectangle shape.
+pub fn default_rect_segment<T: Aabb>(tree: &Tree<T>) -> impl SegmentIntersect<T = T, N = T::Num> {
+    from_closure(tree, (), |_, _, _| true)
+}
+
+use crate::Tree;
+
+///Construct an object that implements [`SegmentIntersect`] from closures.
+///We pass the tree so that we can infer the type of `T`.
+///
+/// `fine` is a function that returns true if the segment touches the
+/// shape of an element whose aabb the segment touches.
+///
+/// `acc` is a user defined object that is passed to every call to the `fine` function.
+pub fn from_closure<A, T: Aabb>(
+    _tree: &Tree<T>,
+    acc: A,
+    fine: impl FnMut(&mut A, &Segment<T::Num>, &T) -> bool,
+) -> impl SegmentIntersect<T = T, N = T::Num> {
+    struct SegmentClosure<T, A, C> {
+        _p: PhantomData<T>,
+        acc: A,
+        fine: C,
+    }
+
+    impl<T: Aabb, A, C> SegmentIntersect for SegmentClosure<T, A, C>
+    where
+        C: FnMut(&mut A, &Segment<T::Num>, &T) -> bool,
+    {
+        type T = T;
+        type N = T::Num;
+
+        fn intersects_fine(&mut self, seg: &Segment<Self::N>, a: &Self::T) -> bool {
+            (self.fine)(&mut self.acc, seg, a)
+        }
+    }
+
+    SegmentClosure {
+        _p: PhantomData,
+        acc,
+        fine,
+    }
+}
+
+macro_rules! segment {
+    ($iterator:ty,$colsingle:ty,$get_bots:ident) => {
+        //`rect` is the part of the bounding rectangle of the segment that
+        //lies inside the space covered by the current subtree.
+        fn segment_recurse<'a, A: Axis, T: Aabb, F: FnMut($colsingle)>(
+            axis: A,
+            m: $iterator,
+            seg: &Segment<T::Num>,
+            rect: Rect<T::Num>,
+            func: &mut F,
+        ) where
+            T::Num: Signed,
+        {
+            let (nn, rest) = m.next();
+            let children = match rest {
+                Some([left, right]) => {
+                    let div = match nn.div {
+                        Some(b) => b,
+                        None => return,
+                    };
+                    Some((div, left, right))
+                }
+                None => None,
+            };
+
+            if !nn.range.is_empty() {
+                let r = rect.get_range(axis);
+                let cont = nn.cont;
+                if r.start <= cont.end && r.end >= cont.start {
+                    let mut node_rect = rect;
+                    let nr = node_rect.get_range_mut(axis);
+                    if cont.start > nr.start {
+                        nr.start = cont.start;
+                    }
+                    if cont.end < nr.end {
+                        nr.end = cont.end;
+                    }
+
+                    if seg.intersects_rect(&node_rect) {
+                        for i in $get_bots(nn) {
+                            func(i);
+                        }
+                    }
+                }
+            }
+
+            if let Some((div, left, right)) = children {
+                let r = *rect.get_range(axis);
+                if r.start <= div {
+                    let mut lrect = rect;
+                    if div < r.end {
+                        lrect.get_range_mut(axis).end = div;
+                    }
+                    if seg.intersects_rect(&lrect) {
+                        segment_recurse(axis.next(), left, seg, lrect, func);
+                    }
+                }
+                if r.end >= div {
+                    let mut rrect = rect;
+                    if div > r.start {
+                        rrect.get_range_mut(axis).start = div;
+                    }
+                    if seg.intersects_rect(&rrect) {
+                        segment_recurse(axis.next(), right, seg, rrect, func);
+                    }
+                }
+            }
+        }
+    };
+}
+
+mod mutable {
+    use super::*;
+    fn foo<'a, 'b: 'a, T: Aabb>(node: PMut<'a, Node<'b, T>>) -> PMut<'a, [T]> {
+        node.into_range()
+    }
+    segment!(VistrMut<'a, Node<T>>, PMut<'a, T>, foo);
+
+    pub fn for_all_intersect_segment_mut<'a, 'b: 'a, A: Axis, T: Aabb>(
+        axis: A,
+        vistr: VistrMut<'a, Node<'b, T>>,
+        seg: &Segment<T::Num>,
+        handler: &mut impl SegmentIntersect<T = T, N = T::Num>,
+        mut closure: impl FnMut(PMut<'a, T>),
+    ) where
+        T::Num: Signed,
+    {
+        segment_recurse(axis, vistr, seg, seg.bounding_rect(), &mut |a| {
+            if seg.intersects_rect(a.get()) && handler.intersects_fine(seg, &a) {
+                closure(a);
+            }
+        });
+    }
+}
+
+mod constant {
+    use super::*;
+    fn foo<'a, 'b: 'a, T: Aabb>(node: &'a Node<'b, T>) -> &'a [T] {
+        &node.range
+    }
+    segment!(Vistr<'a, Node<T>>, &'a T, foo);
+
+    pub fn for_all_intersect_segment<'a, 'b: 'a, A: Axis, T: Aabb>(
+        axis: A,
+        vistr: Vistr<'a, Node<'b, T>>,
+        seg: &Segment<T::Num>,
+        handler: &mut impl SegmentIntersect<T = T, N = T::Num>,
+        mut closure: impl FnMut(&'a T),
+    ) where
+        T::Num: Signed,
+    {
+        segment_recurse(axis, vistr, seg, seg.bounding_rect(), &mut |a: &'a T| {
+            if seg.intersects_rect(a.get()) && handler.intersects_fine(seg, a) {
+                closure(a);
+            }
+        });
+    }
+}
+
+///Naive implementation
+pub fn naive_for_all_intersect_segment_mut<'a, T: Aabb>(
+    bots: PMut<'a, [T]>,
+    seg: &Segment<T::Num>,
+    handler: &mut impl SegmentIntersect<T = T, N = T::Num>,
+    mut closure: impl FnMut(PMut<'a, T>),
+) where
+    T::Num: Signed,
+{
+    for b in bots.iter_mut() {
+        if seg.intersects_rect(b.get()) && handler.intersects_fine(seg, &b) {
+            closure(b);
+        }
+    }
+}
+
+///Panics if a disconnect is detected between tree and naive queries.
+pub fn assert_for_all_intersect_segment_mut<T: Aabb>(
+    tree: &mut Tree<T>,
+    seg: &Segment<T::Num>,
+    handler: &mut impl SegmentIntersect<T = T, N = T::Num>,
+) where
+    T::Num: Signed,
+{
+    use core::ops::Deref;
+    fn into_ptr_usize<T>(a: &T) -> usize {
+        a as *const T as usize
+    }
+    let mut res_dino = Vec::new();
+    tree.for_all_intersect_segment_mut(seg, handler, |a| {
+        res_dino.push(into_ptr_usize(a.deref()));
+    });
+
+    let mut res_naive = Vec::new();
+    naive_for_all_intersect_segment_mut(tree.get_elements_mut(), seg, handler, |a| {
+        res_naive.push(into_ptr_usize(a.deref()));
+    });
+
+    res_dino.sort_unstable();
+    res_naive.sort_unstable();
+
+    assert_eq!(res_naive.len(), res_dino.len());
+    assert!(res_naive.iter().eq(res_dino.iter()));
+}
+
+use super::Queries;
+
+///Segment functions that can be called on a tree.
+pub trait SegmentQuery<'a>: Queries<'a> {
+    /// Find all the elements that the segment passes through.
+    ///
+    /// # Examples
+    ///
+    ///```
+    /// use broccoli::{prelude::*,rect};
+    /// use broccoli::query::segment::Segment;
+    /// use axgeom::vec2;
+    ///
+    /// let mut bots = [rect(0,10,0,10),rect(20,30,0,10),rect(20,30,20,30)];
+    /// let mut tree = broccoli::new(&mut bots);
+    ///
+    /// let mut handler = broccoli::query::segment::default_rect_segment(&tree);
+    /// let seg = Segment{a:vec2(-5,5),b:vec2(40,5)};
+    ///
+    /// let mut test = Vec::new();
+    /// tree.for_all_intersect_segment(&seg,&mut handler,|a|{
+    ///    test.push(a);
+    /// });
+    ///
+    /// test.sort_by_key(|a|a.x.start);
+    /// assert_eq!(test,vec![&rect(0,10,0,10),&rect(20,30,0,10)]);
+    ///```
+    fn for_all_intersect_segment<'b, K: SegmentIntersect<T = Self::T, N = Self::Num>>(
+        &'b self,
+        seg: &Segment<Self::Num>,
+        handler: &mut K,
+        func: impl FnMut(&'b Self::T),
+    ) where
+        'a: 'b,
+        Self::Num: Signed,
+    {
+        constant::for_all_intersect_segment(default_axis(), self.vistr(), seg, handler, func);
+    }
+
+    /// # Examples
+    ///
+    ///```
+    /// use broccoli::{prelude::*,bbox,rect};
+    /// use broccoli::query::segment::Segment;
+    /// use axgeom::vec2;
+    ///
+    /// let mut bots = [bbox(rect(0isize,10,0,10),0u8),bbox(rect(20,30,20,30),0u8)];
+    /// let mut tree = broccoli::new(&mut bots);
+    ///
+    /// //Treat every element as a circle.
+    /// let mut handler = broccoli::query::segment::from_closure(&tree,(),|_,seg,a|{
+    ///     let center_y = (a.rect.y.start+a.rect.y.end)/2;
+    ///     //only handles horizontal segments for brevity.
+    ///     (seg.a.y-center_y).abs() <= (a.rect.x.end-a.rect.x.start)/2
+    /// });
+    ///
+    /// let seg = Segment{a:vec2(-5,5),b:vec2(40,5)};
+    /// tree.for_all_intersect_segment_mut(&seg,&mut handler,|a|{
+    ///    *a.unpack_inner()+=1;
+    /// });
+    ///
+    /// assert_eq!(bots[0].inner,1);
+    /// assert_eq!(bots[1].inner,0);
+    ///```
+    fn for_all_intersect_segment_mut<'b, K: SegmentIntersect<T = Self::T, N = Self::Num>>(
+        &'b mut self,
+        seg: &Segment<Self::Num>,
+        handler: &mut K,
+        func: impl FnMut(PMut<'b, Self::T>),
+    ) where
+        'a: 'b,
+        Self::Num: Signed,
+    {
+        mutable::for_all_intersect_segment_mut(
+            default_axis(),
+            self.vistr_mut(),
+            seg,
+            handler,
+            func,
+        );
+    }
+}
@@ -0,0 +1,201 @@
+//! Render a tree into an SVG string
+//!
+//! Only available with the `svg` feature. The output uses the coordinate system of the tree,
+//! so the y axis points down as is usual for SVG.
+
+use crate::query::inner_prelude::*;
+use alloc::string::String;
+use core::fmt::Write;
+use num_traits::AsPrimitive;
+
+///Builder for rendering a tree into an SVG string.
+///
+/// # Examples
+///
+///```
+/// use broccoli::{prelude::*,rect};
+/// use broccoli::query::svg::Svg;
+///
+/// let mut bots = [rect(0,10,0,10),rect(20,30,20,30)];
+/// let tree = broccoli::new(&mut bots);
+///
+/// let svg = Svg::new(rect(0,100,0,100))
+///     .with_rect(rect(5,25,5,25))
+///     .render(&tree);
+///
+/// assert!(svg.starts_with("<svg"));
+/// assert!(svg.ends_with("</svg>\n"));
+///```
+#[derive(Clone, Debug)]
+pub struct Svg<N> {
+    border: Rect<N>,
+    elements: bool,
+    rects: Vec<Rect<N>>,
+    rays: Vec<Ray<N>>,
+}
+
+impl<N: Num + AsPrimitive<f64>> Svg<N> {
+    ///Create a builder that renders the space inside of `border`.
+    ///Dividers are drawn across the whole border.
+    pub fn new(border: Rect<N>) -> Svg<N> {
+        Svg {
+            border,
+            elements: true,
+            rects: Vec::new(),
+            rays: Vec::new(),
+        }
+    }
+
+    ///Whether or not to draw the aabb of each element. The default is true.
+    #[must_use]
+    pub fn with_elements(mut self, elements: bool) -> Self {
+        self.elements = elements;
+        self
+    }
+
+    ///Overlay a query rect.
+    #[must_use]
+    pub fn with_rect(mut self, rect: Rect<N>) -> Self {
+        self.rects.push(rect);
+        self
+    }
+
+    ///Overlay a query ray. It is drawn until it leaves the border.
+    #[must_use]
+    pub fn with_ray(mut self, ray: Ray<N>) -> Self {
+        self.rays.push(ray);
+        self
+    }
+
+    ///Render the tree.
+    ///
+    ///Each divider is drawn as a band as thick as the `cont` of its node,
+    ///colored by the depth of the node.
+    pub fn render<'a, Q: DrawQuery<'a, Num = N> + ?Sized>(&self, tree: &Q) -> String {
+        let b = self.border.inner_as::<f64>();
+        let width = b.x.end - b.x.start;
+        let height = b.y.end - b.y.start;
+        let stroke = width.max(height) / 500.0;
+
+        let mut s = String::new();
+        //Writing into a String can't fail.
+        let _ = writeln!(
+            s,
+            r#"<svg xmlns="http://www.w3.org/2000/svg" viewBox="{} {} {} {}">"#,
+            b.x.start, b.y.start, width, height
+        );
+        let _ = writeln!(
+            s,
+            r#"<rect x="{}" y="{}" width="{}" height="{}" fill="white"/>"#,
+            b.x.start, b.y.start, width, height
+        );
+
+        let mut dividers = String::new();
+        tree.draw_divider(
+            |axis, node, rect, depth| {
+                let div = match node.div {
+                    Some(div) => div.as_(),
+                    None => return,
+                };
+                let rect = rect.inner_as::<f64>();
+                let color = depth_color(depth);
+
+                if !node.range.is_empty() {
+                    let cont = node.cont.inner_as::<f64>();
+                    let band =
+                        axis.map_val(Rect { x: cont, y: rect.y }, Rect { x: rect.x, y: cont });
+                    write_rect(
+                        &mut dividers,
+                        &band,
+                        &format!(r#"fill="{}" fill-opacity="0.3""#, color),
+                    );
+                }
+
+                let (start, end) = axis.map_val(
+                    (vec2(div, rect.y.start), vec2(div, rect.y.end)),
+                    (vec2(rect.x.start, div), vec2(rect.x.end, div)),
+                );
+                write_line(
+                    &mut dividers,
+                    start,
+                    end,
+                    &format!(r#"stroke="{}" stroke-width="{}""#, color, stroke),
+                );
+            },
+            self.border,
+        );
+        s.push_str(&dividers);
+
+        if self.elements {
+            for a in super::tools::get_elements(tree.vistr()).iter() {
+                write_rect(
+                    &mut s,
+                    &a.get().inner_as(),
+                    &format!(r#"fill="none" stroke="black" stroke-width="{}""#, stroke),
+                );
+            }
+        }
+
+        for r in self.rects.iter() {
+            write_rect(
+                &mut s,
+                &r.inner_as(),
+                &format!(
+                    r#"fill="blue" fill-opacity="0.2" stroke="blue" stroke-width="{}""#,
+                    stroke * 2.0
+                ),
+            );
+        }
+
+        for ray in self.rays.iter() {
+            let point = ray.point.inner_as::<f64>();
+            let dir = ray.dir.inner_as::<f64>();
+            //Long enough to leave the border from anywhere inside of it.
+            let len = dir.x.abs() + dir.y.abs();
+            let t = if len > 0.0 {
+                (width + height) / len
+            } else {
+                0.0
+            };
+            let end = vec2(point.x + dir.x * t, point.y + dir.y * t);
+            let attr = format!(r#"stroke="red" stroke-width="{}""#, stroke * 2.0);
+            write_line(&mut s, point, end, &attr);
+            let _ = writeln!(
+                s,
+                r#"<circle cx="{}" cy="{}" r="{}" fill="red"/>"#,
+                point.x,
+                point.y,
+                stroke * 3.0
+            );
+        }
+
+        s.push_str("</svg>\n");
+        s
+    }
+}
+
+fn depth_color(depth: usize) -> String {
+    format!("hsl({},70%,45%)", (depth * 67) % 360)
+}
+
+fn write_rect(s: &mut String, r: &Rect<f64>, attr: &str) {
+    let _ = writeln!(
+        s,
+        r#"<rect x="{}" y="{}" width="{}" height="{}" {}/>"#,
+        r.x.start,
+        r.y.start,
+        r.x.end - r.x.start,
+        r.y.end - r.y.start,
+        attr
+    );
+}
+
+fn write_line(s: &mut String, a: Vec2<f64>, b: Vec2<f64>, attr: &str) {
+    let _ = writeln!(
+        s,
+        r#"<line x1="{}" y1="{}" x2="{}" y2="{}" {}/>"#,
+        a.x, a.y, b.x, b.y, attr
+    );
+}
+
+use super::draw::DrawQuery;
@@ -112,3 +112,51 @@ pub fn for_every_pair<T: Aabb>(mut arr: PMut<[T]>, mut func: impl FnMut(PMut<T>,
         }
     }
 }
+
+///Returns all the elements of the tree as one contiguous slice in dfs preorder.
+pub fn get_elements<'a, T: Aabb>(vistr: Vistr<'a, Node<T>>) -> &'a [T] {
+    let mut new_slice: Option<&[T]> = None;
+    vistr.dfs_preorder(|a| {
+        new_slice = Some(match new_slice.take() {
+            Some(s) => crate::util::combine_slice(s, &a.range),
+            None => &a.range,
+        });
+    });
+    new_slice.unwrap()
+}
+
+///Returns all the elements of the tree as one contiguous slice in dfs preorder.
+pub fn get_elements_mut<'a, 'b: 'a, T: Aabb>(vistr: VistrMut<'a, Node<'b, T>>) -> PMut<'a, [T]> {
+    let mut new_slice: Option<PMut<[T]>> = None;
+    vistr.dfs_preorder(|a| {
+        new_slice = Some(match new_slice.take() {
+            Some(s) => crate::pmut::combine_slice(s, a.into_range()),
+            None => a.into_range(),
+        });
+    });
+    new_slice.unwrap()
+}
+
+///Returns true if the subtree bounds touch the rect.
+///Returns false if the subtree has no elements.
+pub fn bounds_touch<N: Num>(bounds: &Option<Rect<N>>, rect: &Rect<N>) -> bool {
+    match bounds {
+        Some(b) => {
+            !(b.x.start > rect.x.end
+                || b.x.end < rect.x.start
+                || b.y.start > rect.y.end
+                || b.y.end < rect.y.start)
+        }
+        None => false,
+    }
+}
+
+///Grow the range so that it also covers `b`.
+pub fn grow_range<N: Num>(a: &mut Range<N>, b: &Range<N>) {
+    if b.start < a.start {
+        a.start = b.start;
+    }
+    if b.end > a.end {
+        a.end = b.end;
+    }
+}
@@ -0,0 +1,464 @@
+//! Randomized differential testing of every query
+//!
+//! Only available with the `testing` feature. Random sets of aabbs are generated from a seed,
+//! including degenerate rects, duplicates and very large rects, and every query of the tree
+//! (and its parallel version where there is one) is checked against its naive implementation.
+//! The same seed always generates the same case, so a failing seed can be reproduced.
+//!
+//! Aabbs often share coordinates, so the queries are also checked on aabbs that only
+//! touch the query shape, or that have no width and lie on its edge.
+//! The one exception is colliding pair detection. Which pairs of aabbs that only touch
+//! are reported is not defined, and the tree and the naive implementation can disagree on them.
+//! So colliding pairs are checked on a copy of the aabbs that is scaled so that
+//! no two aabbs share an edge.
+//! Likewise which of the aabbs tied at the furthest distance a k-nearest query returns is
+//! not defined, so k-nearest results are only compared up to those ties, see [`check_k_nearest`].
+//!
+//! # Examples
+//!
+//!```
+//! for seed in 0..4 {
+//!     broccoli::testing::check_random::<i64>(seed, 50);
+//!     broccoli::testing::check_random::<f64>(seed, 50);
+//! }
+//!```
+
+use crate::inner_prelude::*;
+use crate::query::*;
+use core::fmt::Debug;
+use num_traits::{FromPrimitive, Signed};
+use std::sync::Mutex;
+
+///A small deterministic random number generator (xorshift64*).
+#[derive(Clone, Debug)]
+pub struct Rng(u64);
+
+impl Rng {
+    ///Create a generator from a seed. Every seed is valid.
+    #[must_use]
+    pub fn new(seed: u64) -> Rng {
+        //Mix the seed so that nearby seeds do not generate similar cases.
+        let mut z = seed.wrapping_add(0x9E37_79B9_7F4A_7C15);
+        z = (z ^ (z >> 30)).wrapping_mul(0xBF58_476D_1CE4_E5B9);
+        z = (z ^ (z >> 27)).wrapping_mul(0x94D0_49BB_1331_11EB);
+        Rng((z ^ (z >> 31)) | 1)
+    }
+
+    ///The next random number.
+    pub fn next_u64(&mut self) -> u64 {
+        self.0 ^= self.0 >> 12;
+        self.0 ^= self.0 << 25;
+        self.0 ^= self.0 >> 27;
+        self.0.wrapping_mul(0x2545_F491_4F6C_DD1D)
+    }
+
+    ///A random number in `[0,max)`. Panics if `max` is zero.
+    pub fn below(&mut self, max: u64) -> u64 {
+        self.next_u64() % max
+    }
+}
+
+fn num<N: FromPrimitive>(a: f64) -> N {
+    N::from_f64(a).expect("coordinate not representable")
+}
+
+//A random coordinate in `[0,max)` with a fractional part that is lost when
+//converted into an integer type. The coordinates are drawn from a small range
+//so that aabbs often share coordinates, including aabbs with no width that lie
+//on the edge of another aabb.
+fn coord(rng: &mut Rng, max: u64) -> f64 {
+    rng.below(max) as f64 + rng.below(8) as f64 / 8.0
+}
+
+//The width of the space the aabbs are generated in. It grows with the number of aabbs
+//past 2000, so that huge counts are as dense as smaller ones.
+fn space_for(num: usize) -> u64 {
+    let scale = (num.max(2000) as f64 / 2000.0).sqrt();
+    (2000.0 * scale) as u64
+}
+
+fn gen_rect<N: Num + FromPrimitive>(rng: &mut Rng, space: u64, max_size: u64) -> Rect<N> {
+    let x = coord(rng, space);
+    let y = coord(rng, space);
+    let w = (1 + rng.below(max_size)) as f64;
+    let h = (1 + rng.below(max_size)) as f64;
+    rect(num(x), num(x + w), num(y), num(y + h))
+}
+
+//Points can be a little outside of the space.
+fn gen_point<N: Num + FromPrimitive>(rng: &mut Rng, space: u64) -> Vec2<N> {
+    let max = space + space / 10;
+    vec2(num(coord(rng, max)), num(coord(rng, max)))
+}
+
+///Generate `num` random aabbs. The inner value of each is its index.
+///
+///The aabbs are a mix of small rects, points, horizontal and vertical lines,
+///duplicates of earlier aabbs, and very large rects.
+pub fn gen_elements<N: Num + FromPrimitive>(rng: &mut Rng, num: usize) -> Vec<BBox<N, usize>> {
+    let space = space_for(num);
+    let mut bots: Vec<BBox<N, usize>> = Vec::with_capacity(num);
+    for id in 0..num {
+        let r = match rng.below(8) {
+            0..=3 => gen_rect(rng, space, 50),
+            4 => {
+                let p = gen_point(rng, space);
+                rect(p.x, p.x, p.y, p.y)
+            }
+            5 => {
+                let mut r = gen_rect(rng, space, 200);
+                let p: Vec2<N> = gen_point(rng, space);
+                if rng.below(2) == 0 {
+                    r.x = Range {
+                        start: p.x,
+                        end: p.x,
+                    };
+                } else {
+                    r.y = Range {
+                        start: p.y,
+                        end: p.y,
+                    };
+                }
+                r
+            }
+            6 if !bots.is_empty() => bots[rng.below(bots.len() as u64) as usize].rect,
+            _ => gen_rect(rng, space, 2000),
+        };
+        bots.push(crate::bbox(r, id));
+    }
+    bots
+}
+
+//How colfind treats aabbs that only touch is not defined, so colfind is checked on a copy
+//where no start coordinate equals an end coordinate. Every coordinate is a multiple of 1/8,
+//so once scaled by 16, starts are odd and ends are even.
+fn pull_apart<N: Num + Signed + FromPrimitive>(bots: &[BBox<N, usize>]) -> Vec<BBox<N, usize>> {
+    let (scale, one, two): (N, N, N) = (num(16.0), num(1.0), num(2.0));
+    let f = |r: &Range<N>| Range {
+        start: r.start * scale + one,
+        end: r.end * scale + two,
+    };
+    bots.iter()
+        .map(|b| {
+            let r = Rect {
+                x: f(&b.rect.x),
+                y: f(&b.rect.y),
+            };
+            crate::bbox(r, b.inner)
+        })
+        .collect()
+}
+
+//The naive colliding pairs and closest pair take quadratic time. Past this many aabbs,
+//colliding pairs are checked against sweep and prune instead, which does not use the tree,
+//and the closest pair is not checked.
+const QUADRATIC_LIMIT: usize = 4000;
+
+fn check_colliding_pairs<N>(bots: &mut [BBox<N, usize>])
+where
+    N: Num + Debug + Send + Sync,
+{
+    let mut expected = Vec::new();
+    if bots.len() <= QUADRATIC_LIMIT {
+        colfind::query_naive_mut(PMut::new(bots), |a, b| {
+            expected.push(pair(a.inner, b.inner))
+        });
+    } else {
+        colfind::query_sweep_mut(default_axis(), bots, |a, b| {
+            expected.push(pair(a.inner, b.inner))
+        });
+    }
+    expected.sort_unstable();
+
+    let mut pairs = Vec::new();
+    crate::new(bots).find_colliding_pairs_mut(|a, b| pairs.push(pair(a.inner, b.inner)));
+    pairs.sort_unstable();
+    assert_eq!(pairs, expected);
+
+    let pairs = Mutex::new(Vec::new());
+    crate::new_par(bots)
+        .find_colliding_pairs_mut_par(|a, b| pairs.lock().unwrap().push(pair(a.inner, b.inner)));
+    let mut pairs = pairs.into_inner().unwrap();
+    pairs.sort_unstable();
+    assert_eq!(pairs, expected);
+}
+
+///Check a k-nearest query against its naive implementation.
+///
+///Which of the aabbs tied at the furthest distance are returned is not defined,
+///so only the aabbs closer than the furthest one returned are compared.
+///The result must hold at most `num` distinct distances, and fewer than `num` aabbs
+///only if it holds all of them. The inner value of each aabb must be unique.
+///
+///Panics if a disconnect is detected.
+pub fn check_k_nearest<N: Num + Debug>(
+    tree: &mut Tree<BBox<N, usize>>,
+    point: Vec2<N>,
+    num: usize,
+    knear: &mut impl knearest::Knearest<T = BBox<N, usize>, N = N>,
+) {
+    let total = tree.get_elements().len();
+    let mut all: Vec<_> =
+        knearest::naive_k_nearest_mut(tree.get_elements_mut(), point, total, knear)
+            .into_vec()
+            .into_iter()
+            .map(|a| (a.bot.inner, a.mag))
+            .collect();
+    all.sort_by_key(|a| a.0);
+
+    let found: Vec<_> = tree
+        .k_nearest_mut(point, num, knear)
+        .into_vec()
+        .into_iter()
+        .map(|a| (a.bot.inner, a.mag))
+        .collect();
+
+    let mut distances: Vec<_> = found.iter().map(|a| a.1).collect();
+    distances.dedup();
+    assert!(distances.len() <= num);
+    if found.len() < num {
+        assert_eq!(found.len(), total);
+    }
+
+    for &(id, mag) in found.iter() {
+        let i = all.binary_search_by_key(&id, |a| a.0).unwrap();
+        assert_eq!(all[i].1, mag);
+    }
+
+    let closer = |a: &&(usize, N)| match distances.last() {
+        Some(&m) => a.1 < m,
+        None => false,
+    };
+    let mut expected: Vec<_> = all.iter().filter(closer).map(|a| a.0).collect();
+    let mut found: Vec<_> = found.iter().filter(closer).map(|a| a.0).collect();
+    expected.sort_unstable();
+    found.sort_unstable();
+    assert_eq!(found, expected);
+}
+
+fn ids<'a, N: 'a>(a: impl IntoIterator<Item = &'a BBox<N, usize>>) -> Vec<usize> {
+    let mut v: Vec<_> = a.into_iter().map(|a| a.inner).collect();
+    v.sort_unstable();
+    v
+}
+
+fn pair(a: usize, b: usize) -> (usize, usize) {
+    if a < b {
+        (a, b)
+    } else {
+        (b, a)
+    }
+}
+
+///Generate `num` random aabbs from `seed` and check every query against its naive
+///implementation, along with the invariants of trees built sequentially and in parallel.
+///
+///Panics if a disconnect is detected. The panic message holds the seed and the failing
+///aabbs shrunk with [`shrink_elements`].
+pub fn check_random<N>(seed: u64, num: usize)
+where
+    N: Num + Signed + FromPrimitive + Debug + Send + Sync,
+{
+    let mut rng = Rng::new(seed);
+    let bots = gen_elements::<N>(&mut rng, num);
+    if fails(&rng, &bots) {
+        let bots = shrink_elements(&rng, bots);
+        panic!(
+            "random queries failed with seed={} num={}. Shrunk to {} aabbs: {:?}",
+            seed,
+            num,
+            bots.len(),
+            bots
+        );
+    }
+}
+
+fn fails<N>(rng: &Rng, bots: &[BBox<N, usize>]) -> bool
+where
+    N: Num + Signed + FromPrimitive + Debug + Send + Sync,
+{
+    let mut rng = rng.clone();
+    let mut bots = bots.to_vec();
+    std::panic::catch_unwind(std::panic::AssertUnwindSafe(|| {
+        check_elements(&mut rng, &mut bots)
+    }))
+    .is_err()
+}
+
+///Shrink aabbs that fail [`check_elements`] with a clone of `rng` to a smaller set that still fails.
+///
+///Chunks of aabbs are removed while the check keeps failing, halving the chunk size
+///down to single aabbs.
+///The query shapes only depend on `rng` and the number of aabbs, so past 2000 aabbs,
+///where the space they are generated in grows, they can change as the set shrinks.
+pub fn shrink_elements<N>(rng: &Rng, mut bots: Vec<BBox<N, usize>>) -> Vec<BBox<N, usize>>
+where
+    N: Num + Signed + FromPrimitive + Debug + Send + Sync,
+{
+    let mut chunk = bots.len() / 2;
+    while chunk > 0 {
+        let mut start = 0;
+        while start < bots.len() {
+            let end = (start + chunk).min(bots.len());
+            let mut rest = bots[..start].to_vec();
+            rest.extend_from_slice(&bots[end..]);
+            if fails(rng, &rest) {
+                bots = rest;
+            } else {
+                start = end;
+            }
+        }
+        chunk /= 2;
+    }
+    bots
+}
+
+///Check every query against its naive implementation for the given aabbs,
+///using `rng` to generate the query shapes.
+///
+///Panics if a disconnect is detected.
+pub fn check_elements<N>(rng: &mut Rng, bots: &mut [BBox<N, usize>])
+where
+    N: Num + Signed + FromPrimitive + Debug + Send + Sync,
+{
+    let num = bots.len();
+    let space = space_for(num);
+    check_colliding_pairs(&mut pull_apart(bots));
+
+    let mut tree = crate::new(&mut *bots);
+    if let Err(e) = tree.validate() {
+        panic!("broken tree invariant: {}", e);
+    }
+
+    for _ in 0..4 {
+        let r = gen_rect(rng, space, 400);
+        rect::assert_for_all_intersect_rect_mut(&mut tree, &r);
+        rect::assert_for_all_in_rect_mut(&mut tree, &r);
+        rect::assert_for_all_not_in_rect_mut(&mut tree, &r);
+
+        let elems = tree.get_elements();
+        let naive_count = elems.iter().filter(|a| r.contains_rect(&a.rect)).count();
+        let naive_any = elems
+            .iter()
+            .any(|a| r.get_intersect_rect(&a.rect).is_some());
+        assert_eq!(tree.count_in_rect(&r), naive_count);
+        assert_eq!(tree.any_intersect_rect(&r), naive_any);
+
+        let point = gen_point(rng, space);
+        rect::assert_for_all_containing_point_mut(&mut tree, point);
+
+        let k = rng.below(5) as usize;
+        let handler = &mut knearest::default_rect_knearest(&tree);
+        check_k_nearest(&mut tree, point, k, handler);
+
+        let handler = &mut farthest::default_rect_farthest(&tree);
+        farthest::assert_farthest_mut(&mut tree, point, handler);
+
+        let dir = loop {
+            let dir: Vec2<N> = vec2(num_dir(rng), num_dir(rng));
+            if dir != vec2(N::zero(), N::zero()) {
+                break dir;
+            }
+        };
+        let handler = &mut raycast::default_rect_raycast(&tree);
+        raycast::assert_raycast(&mut tree, Ray { point, dir }, handler);
+
+        let seg = segment::Segment {
+            a: point,
+            b: gen_point(rng, space),
+        };
+        let handler = &mut segment::default_rect_segment(&tree);
+        segment::assert_for_all_intersect_segment_mut(&mut tree, &seg, handler);
+
+        let region = gen_convex(rng, space);
+        convex::assert_for_all_intersect_convex_mut(&mut tree, &region);
+    }
+
+    if num <= QUADRATIC_LIMIT {
+        let handler = &mut closest_pair::default_rect_closest_pair(&tree);
+        closest_pair::assert_closest_pair_mut(&mut tree, handler);
+    }
+
+    //The naive graph is quadratic in both time and memory.
+    if num <= 500 {
+        let handler = &mut knearest::default_rect_knearest(&tree);
+        knearest::assert_knn_graph_mut(&mut tree, 3, handler, |a| {
+            vec2(a.rect.x.start, a.rect.y.start)
+        });
+    }
+
+    drop(tree);
+    check_par(rng, bots);
+}
+
+fn num_dir<N: Num + FromPrimitive>(rng: &mut Rng) -> N {
+    num(rng.below(7) as f64 - 3.0)
+}
+
+fn gen_convex<N: Num + Signed + FromPrimitive>(
+    rng: &mut Rng,
+    space: u64,
+) -> convex::ConvexRegion<N> {
+    let c = gen_point::<N>(rng, space);
+    let r: N = num(2.0 * (1 + rng.below(150)) as f64);
+    //A diamond in counter clockwise order.
+    convex::ConvexRegion::from_points(&[
+        vec2(c.x + r, c.y),
+        vec2(c.x, c.y + r),
+        vec2(c.x - r, c.y),
+        vec2(c.x, c.y - r),
+    ])
+}
+
+fn check_par<N>(rng: &mut Rng, bots: &mut [BBox<N, usize>])
+where
+    N: Num + Signed + FromPrimitive + Debug + Send + Sync,
+{
+    let space = space_for(bots.len());
+    let region = gen_convex(rng, space);
+    let mut naive_convex = Vec::new();
+    convex::naive_for_all_intersect_convex_mut(PMut::new(bots), &region, |a| {
+        naive_convex.push(a.inner)
+    });
+    naive_convex.sort_unstable();
+
+    let r = gen_rect(rng, space, 400);
+    let mut naive_rect = [Vec::new(), Vec::new(), Vec::new()];
+    rect::naive_for_all_intersect_rect_mut(PMut::new(bots), &r, |a| naive_rect[0].push(a.inner));
+    rect::naive_for_all_in_rect_mut(PMut::new(bots), &r, |a| naive_rect[1].push(a.inner));
+    rect::naive_for_all_not_in_rect_mut(PMut::new(bots), &r, |a| naive_rect[2].push(a.inner));
+    for a in naive_rect.iter_mut() {
+        a.sort_unstable();
+    }
+
+    let mut tree = crate::new_par(bots);
+    if let Err(e) = tree.validate() {
+        panic!("broken tree invariant in parallel tree: {}", e);
+    }
+
+    let found = Mutex::new(Vec::new());
+    tree.for_all_intersect_convex_par(&region, |a| found.lock().unwrap().push(a));
+    assert_eq!(ids(found.into_inner().unwrap()), naive_convex);
+
+    let found = Mutex::new(Vec::new());
+    tree.for_all_intersect_rect_par(&r, |a| found.lock().unwrap().push(a));
+    assert_eq!(ids(found.into_inner().unwrap()), naive_rect[0]);
+
+    let found = Mutex::new(Vec::new());
+    tree.for_all_in_rect_par(&r, |a| found.lock().unwrap().push(a));
+    assert_eq!(ids(found.into_inner().unwrap()), naive_rect[1]);
+
+    let found = Mutex::new(Vec::new());
+    tree.for_all_not_in_rect_par(&r, |a| found.lock().unwrap().push(a));
+    assert_eq!(ids(found.into_inner().unwrap()), naive_rect[2]);
+
+    let mut found = Vec::new();
+    tree.for_all_not_in_rect(&r, |a| found.push(a));
+    assert_eq!(ids(found), naive_rect[2]);
+
+    let found = Mutex::new(Vec::new());
+    tree.for_all_not_in_rect_par_mut(&r, |a| found.lock().unwrap().push(a.inner));
+    let mut found = found.into_inner().unwrap();
+    found.sort_unstable();
+    assert_eq!(found, naive_rect[2]);
+}
@@ -1,4 +1,5 @@
 use super::*;
+use elems::Indexed;
 use par::ParallelBuilder;
 ///Builder pattern for Tree.
 ///For most usecases, the user is suggested to use
@@ -30,12 +31,41 @@ where
             &mut SplitterEmpty,
             self.prebuilder,
             self.rebal_strat,
+            Vec::new(),
         );
         NotSorted(inner)
     }
 
+    ///Build in parallel, recording how the elements were reordered.
+    ///See [`TreeBuilder::build_with_permutation_seq`].
+    pub fn build_with_permutation_par(&mut self) -> (Tree<'a, T>, Permutation) {
+        let bots = core::mem::replace(&mut self.bots, &mut []);
+        let mut inds: Vec<_> = (0..bots.len()).collect();
+
+        let pswitch = self.par_builder.build_for_tree_of_height(self.prebuilder.get_height());
+        let tree = create_tree_par(
+            self.axis,
+            pswitch,
+            Indexed {
+                elems: bots,
+                inds: &mut inds,
+            },
+            DefaultSorter,
+            &mut SplitterEmpty,
+            self.prebuilder,
+            self.rebal_strat,
+            Vec::new(),
+        );
+        (tree, Permutation::new(inds))
+    }
+
     ///Build in parallel
     pub fn build_par(&mut self) -> Tree<'a, T> {
+        self.build_par_with_nodes(Vec::new())
+    }
+
+    ///Build in parallel, reusing the allocation of `nodes`.
+    pub(crate) fn build_par_with_nodes(&mut self, nodes: Vec<Node<'a, T>>) -> Tree<'a, T> {
         let bots = core::mem::replace(&mut self.bots, &mut []);
 
         let pswitch = self.par_builder.build_for_tree_of_height(self.prebuilder.get_height());
@@ -48,6 +78,7 @@ where
             &mut SplitterEmpty,
             self.prebuilder,
             self.rebal_strat,
+            nodes,
         )
     }
 }
@@ -90,12 +121,94 @@ impl<'a, T: Aabb> TreeBuilder<'a, T> {
             &mut SplitterEmpty,
             self.prebuilder,
             self.rebal_strat,
+            Vec::new(),
         );
         NotSorted(inner)
     }
 
+    ///Build sequentially, first checking that every aabb is valid.
+    ///An aabb is invalid if the start of one of its ranges is not less than or equal to its end,
+    ///which is the case if either is `NaN`.
+    ///
+    ///On error, the elements are left as they were and the builder can still be used.
+    ///
+    /// # Examples
+    ///
+    ///```
+    /// use broccoli::build::TreeBuilder;
+    /// let mut bots = [axgeom::rect(0.0,10.0,0.0,10.0),axgeom::rect(5.0,f32::NAN,0.0,10.0),axgeom::rect(5.0,3.0,0.0,10.0)];
+    ///
+    /// let e = TreeBuilder::new(&mut bots).build_checked().err().unwrap();
+    /// assert_eq!(e.indices,vec![1,2]);
+    ///```
+    pub fn build_checked(&mut self) -> Result<Tree<'a, T>, InvalidAabbError> {
+        let indices: Vec<usize> = self
+            .bots
+            .iter()
+            .enumerate()
+            .filter(|(_, a)| {
+                let r = a.get();
+                !(r.x.start <= r.x.end && r.y.start <= r.y.end)
+            })
+            .map(|(i, _)| i)
+            .collect();
+
+        if indices.is_empty() {
+            Ok(self.build_seq())
+        } else {
+            Err(InvalidAabbError { indices })
+        }
+    }
+
+    ///Build sequentially, recording how the elements were reordered.
+    ///
+    ///The original index of every element is moved along with it during the build,
+    ///so this needs an extra `usize` per element compared to [`TreeBuilder::build_seq`].
+    ///
+    /// # Examples
+    ///
+    ///```
+    /// use broccoli::{bbox,rect};
+    /// use broccoli::build::TreeBuilder;
+    /// let mut bots = [bbox(rect(30,40,0,10),0),bbox(rect(0,10,0,10),1),bbox(rect(20,30,0,10),2)];
+    ///
+    /// let (mut tree, perm) = TreeBuilder::new(&mut bots).build_with_permutation_seq();
+    /// for (i,a) in tree.get_elements().iter().enumerate(){
+    ///     assert_eq!(a.inner,perm.original_index(i));
+    ///     assert_eq!(perm.tree_index(a.inner),i);
+    /// }
+    ///
+    /// perm.unpermute(&mut bots);
+    /// assert_eq!(bots[0].inner,0);
+    /// assert_eq!(bots[1].inner,1);
+    /// assert_eq!(bots[2].inner,2);
+    ///```
+    pub fn build_with_permutation_seq(&mut self) -> (Tree<'a, T>, Permutation) {
+        let bots = core::mem::replace(&mut self.bots, &mut []);
+        let mut inds: Vec<_> = (0..bots.len()).collect();
+
+        let tree = create_tree_seq(
+            self.axis,
+            Indexed {
+                elems: bots,
+                inds: &mut inds,
+            },
+            DefaultSorter,
+            &mut SplitterEmpty,
+            self.prebuilder,
+            self.rebal_strat,
+            Vec::new(),
+        );
+        (tree, Permutation::new(inds))
+    }
+
     ///Build sequentially
     pub fn build_seq(&mut self) -> Tree<'a, T> {
+        self.build_seq_with_nodes(Vec::new())
+    }
+
+    ///Build sequentially, reusing the allocation of `nodes`.
+    pub(crate) fn build_seq_with_nodes(&mut self, nodes: Vec<Node<'a, T>>) -> Tree<'a, T> {
         let bots = core::mem::replace(&mut self.bots, &mut []);
 
         create_tree_seq(
@@ -105,6 +218,7 @@ impl<'a, T: Aabb> TreeBuilder<'a, T> {
             &mut SplitterEmpty,
             self.prebuilder,
             self.rebal_strat,
+            nodes,
         )
     }
 
@@ -139,23 +253,118 @@ impl<'a, T: Aabb> TreeBuilder<'a, T> {
             splitter,
             self.prebuilder,
             self.rebal_strat,
+            Vec::new(),
         )
     }
 }
 
+///Returned by [`TreeBuilder::build_checked`] if any aabb is invalid.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct InvalidAabbError {
+    ///The indices of the invalid elements in the slice passed to the builder.
+    pub indices: Vec<usize>,
+}
+
+impl core::fmt::Display for InvalidAabbError {
+    fn fmt(&self, f: &mut core::fmt::Formatter) -> core::fmt::Result {
+        write!(f, "invalid aabbs at indices {:?}", self.indices)
+    }
+}
+
+///How the elements were reordered by [`TreeBuilder::build_with_permutation_seq`].
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct Permutation {
+    to_original: Vec<usize>,
+    to_tree: Vec<usize>,
+}
+
+impl Permutation {
+    fn new(to_original: Vec<usize>) -> Permutation {
+        let mut to_tree = vec![0; to_original.len()];
+        for (i, &a) in to_original.iter().enumerate() {
+            to_tree[a] = i;
+        }
+        Permutation {
+            to_original,
+            to_tree,
+        }
+    }
+
+    ///The number of elements.
+    #[must_use]
+    pub fn len(&self) -> usize {
+        self.to_original.len()
+    }
+
+    ///Returns true if there are no elements.
+    #[must_use]
+    pub fn is_empty(&self) -> bool {
+        self.to_original.is_empty()
+    }
+
+    ///The index in the slice passed to the builder of the element at `tree_index`
+    ///in [`Tree::get_elements`].
+    #[must_use]
+    pub fn original_index(&self, tree_index: usize) -> usize {
+        self.to_original[tree_index]
+    }
+
+    ///The index in [`Tree::get_elements`] of the element at `original` in the slice
+    ///passed to the builder.
+    #[must_use]
+    pub fn tree_index(&self, original: usize) -> usize {
+        self.to_tree[original]
+    }
+
+    ///Move elements that are in the original order into the order of the tree.
+    ///
+    ///Panics if the length of `elems` does not equal [`Permutation::len`].
+    pub fn permute<T>(&self, elems: &mut [T]) {
+        gather(elems, &self.to_original);
+    }
+
+    ///Move elements that are in the order of the tree back into the original order.
+    ///
+    ///Panics if the length of `elems` does not equal [`Permutation::len`].
+    pub fn unpermute<T>(&self, elems: &mut [T]) {
+        gather(elems, &self.to_tree);
+    }
+}
+
+//Reorder in place so that the new elems[i] is the old elems[from[i]].
+fn gather<T>(elems: &mut [T], from: &[usize]) {
+    assert_eq!(elems.len(), from.len());
+    let mut done = vec![false; elems.len()];
+    for start in 0..elems.len() {
+        if done[start] {
+            continue;
+        }
+        let mut j = start;
+        done[j] = true;
+        while from[j] != start {
+            elems.swap(j, from[j]);
+            j = from[j];
+            done[j] = true;
+        }
+    }
+}
+
 fn create_tree_seq<'a, T: Aabb, K: Splitter>(
     div_axis: DefaultA,
-    rest: &'a mut [T],
+    rest: impl Elems<'a, T = T>,
     sorter: impl Sorter,
     splitter: &mut K,
     height: TreePreBuilder,
     binstrat: BinStrat,
+    mut nodes: Vec<Node<'a, T>>,
 ) -> Tree<'a, T> {
-    let num_aabbs = rest.len();
+    let num_aabbs = rest.elems().len();
 
     let cc = height.num_nodes();
     //let cc = tree::nodes_left(0, height);
-    let mut nodes = Vec::with_capacity(cc);
+    //The nodes may be an allocation that is being reused.
+    nodes.clear();
+    nodes.reserve(cc);
 
     let r = Recurser {
         height: height.get_height(),
@@ -166,6 +375,9 @@ fn create_tree_seq<'a, T: Aabb, K: Splitter>(
     r.recurse_preorder_seq(div_axis, rest, &mut nodes, splitter, 0);
     assert_eq!(cc, nodes.len());
 
+    create_bounds(div_axis, &mut nodes);
+    create_subtree_lens(&mut nodes);
+
     let inner = compt::dfs_order::CompleteTreeContainer::from_preorder(nodes).unwrap();
 
     let k = inner
@@ -177,22 +389,26 @@ fn create_tree_seq<'a, T: Aabb, K: Splitter>(
     Tree { inner,num_aabbs }
 }
 
+#[allow(clippy::too_many_arguments)]
 fn create_tree_par<'a, JJ: par::Joiner, T: Aabb + Send + Sync, K: Splitter + Send + Sync>(
     div_axis: DefaultA,
     dlevel: JJ,
-    rest: &'a mut [T],
+    rest: impl Elems<'a, T = T> + Send,
     sorter: impl Sorter,
     splitter: &mut K,
     height: TreePreBuilder,
     binstrat: BinStrat,
+    mut nodes: Vec<Node<'a, T>>,
 ) -> Tree<'a, T>
 where
     T::Num: Send + Sync,
 {
-    let num_aabbs = rest.len();
+    let num_aabbs = rest.elems().len();
     let cc = height.num_nodes();
     //let cc = tree::nodes_left(0, height);
-    let mut nodes = Vec::with_capacity(cc);
+    //The nodes may be an allocation that is being reused.
+    nodes.clear();
+    nodes.reserve(cc);
 
     let r = Recurser {
         height: height.get_height(),
@@ -203,6 +419,10 @@ where
     r.recurse_preorder(div_axis, dlevel, rest, &mut nodes, splitter, 0);
 
     assert_eq!(cc, nodes.len());
+
+    create_bounds(div_axis, &mut nodes);
+    create_subtree_lens(&mut nodes);
+
     let inner = compt::dfs_order::CompleteTreeContainer::from_preorder(nodes).unwrap();
 
     let k = inner
@@ -214,34 +434,38 @@ where
     Tree { inner ,num_aabbs}
 }
 
-struct Recurser<'a, T: Aabb, K: Splitter, S: Sorter> {
+struct Recurser<'a, E: Elems<'a>, K: Splitter, S: Sorter> {
     height: usize,
     binstrat: BinStrat,
     sorter: S,
-    _p: PhantomData<(K, &'a T)>,
+    _p: PhantomData<(K, &'a E::T)>,
 }
 
-struct NonLeafFinisher<'a, A, T: Aabb> {
+struct NonLeafFinisher<'a, A, E: Elems<'a>> {
     axis: A,
-    div: Option<T::Num>, //This can be null if there are no bots left at all
-    mid: &'a mut [T],
+    div: Option<<E::T as Aabb>::Num>, //This can be null if there are no bots left at all
+    mid: E,
 }
-impl<'a, A: Axis, T: Aabb> NonLeafFinisher<'a, A, T> {
-    fn finish(self, sorter: impl Sorter) -> Node<'a, T> {
-        sorter.sort(self.axis.next(), self.mid);
-        let cont = create_cont(self.axis, self.mid);
+impl<'a, A: Axis, E: Elems<'a>> NonLeafFinisher<'a, A, E> {
+    fn finish(mut self, sorter: impl Sorter) -> Node<'a, E::T> {
+        sorter.sort(self.axis.next(), &mut self.mid);
+        let mid = self.mid.into_elems();
+        let cont = create_cont(self.axis, mid);
 
         Node {
-            range: PMut::new(self.mid),
+            range: PMut::new(mid),
             cont,
             div: self.div,
+            bounds: None,
+            subtree_len: 0,
         }
     }
 }
 
-impl<'a, T: Aabb, K: Splitter, S: Sorter> Recurser<'a, T, K, S> {
-    fn create_leaf<A: Axis>(&self, axis: A, rest: &'a mut [T]) -> Node<'a, T> {
-        self.sorter.sort(axis.next(), rest);
+impl<'a, E: Elems<'a>, K: Splitter, S: Sorter> Recurser<'a, E, K, S> {
+    fn create_leaf<A: Axis>(&self, axis: A, mut rest: E) -> Node<'a, E::T> {
+        self.sorter.sort(axis.next(), &mut rest);
+        let rest = rest.into_elems();
 
         let cont = create_cont(axis, rest);
 
@@ -249,14 +473,16 @@ impl<'a, T: Aabb, K: Splitter, S: Sorter> Recurser<'a, T, K, S> {
             range: PMut::new(rest),
             cont,
             div: None,
+            bounds: None,
+            subtree_len: 0,
         }
     }
 
     fn create_non_leaf<A: Axis>(
         &self,
         axis: A,
-        rest: &'a mut [T],
-    ) -> (NonLeafFinisher<'a, A, T>, &'a mut [T], &'a mut [T]) {
+        rest: E,
+    ) -> (NonLeafFinisher<'a, A, E>, E, E) {
         match construct_non_leaf(self.binstrat, axis, rest) {
             ConstructResult::NonEmpty {
                 div,
@@ -273,13 +499,19 @@ impl<'a, T: Aabb, K: Splitter, S: Sorter> Recurser<'a, T, K, S> {
                 right,
             ),
             ConstructResult::Empty(mid) => {
+                //The children are empty too, but their slices still have to start
+                //where the elements of this subtree are, so that the elements
+                //of every subtree stay contiguous in dfs preorder.
+                let (mid, rest) = mid.split_at(0);
+                let (left, right) = rest.split_at(0);
+
                 let node = NonLeafFinisher {
                     mid,
                     div: None,
                     axis,
                 };
 
-                (node, &mut [], &mut [])
+                (node, left, right)
             }
         }
     }
@@ -287,8 +519,8 @@ impl<'a, T: Aabb, K: Splitter, S: Sorter> Recurser<'a, T, K, S> {
     fn recurse_preorder_seq<A: Axis>(
         &self,
         axis: A,
-        rest: &'a mut [T],
-        nodes: &mut Vec<Node<'a, T>>,
+        rest: E,
+        nodes: &mut Vec<Node<'a, E::T>>,
         splitter: &mut K,
         depth: usize,
     ) {
@@ -308,16 +540,17 @@ impl<'a, T: Aabb, K: Splitter, S: Sorter> Recurser<'a, T, K, S> {
         }
     }
 }
-impl<'a, T: Aabb + Send + Sync, K: Splitter + Send + Sync, S: Sorter> Recurser<'a, T, K, S>
+impl<'a, E: Elems<'a> + Send, K: Splitter + Send + Sync, S: Sorter> Recurser<'a, E, K, S>
 where
-    T::Num: Send + Sync,
+    E::T: Send + Sync,
+    <E::T as Aabb>::Num: Send + Sync,
 {
     fn recurse_preorder<A: Axis, JJ: par::Joiner>(
         &self,
         axis: A,
         dlevel: JJ,
-        rest: &'a mut [T],
-        nodes: &mut Vec<Node<'a, T>>,
+        rest: E,
+        nodes: &mut Vec<Node<'a, E::T>>,
         splitter: &mut K,
         depth: usize,
     ) {
@@ -449,6 +682,64 @@ fn bench_cont2(b: &mut test::Bencher) {
     });
 }
 
+//Compute the bounding rect of every subtree given the nodes in pre-order.
+//Fill in the number of elements of every subtree.
+fn create_subtree_lens<T: Aabb>(nodes: &mut [Node<T>]) -> usize {
+    let (node, rest) = nodes.split_first_mut().unwrap();
+
+    let mut len = node.range.len();
+    if !rest.is_empty() {
+        let (left, right) = rest.split_at_mut(rest.len() / 2);
+        len += create_subtree_lens(left) + create_subtree_lens(right);
+    }
+
+    node.subtree_len = len;
+    len
+}
+
+fn create_bounds<A: Axis, T: Aabb>(
+    axis: A,
+    nodes: &mut [Node<T>],
+) -> Option<axgeom::Rect<T::Num>> {
+    let (node, rest) = nodes.split_first_mut().unwrap();
+
+    let mut bounds = if node.range.is_empty() {
+        None
+    } else {
+        Some(axgeom::Rect {
+            x: if axis.is_xaxis() {
+                node.cont
+            } else {
+                create_cont(axgeom::XAXIS, &node.range)
+            },
+            y: if axis.is_xaxis() {
+                create_cont(axgeom::YAXIS, &node.range)
+            } else {
+                node.cont
+            },
+        })
+    };
+
+    if !rest.is_empty() {
+        let (left, right) = rest.split_at_mut(rest.len() / 2);
+        for child in [left, right].iter_mut() {
+            if let Some(b) = create_bounds(axis.next(), child) {
+                bounds = Some(match bounds {
+                    Some(mut a) => {
+                        crate::query::tools::grow_range(&mut a.x, &b.x);
+                        crate::query::tools::grow_range(&mut a.y, &b.y);
+                        a
+                    }
+                    None => b,
+                });
+            }
+        }
+    }
+
+    node.bounds = bounds;
+    bounds
+}
+
 fn create_cont<A: Axis, T: Aabb>(axis: A, middle: &[T]) -> axgeom::Range<T::Num> {
     match middle.split_first() {
         Some((first, rest)) => {
@@ -480,35 +771,27 @@ fn create_cont<A: Axis, T: Aabb>(axis: A, middle: &[T]) -> axgeom::Range<T::Num>
     }
 }
 
-enum ConstructResult<'a, T: Aabb> {
+enum ConstructResult<'a, E: Elems<'a>> {
     NonEmpty {
-        div: T::Num,
-        mid: &'a mut [T],
-        right: &'a mut [T],
-        left: &'a mut [T],
+        div: <E::T as Aabb>::Num,
+        mid: E,
+        right: E,
+        left: E,
     },
-    Empty(&'a mut [T]),
+    Empty(E),
 }
 
-fn construct_non_leaf<T: Aabb>(
+fn construct_non_leaf<'a, E: Elems<'a>>(
     bin_strat: BinStrat,
     div_axis: impl Axis,
-    bots: &mut [T],
-) -> ConstructResult<T> {
-    let med = if bots.is_empty() {
+    mut bots: E,
+) -> ConstructResult<'a, E> {
+    let med = if bots.elems().is_empty() {
         return ConstructResult::Empty(bots);
     } else {
-        let closure = move |a: &T, b: &T| -> core::cmp::Ordering {
-            crate::util::compare_bots(div_axis, a, b)
-        };
-
-        let k = {
-            let mm = bots.len() / 2;
-            pdqselect::select_by(bots, mm, closure);
-            &bots[mm]
-        };
-
-        k.get().get_range(div_axis).start
+        let mm = bots.elems().len() / 2;
+        bots.select(div_axis, mm);
+        bots.elems()[mm].get().get_range(div_axis).start
     };
 
     //It is very important that the median bot end up be binned into the middile bin.
@@ -517,17 +800,12 @@ fn construct_non_leaf<T: Aabb>(
     //Very important that if a bots border is exactly on the divider, it is put in the middle.
     //If this were not true, there is no guarentee that the middile bin has bots in it even
     //though we did pick a divider.
-    let binned = match bin_strat {
-        BinStrat::Checked => oned::bin_middle_left_right(div_axis, &med, bots),
-        BinStrat::NotChecked => unsafe {
-            oned::bin_middle_left_right_unchecked(div_axis, &med, bots)
-        },
-    };
+    let [middle, left, right] = bots.bin(bin_strat, div_axis, &med);
 
     ConstructResult::NonEmpty {
-        mid: binned.middle,
+        mid: middle,
         div: med,
-        left: binned.left,
-        right: binned.right,
+        left,
+        right,
     }
 }
@@ -0,0 +1,198 @@
+use super::oned;
+use super::BinStrat;
+use crate::inner_prelude::*;
+use crate::util::compare_bots;
+use core::cmp::Ordering;
+
+///The elements of a subtree while it is being built.
+///Every step of the construction that reorders the elements goes through this trait,
+///so that a build can move other data along with the elements.
+pub trait Elems<'a>: Sized {
+    type T: Aabb + 'a;
+
+    fn elems(&self) -> &[Self::T];
+
+    fn split_at(self, mid: usize) -> (Self, Self);
+
+    fn into_elems(self) -> &'a mut [Self::T];
+
+    ///Move the `k`th element into its sorted position, with smaller elements before it
+    ///and bigger elements after it.
+    fn select(&mut self, axis: impl Axis, k: usize);
+
+    fn sort(&mut self, axis: impl Axis);
+
+    ///Returns the elements that intersect the divider, those to the left of it,
+    ///and those to the right of it, laid out in that order.
+    fn bin(self, bin_strat: BinStrat, axis: impl Axis, med: &<Self::T as Aabb>::Num) -> [Self; 3];
+}
+
+impl<'a, T: Aabb> Elems<'a> for &'a mut [T] {
+    type T = T;
+
+    #[inline(always)]
+    fn elems(&self) -> &[T] {
+        self
+    }
+
+    #[inline(always)]
+    fn split_at(self, mid: usize) -> (Self, Self) {
+        self.split_at_mut(mid)
+    }
+
+    #[inline(always)]
+    fn into_elems(self) -> &'a mut [T] {
+        self
+    }
+
+    fn select(&mut self, axis: impl Axis, k: usize) {
+        pdqselect::select_by(self, k, |a, b| compare_bots(axis, a, b));
+    }
+
+    fn sort(&mut self, axis: impl Axis) {
+        crate::util::sweeper_update(axis, self);
+    }
+
+    fn bin(self, bin_strat: BinStrat, axis: impl Axis, med: &T::Num) -> [Self; 3] {
+        let binned = match bin_strat {
+            BinStrat::Checked => oned::bin_middle_left_right(axis, med, self),
+            BinStrat::NotChecked => unsafe {
+                oned::bin_middle_left_right_unchecked(axis, med, self)
+            },
+        };
+        [binned.middle, binned.left, binned.right]
+    }
+}
+
+///Elements along with the original index of each of them.
+///Every swap of two elements swaps their indices too.
+pub struct Indexed<'a, 'b, T> {
+    pub elems: &'a mut [T],
+    pub inds: &'b mut [usize],
+}
+
+impl<'a, 'b, T: Aabb> Indexed<'a, 'b, T> {
+    #[inline(always)]
+    fn swap(&mut self, a: usize, b: usize) {
+        self.elems.swap(a, b);
+        self.inds.swap(a, b);
+    }
+
+    #[inline(always)]
+    fn cmp(&self, axis: impl Axis, a: usize, b: usize) -> Ordering {
+        compare_bots(axis, &self.elems[a], &self.elems[b])
+    }
+
+    //Restore the heap property of the subtree at `root`, for a heap of the first `end` elements.
+    fn sift_down(&mut self, axis: impl Axis, mut root: usize, end: usize) {
+        loop {
+            let mut child = 2 * root + 1;
+            if child >= end {
+                break;
+            }
+            if child + 1 < end && self.cmp(axis, child, child + 1) == Ordering::Less {
+                child += 1;
+            }
+            if self.cmp(axis, root, child) != Ordering::Less {
+                break;
+            }
+            self.swap(root, child);
+            root = child;
+        }
+    }
+}
+
+impl<'a, 'b, T: Aabb> Elems<'a> for Indexed<'a, 'b, T> {
+    type T = T;
+
+    #[inline(always)]
+    fn elems(&self) -> &[T] {
+        self.elems
+    }
+
+    fn split_at(self, mid: usize) -> (Self, Self) {
+        let (a, b) = self.elems.split_at_mut(mid);
+        let (c, d) = self.inds.split_at_mut(mid);
+        (Indexed { elems: a, inds: c }, Indexed { elems: b, inds: d })
+    }
+
+    #[inline(always)]
+    fn into_elems(self) -> &'a mut [T] {
+        self.elems
+    }
+
+    //Quickselect with a three way partition, so that duplicates do not make it quadratic.
+    fn select(&mut self, axis: impl Axis, k: usize) {
+        let (mut lo, mut hi) = (0, self.elems.len());
+        while hi - lo > 1 {
+            self.swap(lo, lo + (hi - lo) / 2);
+
+            //The elements in [lt,i) are equal to the pivot, which starts at lo.
+            let (mut lt, mut i, mut gt) = (lo, lo + 1, hi);
+            while i < gt {
+                match self.cmp(axis, i, lt) {
+                    Ordering::Less => {
+                        self.swap(lt, i);
+                        lt += 1;
+                        i += 1;
+                    }
+                    Ordering::Greater => {
+                        gt -= 1;
+                        self.swap(i, gt);
+                    }
+                    Ordering::Equal => i += 1,
+                }
+            }
+
+            if k < lt {
+                hi = lt;
+            } else if k >= gt {
+                lo = gt;
+            } else {
+                return;
+            }
+        }
+    }
+
+    //Heapsort, since the slice sorts of core can not move the indices along.
+    fn sort(&mut self, axis: impl Axis) {
+        let len = self.elems.len();
+        for i in (0..len / 2).rev() {
+            self.sift_down(axis, i, len);
+        }
+        for end in (1..len).rev() {
+            self.swap(0, end);
+            self.sift_down(axis, 0, end);
+        }
+    }
+
+    fn bin(mut self, _bin_strat: BinStrat, axis: impl Axis, med: &T::Num) -> [Self; 3] {
+        let mut left_end = 0;
+        let mut middle_end = 0;
+
+        //Same as oned::bin_middle_left_right.
+        for index_at in 0..self.elems.len() {
+            match self.elems[index_at]
+                .get()
+                .get_range(axis)
+                .contains_ext(*med)
+            {
+                Ordering::Equal => {
+                    self.swap(index_at, left_end);
+                    self.swap(left_end, middle_end);
+                    middle_end += 1;
+                    left_end += 1;
+                }
+                Ordering::Greater => {
+                    self.swap(index_at, left_end);
+                    left_end += 1;
+                }
+                Ordering::Less => {}
+            }
+        }
+
+        let (rest, right) = self.split_at(left_end);
+        let (middle, left) = rest.split_at(middle_end);
+        [middle, left, right]
+    }
+}
@@ -13,8 +13,12 @@ pub const fn default_axis() -> DefaultA {
     XAXIS
 }
 
+mod elems;
 mod oned;
+use elems::Elems;
 
+pub use builder::InvalidAabbError;
+pub use builder::Permutation;
 pub use builder::TreeBuilder;
 mod builder;
 
@@ -46,15 +50,15 @@ pub trait Splitter: Sized {
 ///where one implementation actually does sort the tree, while the other one
 ///does nothing when sort() is called.
 trait Sorter: Copy + Clone + Send + Sync {
-    fn sort(&self, axis: impl Axis, bots: &mut [impl Aabb]);
+    fn sort<'a>(&self, axis: impl Axis, bots: &mut impl Elems<'a>);
 }
 
 #[derive(Copy, Clone)]
 struct DefaultSorter;
 
 impl Sorter for DefaultSorter {
-    fn sort(&self, axis: impl Axis, bots: &mut [impl Aabb]) {
-        crate::util::sweeper_update(axis, bots);
+    fn sort<'a>(&self, axis: impl Axis, bots: &mut impl Elems<'a>) {
+        bots.sort(axis);
     }
 }
 
@@ -62,7 +66,7 @@ impl Sorter for DefaultSorter {
 struct NoSorter;
 
 impl Sorter for NoSorter {
-    fn sort(&self, _axis: impl Axis, _bots: &mut [impl Aabb]) {}
+    fn sort<'a>(&self, _axis: impl Axis, _bots: &mut impl Elems<'a>) {}
 }
 
 const fn nodes_left(depth: usize, height: usize) -> usize {
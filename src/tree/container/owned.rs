@@ -23,6 +23,7 @@ pub struct TreeIndOwned<N: Num, T> {
     tree: TreeIndPtr<N,T>,
     _base: Box<[BBox<N,Ptr<T>>]>,
     _bots: Box<[T]>,
+    spare: Option<TreePtr<BBox<N,Ptr<T>>>>,
 }
 fn convert_box<T,X>(mut v_orig:Box<[T]>)->Box<[X]>{
     assert_eq!(core::mem::size_of::<X>(),core::mem::size_of::<T>());
@@ -36,7 +37,51 @@ fn convert_box<T,X>(mut v_orig:Box<[T]>)->Box<[X]>{
     }
 }
 
+//Convert a tree into its pointer form, which does not borrow the elements.
+fn tree_ptr<A: Aabb, B: Aabb>(tree: Tree<A>) -> TreePtr<B> {
+    TreePtr {
+        _num_aabbs: tree.num_aabbs,
+        _inner: unsafe { tree.inner.convert() },
+    }
+}
+
+//A tree with no elements that stands in for a tree while it is rebuilt,
+//so that the container stays valid if the user's function panics.
+fn empty_tree_ptr<T: Aabb>() -> TreePtr<T> {
+    tree_ptr::<T, T>(crate::new(&mut []))
+}
+
+//Take a tree out to rebuild it, leaving the empty tree kept in `spare` in its place.
+//An empty tree is only created if the last rebuild panicked or there was none yet.
+fn take_tree<T: Aabb>(tree: &mut TreePtr<T>, spare: &mut Option<TreePtr<T>>) -> TreePtr<T> {
+    let empty = spare.take().unwrap_or_else(empty_tree_ptr);
+    core::mem::replace(tree, empty)
+}
+
+//Put a rebuilt tree in place, keeping the empty tree for the next rebuild.
+fn put_tree<T: Aabb>(tree: &mut TreePtr<T>, spare: &mut Option<TreePtr<T>>, new: TreePtr<T>) {
+    *spare = Some(core::mem::replace(tree, new));
+}
+
+//Reuse the allocation of the nodes of a tree that is no longer needed.
+fn recycle_nodes<'a, A: Aabb, B: Aabb>(tree: TreePtr<A>) -> Vec<Node<'a, B>> {
+    assert_eq!(core::mem::size_of::<NodePtr<A>>(), core::mem::size_of::<Node<B>>());
+    assert_eq!(core::mem::align_of::<NodePtr<A>>(), core::mem::align_of::<Node<B>>());
+    let mut nodes = tree._inner.into_nodes().into_vec();
+    nodes.clear();
+    let mut nodes = core::mem::ManuallyDrop::new(nodes);
+    unsafe { Vec::from_raw_parts(nodes.as_mut_ptr() as *mut Node<B>, 0, nodes.capacity()) }
+}
+
 impl<N: Num + Send + Sync, T: Send + Sync> TreeIndOwned<N, T> {
+    ///The parallel version of [`TreeIndOwned::rebuild`].
+    pub fn rebuild_par(&mut self, func: impl FnMut(&mut T) -> Rect<N>) {
+        let nodes = recycle_nodes(take_tree(&mut self.tree.tree, &mut self.spare));
+        let base = self.update_base(func);
+        let tree = tree_ptr(TreeBuilder::new(base).build_par_with_nodes(nodes));
+        put_tree(&mut self.tree.tree, &mut self.spare, tree);
+    }
+
     pub fn new_par(mut bots: Box<[T]>, func: impl FnMut(&mut T) -> Rect<N>) -> TreeIndOwned<N, T> {
         
         let mut base=TreeIndBase::new(&mut bots,func);
@@ -47,7 +92,8 @@ impl<N: Num + Send + Sync, T: Send + Sync> TreeIndOwned<N, T> {
         TreeIndOwned {
             tree,
             _bots: bots,
-            _base
+            _base,
+            spare: None,
         }
     }
 }
@@ -62,12 +108,62 @@ impl<N: Num, T> TreeIndOwned<N, T> {
         TreeIndOwned {
             tree,
             _bots: bots,
-            _base
+            _base,
+            spare: None,
         }
     }
 }
 
 impl<N: Num, T> TreeIndOwned<N, T> {
+    ///Recompute the aabb of every element with `func` and rebuild the tree,
+    ///reusing the existing allocations.
+    ///
+    /// # Examples
+    ///
+    ///```
+    /// use broccoli::{prelude::*,container::*,rect};
+    ///
+    /// let bots = vec![rect(0,10,0,10),rect(20,30,0,10)].into_boxed_slice();
+    /// let mut tree = TreeIndOwned::new(bots,|a|*a);
+    ///
+    /// let mut num = 0;
+    /// tree.as_tree_mut().find_colliding_pairs_mut(|_,_|num+=1);
+    /// assert_eq!(num,0);
+    ///
+    /// //Move the second element onto the first.
+    /// tree.rebuild(|a|{
+    ///     if a.x.start == 20 {
+    ///         *a = rect(5,15,0,10);
+    ///     }
+    ///     *a
+    /// });
+    ///
+    /// tree.as_tree_mut().find_colliding_pairs_mut(|_,_|num+=1);
+    /// assert_eq!(num,1);
+    ///```
+    pub fn rebuild(&mut self, func: impl FnMut(&mut T) -> Rect<N>) {
+        let nodes = recycle_nodes(take_tree(&mut self.tree.tree, &mut self.spare));
+        let base = self.update_base(func);
+        let tree = tree_ptr(TreeBuilder::new(base).build_seq_with_nodes(nodes));
+        put_tree(&mut self.tree.tree, &mut self.spare, tree);
+    }
+
+    fn update_base(&mut self, mut func: impl FnMut(&mut T) -> Rect<N>) -> &mut [BBox<N, &mut T>] {
+        for b in self._base.iter_mut() {
+            b.rect = func(unsafe { &mut *b.inner.0 });
+        }
+        unsafe { &mut *(&mut *self._base as *mut [BBox<N, Ptr<T>>] as *mut [BBox<N, &mut T>]) }
+    }
+
+    ///Return the elements in the order they were passed to [`TreeIndOwned::new`].
+    ///
+    ///Only the pointers to the elements are reordered by the tree, so unlike
+    ///[`TreeOwned::into_inner`], which returns the elements in the order of the tree,
+    ///the original order is kept.
+    pub fn into_inner(self) -> Box<[T]> {
+        self._bots
+    }
+
     ///Cant use Deref because of lifetime
     #[inline(always)]
     pub fn as_tree<'a,'b,'c>(&'c self) -> &'c TreeInd<'a,'b,N, T> {
@@ -105,12 +201,14 @@ impl<N: Num, T> TreeIndOwned<N, T> {
 pub struct TreeOwned<T: Aabb> {
     inner: TreePtr<T>,
     _bots: Box<[T]>,
+    spare: Option<TreePtr<T>>,
 }
 
 impl<T: Aabb + Send + Sync> TreeOwned<T>
 where
     T::Num: Send + Sync,
 {
+    ///The parallel version of [`TreeOwned::new`].
     pub fn new_par(mut bots: Box<[T]>) -> TreeOwned<T> {
         let tree = crate::new_par(&mut bots);
 
@@ -121,11 +219,15 @@ where
         TreeOwned {
             inner,
             _bots: bots,
+            spare: None,
         }
     }
 }
 
 impl<T: Aabb> TreeOwned<T> {
+    ///Take ownership of the elements and build the tree.
+    ///
+    ///The elements are reordered into the order of the tree.
     pub fn new(mut bots: Box<[T]>) -> TreeOwned<T> {
         let tree = crate::new(&mut bots);
 
@@ -136,10 +238,65 @@ impl<T: Aabb> TreeOwned<T> {
         TreeOwned {
             inner,
             _bots: bots,
+            spare: None,
         }
     }
 }
+impl<T: Aabb + Send + Sync> TreeOwned<T>
+where
+    T::Num: Send + Sync,
+{
+    ///The parallel version of [`TreeOwned::rebuild`].
+    pub fn rebuild_par(&mut self, func: impl FnOnce(&mut [T])) {
+        let nodes = recycle_nodes(take_tree(&mut self.inner, &mut self.spare));
+        func(&mut self._bots);
+        let tree = tree_ptr(TreeBuilder::new(&mut self._bots).build_par_with_nodes(nodes));
+        put_tree(&mut self.inner, &mut self.spare, tree);
+    }
+}
+
 impl<T: Aabb> TreeOwned<T> {
+    ///Let `func` modify the elements, including their aabbs, and then rebuild the tree,
+    ///reusing the existing allocations.
+    ///
+    ///The elements are passed to `func` in the order of the tree, not the order they were
+    ///passed to [`TreeOwned::new`].
+    ///
+    /// # Examples
+    ///
+    ///```
+    /// use broccoli::{prelude::*,container::*,rect};
+    ///
+    /// let bots = vec![rect(0,10,0,10),rect(20,30,0,10)].into_boxed_slice();
+    /// let mut tree = TreeOwned::new(bots);
+    ///
+    /// //Move every element onto the first.
+    /// tree.rebuild(|bots|{
+    ///     for a in bots.iter_mut(){
+    ///         *a = rect(0,10,0,10);
+    ///     }
+    /// });
+    ///
+    /// let mut num = 0;
+    /// tree.as_tree_mut().find_colliding_pairs_mut(|_,_|num+=1);
+    /// assert_eq!(num,1);
+    ///```
+    pub fn rebuild(&mut self, func: impl FnOnce(&mut [T])) {
+        let nodes = recycle_nodes(take_tree(&mut self.inner, &mut self.spare));
+        func(&mut self._bots);
+        let tree = tree_ptr(TreeBuilder::new(&mut self._bots).build_seq_with_nodes(nodes));
+        put_tree(&mut self.inner, &mut self.spare, tree);
+    }
+
+    ///Return the elements in the order of the tree.
+    ///
+    ///The tree reorders the elements themselves, so unlike [`TreeIndOwned::into_inner`],
+    ///which returns the elements in the order they were passed to [`TreeIndOwned::new`],
+    ///the order they were passed to [`TreeOwned::new`] is lost.
+    pub fn into_inner(self) -> Box<[T]> {
+        self._bots
+    }
+
     ///Cant use Deref because of lifetime
     #[inline(always)]
     pub fn as_tree(&self) -> &Tree<T> {
@@ -152,3 +309,179 @@ impl<T: Aabb> TreeOwned<T> {
         unsafe { &mut *(&mut self.inner as *mut _ as *mut _) }
     }
 }
+
+
+/// An owned version of [`Tree`](crate::Tree) whose elements can be added and removed
+///
+/// Adding or removing elements marks the tree as out of date, and it is rebuilt
+/// the next time it is accessed. The allocations of the elements and the nodes are
+/// kept across rebuilds.
+///
+/// The query traits are not implemented on this type, since an out of date tree
+/// can not be rebuilt through `&self`. Queries go through [`TreeOwnedVec::as_tree`]
+/// or [`TreeOwnedVec::as_tree_mut`], which rebuild the tree first if needed.
+///
+/// ```rust
+/// use broccoli::{bbox,rect,prelude::*,container::*};
+///
+/// let mut tree = TreeOwnedVec::new(vec![bbox(rect(0,10,0,10),0)]);
+///
+/// let mut num = 0;
+/// tree.as_tree_mut().find_colliding_pairs_mut(|_,_|num+=1);
+/// assert_eq!(num,0);
+///
+/// tree.push(bbox(rect(5,15,5,15),1));
+/// tree.push(bbox(rect(50,60,50,60),2));
+///
+/// tree.as_tree_mut().find_colliding_pairs_mut(|_,_|num+=1);
+/// assert_eq!(num,1);
+///
+/// tree.retain(|a|a.inner!=1);
+///
+/// tree.as_tree_mut().find_colliding_pairs_mut(|_,_|num+=1);
+/// assert_eq!(num,1);
+/// ```
+pub struct TreeOwnedVec<T: Aabb> {
+    inner: TreePtr<T>,
+    _bots: Vec<T>,
+    dirty: bool,
+    spare: Option<TreePtr<T>>,
+}
+
+impl<T: Aabb + Send + Sync> TreeOwnedVec<T>
+where
+    T::Num: Send + Sync,
+{
+    ///The parallel version of [`TreeOwnedVec::new`].
+    pub fn new_par(mut bots: Vec<T>) -> TreeOwnedVec<T> {
+        let inner = tree_ptr(crate::new_par(&mut bots));
+        TreeOwnedVec {
+            inner,
+            _bots: bots,
+            dirty: false,
+            spare: None,
+        }
+    }
+
+    ///The parallel version of [`TreeOwnedVec::update`].
+    pub fn update_par(&mut self) {
+        if self.dirty {
+            let nodes = recycle_nodes(take_tree(&mut self.inner, &mut self.spare));
+            let tree = tree_ptr(TreeBuilder::new(&mut self._bots).build_par_with_nodes(nodes));
+            put_tree(&mut self.inner, &mut self.spare, tree);
+            self.dirty = false;
+        }
+    }
+}
+
+impl<T: Aabb> TreeOwnedVec<T> {
+    ///Take ownership of the elements and build the tree.
+    ///
+    ///The elements are reordered into the order of the tree.
+    ///More elements can be added later with [`TreeOwnedVec::push`].
+    pub fn new(mut bots: Vec<T>) -> TreeOwnedVec<T> {
+        let inner = tree_ptr(crate::new(&mut bots));
+        TreeOwnedVec {
+            inner,
+            _bots: bots,
+            dirty: false,
+            spare: None,
+        }
+    }
+
+    ///Rebuild the tree if elements were added or removed since it was last built.
+    ///
+    ///This is done automatically when the tree is accessed, but can be called
+    ///ahead of time to control when the work is done.
+    pub fn update(&mut self) {
+        if self.dirty {
+            let nodes = recycle_nodes(take_tree(&mut self.inner, &mut self.spare));
+            let tree = tree_ptr(TreeBuilder::new(&mut self._bots).build_seq_with_nodes(nodes));
+            put_tree(&mut self.inner, &mut self.spare, tree);
+            self.dirty = false;
+        }
+    }
+
+    ///Add an element. The tree is rebuilt the next time it is accessed.
+    pub fn push(&mut self, a: T) {
+        self.dirty = true;
+        self._bots.push(a);
+    }
+
+    ///Remove the element at `index` in [`TreeOwnedVec::get_elements`] and return it.
+    ///The last element takes its place. The tree is rebuilt the next time it is accessed.
+    ///
+    ///Panics if `index` is out of bounds.
+    pub fn swap_remove(&mut self, index: usize) -> T {
+        self.dirty = true;
+        self._bots.swap_remove(index)
+    }
+
+    ///Keep only the elements for which `func` returns true.
+    ///The tree is rebuilt the next time it is accessed.
+    pub fn retain(&mut self, func: impl FnMut(&T) -> bool) {
+        self.dirty = true;
+        self._bots.retain(func);
+    }
+
+    ///The elements in the order of the tree, or, if elements were added or removed
+    ///since the tree was last built, in the order they are stored in until the next rebuild.
+    #[must_use]
+    pub fn get_elements(&self) -> &[T] {
+        &self._bots
+    }
+
+    ///The number of elements, including those added since the tree was last built.
+    #[must_use]
+    pub fn len(&self) -> usize {
+        self._bots.len()
+    }
+
+    ///Returns true if there are no elements.
+    #[must_use]
+    pub fn is_empty(&self) -> bool {
+        self._bots.is_empty()
+    }
+
+    ///The number of elements that can be held without reallocating.
+    #[must_use]
+    pub fn capacity(&self) -> usize {
+        self._bots.capacity()
+    }
+
+    ///Return the elements in the order of [`TreeOwnedVec::get_elements`].
+    pub fn into_inner(self) -> Vec<T> {
+        self._bots
+    }
+
+    ///Cant use Deref because of lifetime.
+    ///
+    ///Unlike [`TreeOwned::as_tree`], this takes `&mut self`. If elements were added
+    ///or removed since the tree was last built, the tree is out of date and
+    ///is rebuilt first, which needs mutable access to the elements and the nodes.
+    ///
+    /// # Examples
+    ///
+    ///```
+    /// use broccoli::{prelude::*,container::*,rect};
+    ///
+    /// let mut tree = TreeOwnedVec::new(vec![rect(0,10,0,10)]);
+    /// tree.push(rect(5,15,5,15));
+    ///
+    /// //The tree is rebuilt here, so it contains both elements.
+    /// assert_eq!(tree.as_tree().get_elements().len(),2);
+    ///```
+    #[inline(always)]
+    pub fn as_tree(&mut self) -> &Tree<T> {
+        self.update();
+        unsafe { &*(&self.inner as *const _ as *const _) }
+    }
+
+    ///Cant use Deref because of lifetime.
+    ///The tree is rebuilt if it is out of date.
+    #[inline(always)]
+    pub fn as_tree_mut(&mut self) -> &mut Tree<T> {
+        self.update();
+        unsafe { &mut *(&mut self.inner as *mut _ as *mut _) }
+    }
+}
@@ -56,6 +56,70 @@ impl<T, D> CollidingPairs<T, D> {
             )
         }
     }
+
+    ///Keep only the pairs for which `func` returns true.
+    ///panics if the slice passed is not the slice used to create this
+    ///`CollidingPairs` object.
+    ///
+    /// # Examples
+    ///
+    ///```
+    /// let mut aabbs = [
+    ///     broccoli::bbox(broccoli::rect(0isize, 10, 0, 10), 0),
+    ///     broccoli::bbox(broccoli::rect(12, 20, 12, 20), 1),
+    ///     broccoli::bbox(broccoli::rect(5, 15, 5, 15), 2),
+    /// ];
+    ///
+    /// let mut base=broccoli::container::TreeIndBase::new(&mut aabbs,|a|a.rect);
+    /// let mut tree = base.build();
+    /// let mut pairs=tree.collect_colliding_pairs(|_, _| Some(()));
+    /// assert_eq!(pairs.get(&aabbs).len(),2);
+    ///
+    /// //Drop the pairs of the first element.
+    /// pairs.retain(&aabbs,|a,b,()|a.inner!=0 && b.inner!=0);
+    /// assert_eq!(pairs.get(&aabbs).len(),1);
+    ///```
+    pub fn retain(&mut self, arr: &[T], mut func: impl FnMut(&T, &T, &mut D) -> bool) {
+        assert_eq!(self.orig.0 as *const _, arr as *const _);
+        retain_pairs(&mut self.cols, |a| {
+            func(unsafe { &*a.first.0 }, unsafe { &*a.second.0 }, &mut a.extra)
+        });
+    }
+
+    ///Add the pairs of `other`, for example pairs found after the tree was rebuilt.
+    ///panics if `other` was not created from the same slice as this
+    ///`CollidingPairs` object.
+    ///
+    /// # Examples
+    ///
+    ///```
+    /// let mut aabbs = [
+    ///     broccoli::bbox(broccoli::rect(0isize, 10, 0, 10), 0),
+    ///     broccoli::bbox(broccoli::rect(12, 20, 12, 20), 1),
+    ///     broccoli::bbox(broccoli::rect(5, 15, 5, 15), 2),
+    /// ];
+    ///
+    /// let mut base=broccoli::container::TreeIndBase::new(&mut aabbs,|a|a.rect);
+    /// let mut pairs=base.build().collect_colliding_pairs(|_, _| Some(()));
+    /// let other=base.build().collect_colliding_pairs(|_, _| Some(()));
+    ///
+    /// pairs.extend_from(other);
+    /// assert_eq!(pairs.get(&aabbs).len(),4);
+    ///
+    /// //Every pair is now there twice.
+    /// pairs.dedup();
+    /// assert_eq!(pairs.get(&aabbs).len(),2);
+    ///```
+    pub fn extend_from(&mut self, other: CollidingPairs<T, D>) {
+        assert_eq!(self.orig.0, other.orig.0);
+        self.cols.extend(other.cols);
+    }
+
+    ///Remove every pair of elements that was already seen earlier in the list,
+    ///in either order, keeping the first.
+    pub fn dedup(&mut self) {
+        dedup_pairs(&mut self.cols);
+    }
 }
 
 ///CollidingPairsPar created via [`TreeInd::collect_colliding_pairs_par`]
@@ -81,6 +145,122 @@ impl<T, D> CollidingPairsPar<T, D> {
         assert_eq!(arr as *const _, self.original.0 as *const _);
         unsafe { &*(self.cols.as_slice() as *const _ as *const _) }
     }
+
+    ///Keep only the pairs for which `func` returns true.
+    ///See [`CollidingPairs::retain`].
+    pub fn retain(&mut self, arr: &[T], mut func: impl FnMut(&T, &T, &mut D) -> bool) {
+        assert_eq!(arr as *const _, self.original.0 as *const _);
+        for cols in self.cols.iter_mut() {
+            retain_pairs(cols, |a| {
+                func(unsafe { &*a.first.0 }, unsafe { &*a.second.0 }, &mut a.extra)
+            });
+        }
+        self.cols.retain(|a| !a.is_empty());
+    }
+
+    ///Add the pairs of `other`.
+    ///See [`CollidingPairs::extend_from`].
+    ///
+    ///The pairs of both are grouped again by the connected components of the
+    ///collision graph, since the groups of `other` may share elements with
+    ///the groups of `self`. A group can only be handled by one thread,
+    ///so if most of the pairs end up in one big component, for example a pile of
+    ///touching elements, most of the parallelism is lost.
+    pub fn extend_from(&mut self, other: CollidingPairsPar<T, D>) {
+        assert_eq!(self.original.0, other.original.0);
+        let cols = core::mem::take(&mut self.cols)
+            .into_iter()
+            .chain(other.cols)
+            .flatten()
+            .collect();
+        self.cols = group_by_component(self.original, cols);
+    }
+
+    ///Remove every pair of elements that was already seen earlier in its group,
+    ///in either order, keeping the first.
+    ///A pair and its duplicates are always in the same group.
+    pub fn dedup(&mut self) {
+        for cols in self.cols.iter_mut() {
+            dedup_pairs(cols);
+        }
+    }
+}
+
+fn retain_pairs<T, D>(
+    cols: &mut Vec<ColPairPtr<T, D>>,
+    mut func: impl FnMut(&mut ColPairPtr<T, D>) -> bool,
+) {
+    let old = core::mem::take(cols);
+    *cols = old
+        .into_iter()
+        .filter_map(|mut a| if func(&mut a) { Some(a) } else { None })
+        .collect();
+}
+
+//A key that is the same for both orders of a pair.
+fn pair_key<T, D>(a: &ColPairPtr<T, D>) -> (usize, usize) {
+    let (x, y) = (a.first.0 as usize, a.second.0 as usize);
+    (x.min(y), x.max(y))
+}
+
+//Remove all but the first of every pair of elements, keeping the order of the pairs.
+fn dedup_pairs<T, D>(cols: &mut Vec<ColPairPtr<T, D>>) {
+    let mut order: Vec<usize> = (0..cols.len()).collect();
+    order.sort_by_key(|&i| pair_key(&cols[i]));
+
+    let mut keep = vec![true; cols.len()];
+    for w in order.windows(2) {
+        if pair_key(&cols[w[0]]) == pair_key(&cols[w[1]]) {
+            keep[w[1]] = false;
+        }
+    }
+
+    let mut i = 0;
+    cols.retain(|_| {
+        i += 1;
+        keep[i - 1]
+    });
+}
+
+//Group the pairs by the connected components of the collision graph,
+//so that no two groups share an element.
+fn group_by_component<T, D>(
+    orig: Ptr<[T]>,
+    cols: Vec<ColPairPtr<T, D>>,
+) -> Vec<Vec<ColPairPtr<T, D>>> {
+    let index = |a: Ptr<T>| unsafe { (a.0 as *const T).offset_from(orig.0 as *const T) } as usize;
+
+    fn find(parent: &mut [usize], mut a: usize) -> usize {
+        while parent[a] != a {
+            parent[a] = parent[parent[a]];
+            a = parent[a];
+        }
+        a
+    }
+
+    let num = cols
+        .iter()
+        .map(|a| index(a.first).max(index(a.second)) + 1)
+        .max()
+        .unwrap_or(0);
+    let mut parent: Vec<usize> = (0..num).collect();
+    for a in cols.iter() {
+        let x = find(&mut parent, index(a.first));
+        let y = find(&mut parent, index(a.second));
+        parent[x] = y;
+    }
+
+    let mut group = vec![None; num];
+    let mut groups: Vec<Vec<_>> = Vec::new();
+    for a in cols {
+        let root = find(&mut parent, index(a.first));
+        let g = *group[root].get_or_insert_with(|| {
+            groups.push(Vec::new());
+            groups.len() - 1
+        });
+        groups[g].push(a);
+    }
+    groups
 }
 impl<T: Send + Sync, D: Send + Sync> CollidingPairsPar<T, D> {
     pub fn for_every_pair_mut_par(
@@ -105,6 +285,296 @@ impl<T: Send + Sync, D: Send + Sync> CollidingPairsPar<T, D> {
     }
 }
 
+///A colliding pair stored as indices into the slice of elements
+///that a [`TreeIndBase`] was created from.
+#[derive(Copy, Clone, Debug, PartialEq, Eq, Hash)]
+pub struct ColPairIndex<D> {
+    pub first: u32,
+    pub second: u32,
+    pub extra: D,
+}
+
+///CollidingPairsIndex created via [`TreeInd::collect_colliding_pairs_index`]
+///
+///Unlike [`CollidingPairs`], the pairs are stored as indices instead of pointers,
+///so they stay valid if the elements are moved, reallocated or cloned,
+///and they can be sent to other threads or remapped after elements are removed.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct CollidingPairsIndex<D> {
+    cols: Vec<ColPairIndex<D>>,
+}
+
+impl<D> From<Vec<ColPairIndex<D>>> for CollidingPairsIndex<D> {
+    fn from(cols: Vec<ColPairIndex<D>>) -> Self {
+        CollidingPairsIndex { cols }
+    }
+}
+
+impl<D> CollidingPairsIndex<D> {
+    ///Return the list of colliding pairs.
+    #[inline(always)]
+    pub fn get(&self) -> &[ColPairIndex<D>] {
+        &self.cols
+    }
+
+    ///Return the list of colliding pairs.
+    pub fn into_inner(self) -> Vec<ColPairIndex<D>> {
+        self.cols
+    }
+
+    ///Visit every colliding pair.
+    ///panics if an index is out of bounds of `arr`
+    ///or if a pair refers to the same element twice.
+    pub fn for_every_pair_mut<T>(
+        &mut self,
+        arr: &mut [T],
+        mut func: impl FnMut(&mut T, &mut T, &mut D),
+    ) {
+        for ColPairIndex {
+            first,
+            second,
+            extra,
+        } in self.cols.iter_mut()
+        {
+            let (a, b) = get_pair_mut(arr, *first, *second);
+            func(a, b, extra)
+        }
+    }
+
+    ///Change the index of every element with `func`,
+    ///removing the pairs that contain an element for which it returns `None`.
+    ///
+    /// # Examples
+    ///
+    ///```
+    /// let mut aabbs = vec![
+    ///     broccoli::bbox(broccoli::rect(0isize, 10, 0, 10), 0),
+    ///     broccoli::bbox(broccoli::rect(12, 20, 12, 20), 1),
+    ///     broccoli::bbox(broccoli::rect(5, 15, 5, 15), 2),
+    /// ];
+    ///
+    /// let mut base=broccoli::container::TreeIndBase::new(&mut aabbs,|a|a.rect);
+    /// let mut tree = base.build();
+    /// let mut pairs=tree.collect_colliding_pairs_index(|_, _| Some(()));
+    /// assert_eq!(pairs.get().len(),2);
+    ///
+    /// //Remove the first element. The last element takes its place.
+    /// let removed=0;
+    /// let last=(aabbs.len()-1) as u32;
+    /// aabbs.swap_remove(removed as usize);
+    /// pairs.remap(|i|{
+    ///     if i == removed {
+    ///         None
+    ///     } else if i == last {
+    ///         Some(removed)
+    ///     } else {
+    ///         Some(i)
+    ///     }
+    /// });
+    ///
+    /// assert_eq!(pairs.get().len(),1);
+    /// pairs.for_every_pair_mut(&mut aabbs,|a,b,()|{
+    ///     assert_eq!(a.inner+b.inner,3);
+    /// });
+    ///```
+    pub fn remap(&mut self, mut func: impl FnMut(u32) -> Option<u32>) {
+        let cols = core::mem::take(&mut self.cols);
+        self.cols = cols
+            .into_iter()
+            .filter_map(|mut a| {
+                a.first = func(a.first)?;
+                a.second = func(a.second)?;
+                Some(a)
+            })
+            .collect();
+    }
+}
+
+impl<D: Send + Sync> CollidingPairsIndex<D> {
+    ///Visit every colliding pair, passing read only references
+    ///so that the pairs can be visited in parallel.
+    ///panics if an index is out of bounds of `arr`.
+    pub fn for_every_pair_par<T: Send + Sync>(
+        &self,
+        arr: &[T],
+        func: impl Fn(&T, &T, &D) + Send + Sync,
+    ) {
+        use rayon::prelude::*;
+        self.cols.par_iter().for_each(|a| {
+            func(&arr[a.first as usize], &arr[a.second as usize], &a.extra)
+        });
+    }
+}
+
+impl<D> CollidingPairsIndex<D> {
+    ///Color the collision graph so that no two pairs of the same color share an element,
+    ///and return the pairs grouped by color in a [`ColoredPairs`].
+    ///
+    ///Pairs are colored greedily in order, so the same pairs always produce the same colors.
+    ///The number of colors is at most `2*d-1`, where `d` is the largest number of pairs
+    ///that a single element is part of.
+    ///
+    ///panics if a pair refers to the same element twice.
+    pub fn into_colored(self) -> ColoredPairs<D> {
+        let num_elements = self
+            .cols
+            .iter()
+            .map(|a| a.first.max(a.second) as usize + 1)
+            .max()
+            .unwrap_or(0);
+
+        //The color an element was last used in, offset by one so that zero means never.
+        let mut last_color = vec![0usize; num_elements];
+        let mut remaining = self.cols;
+        let mut cols = Vec::with_capacity(remaining.len());
+        let mut batches = Vec::new();
+
+        while !remaining.is_empty() {
+            let color = batches.len() + 1;
+            let mut rest = Vec::new();
+            for a in remaining.into_iter() {
+                let (first, second) = (a.first as usize, a.second as usize);
+                assert_ne!(first, second);
+                if last_color[first] != color && last_color[second] != color {
+                    last_color[first] = color;
+                    last_color[second] = color;
+                    cols.push(a);
+                } else {
+                    rest.push(a);
+                }
+            }
+            batches.push(cols.len());
+            remaining = rest;
+        }
+
+        ColoredPairs {
+            cols,
+            batches,
+            num_elements,
+        }
+    }
+}
+
+///The pairs of a [`CollidingPairsIndex`] grouped into batches of pairs that share no element,
+///created via [`CollidingPairsIndex::into_colored`].
+///
+///Every pair of a batch can be visited in parallel, one batch after the other.
+///
+/// # Examples
+///
+///```
+/// let mut aabbs = vec![
+///     broccoli::bbox(broccoli::rect(0isize, 10, 0, 10), 0),
+///     broccoli::bbox(broccoli::rect(12, 20, 12, 20), 0),
+///     broccoli::bbox(broccoli::rect(5, 15, 5, 15), 0),
+/// ];
+///
+/// let mut base=broccoli::container::TreeIndBase::new(&mut aabbs,|a|a.rect);
+/// let mut tree = base.build();
+/// let mut pairs=tree.collect_colliding_pairs_index(|_, _| Some(())).into_colored();
+///
+/// //Both pairs contain the third element, so they are in different batches.
+/// assert_eq!(pairs.num_batches(),2);
+///
+/// pairs.for_every_pair_mut_par(&mut aabbs,|a,b,()|{
+///     a.inner+=1;
+///     b.inner+=1;
+/// });
+/// assert_eq!(aabbs[2].inner,2);
+///```
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct ColoredPairs<D> {
+    cols: Vec<ColPairIndex<D>>,
+    //The end of every batch in cols.
+    batches: Vec<usize>,
+    num_elements: usize,
+}
+
+impl<D> ColoredPairs<D> {
+    ///The number of batches, which is the number of colors used.
+    #[must_use]
+    pub fn num_batches(&self) -> usize {
+        self.batches.len()
+    }
+
+    ///The pairs of every batch.
+    pub fn batches(&self) -> impl Iterator<Item = &[ColPairIndex<D>]> {
+        let cols = &self.cols;
+        let starts = core::iter::once(0).chain(self.batches.iter().copied());
+        starts
+            .zip(self.batches.iter().copied())
+            .map(move |(start, end)| &cols[start..end])
+    }
+
+    ///Return the pairs without their grouping.
+    pub fn into_inner(self) -> CollidingPairsIndex<D> {
+        CollidingPairsIndex { cols: self.cols }
+    }
+
+    ///Visit every colliding pair one batch after the other.
+    ///panics if an index is out of bounds of `arr`.
+    pub fn for_every_pair_mut<T>(
+        &mut self,
+        arr: &mut [T],
+        mut func: impl FnMut(&mut T, &mut T, &mut D),
+    ) {
+        for ColPairIndex {
+            first,
+            second,
+            extra,
+        } in self.cols.iter_mut()
+        {
+            let (a, b) = get_pair_mut(arr, *first, *second);
+            func(a, b, extra)
+        }
+    }
+}
+
+impl<D: Send + Sync> ColoredPairs<D> {
+    ///Visit every colliding pair, visiting the pairs of each batch in parallel.
+    ///panics if an index is out of bounds of `arr`.
+    pub fn for_every_pair_mut_par<T: Send + Sync>(
+        &mut self,
+        arr: &mut [T],
+        func: impl Fn(&mut T, &mut T, &mut D) + Send + Sync,
+    ) {
+        use rayon::prelude::*;
+        assert!(self.num_elements <= arr.len());
+        let base = Ptr(arr.as_mut_ptr());
+        let func = &func;
+
+        let mut start = 0;
+        for &end in self.batches.iter() {
+            self.cols[start..end].par_iter_mut().for_each(|a| {
+                //The pairs of a batch share no element and the indices are
+                //in bounds, so these are the only references to the two elements.
+                let first = unsafe { &mut *base.0.add(a.first as usize) };
+                let second = unsafe { &mut *base.0.add(a.second as usize) };
+                func(first, second, &mut a.extra)
+            });
+            start = end;
+        }
+    }
+}
+
+fn get_pair_mut<T>(arr: &mut [T], first: u32, second: u32) -> (&mut T, &mut T) {
+    let (first, second) = (first as usize, second as usize);
+    assert_ne!(first, second);
+    if first < second {
+        let (a, b) = arr.split_at_mut(second);
+        (&mut a[first], &mut b[0])
+    } else {
+        let (a, b) = arr.split_at_mut(first);
+        (&mut b[0], &mut a[second])
+    }
+}
+
+//The index of an element in the slice it belongs to.
+fn index_of<T>(orig: Ptr<[T]>, a: &T) -> u32 {
+    let i = unsafe { (a as *const T).offset_from(orig.0 as *const T) } as usize;
+    core::convert::TryFrom::try_from(i).expect("index does not fit in a u32")
+}
+
 ///Contains a filtered list of all elements in the tree from calling [`TreeInd::collect_all`].
 pub struct FilteredElements<T, D> {
     elems: Vec<(Ptr<T>, D)>,
@@ -326,6 +796,56 @@ impl<'a,'b,N:Num,T> TreeInd<'a,'b,N,T>{
     pub fn get_inner_elements(&self)->&[T]{
         unsafe{&*self.orig.0}
     }
+
+    /// Find the `k` closest other elements of every element.
+    /// Like [`KnearestQuery::knn_graph_mut`](crate::query::knearest::KnearestQuery::knn_graph_mut)
+    /// except both the outer vec and the indices it contains
+    /// refer to the order of [`TreeInd::get_inner_elements`].
+    ///
+    /// # Examples
+    ///
+    ///```
+    /// use axgeom::vec2;
+    ///
+    /// let mut aabbs = [
+    ///    broccoli::bbox(broccoli::rect(0isize, 10, 0, 10), 0),
+    ///    broccoli::bbox(broccoli::rect(15, 20, 15, 20), 1),
+    ///    broccoli::bbox(broccoli::rect(50, 60, 50, 60), 2),
+    /// ];
+    ///
+    /// let mut base=broccoli::container::TreeIndBase::new(&mut aabbs,|a|a.rect); 
+    /// let mut tree = base.build();
+    /// let mut handler = broccoli::query::knearest::default_rect_knearest(&tree);
+    ///
+    /// let graph=tree.knn_graph_mut(1,&mut handler,|a|vec2(a.rect.x.start,a.rect.y.start));
+    /// assert_eq!(graph,vec![vec![1],vec![0],vec![1]]);
+    /// ```
+    pub fn knn_graph_mut<K: crate::query::knearest::Knearest<T = BBox<N, &'a mut T>, N = N>>(
+        &mut self,
+        k: usize,
+        ktrait: &mut K,
+        point: impl FnMut(&BBox<N, &'a mut T>) -> Vec2<N>,
+    ) -> Vec<Vec<usize>> {
+        use crate::query::knearest::KnearestQuery;
+        let orig = self.orig.0 as *const T;
+        let indices: Vec<usize> = self
+            .tree
+            .get_elements()
+            .iter()
+            .map(|b| unsafe { (&*b.inner as *const T).offset_from(orig) as usize })
+            .collect();
+
+        let mut res = vec![Vec::new(); indices.len()];
+        for (i, n) in self
+            .tree
+            .knn_graph_mut(k, ktrait, point)
+            .into_iter()
+            .enumerate()
+        {
+            res[indices[i]] = n.into_iter().map(|j| indices[j]).collect();
+        }
+        res
+    }
     /// Collect all elements based off of a predicate and return a [`FilteredElements`].
     ///
     /// # Examples
@@ -435,6 +955,71 @@ impl<'a,'b,N:Num,T> TreeInd<'a,'b,N,T>{
         }
     }
 
+    /// Find all colliding pairs based on a predicate and return a [`CollidingPairsIndex`]
+    /// that refers to the elements by their index in [`TreeInd::get_inner_elements`].
+    ///
+    /// # Examples
+    ///
+    ///```
+    /// let mut aabbs = vec![
+    ///     broccoli::bbox(broccoli::rect(0isize, 10, 0, 10), 0),
+    ///     broccoli::bbox(broccoli::rect(12, 20, 12, 20), 1),
+    ///     broccoli::bbox(broccoli::rect(5, 15, 5, 15), 2),
+    /// ];
+    ///
+    /// let mut base=broccoli::container::TreeIndBase::new(&mut aabbs,|a|a.rect);
+    /// let mut tree = base.build();
+    ///
+    /// //Find all colliding aabbs only once.
+    /// let mut pairs=tree.collect_colliding_pairs_index(|_, _| Some(()));
+    ///
+    /// //The pairs are still valid after the elements are moved.
+    /// let mut aabbs=aabbs.clone();
+    /// aabbs.reserve(1000);
+    /// pairs.for_every_pair_mut(&mut aabbs,|a,b,()|{
+    ///     a.inner+=1;
+    ///     b.inner+=1;
+    /// });
+    /// assert_eq!(aabbs[2].inner,4);
+    ///```
+    pub fn collect_colliding_pairs_index<D>(
+        &mut self,
+        mut func: impl FnMut(&mut T, &mut T) -> Option<D>,
+    ) -> CollidingPairsIndex<D> {
+        let orig = self.orig;
+        let mut cols = Vec::new();
+        self.tree.find_colliding_pairs_mut(|a, b| {
+            let a = a.unpack_inner();
+            let b = b.unpack_inner();
+            if let Some(extra) = func(a, b) {
+                cols.push(ColPairIndex {
+                    first: index_of(orig, a),
+                    second: index_of(orig, b),
+                    extra,
+                });
+            }
+        });
+        CollidingPairsIndex { cols }
+    }
+
+    /// The parallel version of [`TreeInd::collect_colliding_pairs_index`].
+    pub fn collect_colliding_pairs_index_par<D: Send + Sync>(
+        &mut self,
+        func: impl Fn(&mut T, &mut T) -> Option<D> + Send + Sync + Copy,
+    ) -> CollidingPairsIndex<D> where N:Send+Sync,T:Send+Sync{
+        let orig = self.orig;
+        let cols = self.collect_colliding_pairs_par_inner(move |a, b| {
+            func(a, b).map(|extra| ColPairIndex {
+                first: index_of(orig, a),
+                second: index_of(orig, b),
+                extra,
+            })
+        });
+        CollidingPairsIndex {
+            cols: cols.into_iter().flatten().collect(),
+        }
+    }
+
     /// The parallel version of [`TreeInd::collect_colliding_pairs`] that instead
     /// returns a [`CollidingPairsPar`].
     ///
@@ -8,6 +8,10 @@ use build::TreeBuilder;
 
 pub mod container;
 
+pub mod stats;
+
+pub mod validate;
+
 
 
 
@@ -62,6 +66,10 @@ impl<'a, T: Aabb> RectQuery<'a> for Tree<'a, T> {}
 impl<'a, T: Aabb> ColfindQuery<'a> for Tree<'a, T> {}
 impl<'a, T: Aabb> RaycastQuery<'a> for Tree<'a, T> {}
 impl<'a, T: Aabb> KnearestQuery<'a> for Tree<'a, T> {}
+impl<'a, T: Aabb> MultiQuery<'a> for Tree<'a, T> {}
+impl<'a, T: Aabb> ClosestPairQuery<'a> for Tree<'a, T> {}
+impl<'a, T: Aabb> FarthestQuery<'a> for Tree<'a, T> {}
+impl<'a, T: Aabb> SegmentQuery<'a> for Tree<'a, T> {}
 
 impl<'a, T: Aabb> Queries<'a> for Tree<'a, T> {
     type T = T;
@@ -235,22 +243,8 @@ impl<'a, T: Aabb> Tree<'a, T> {
     ///```
     #[must_use]
     pub fn get_elements_mut(&mut self)->PMut<[T]>{
-
-        fn foo<'a,T:Aabb>(v:VistrMut<'a,Node<T>>)->PMut<'a,[T]>{
-            let mut new_slice = None;
-
-            v.dfs_preorder(|a| {
-                if let Some(s) = new_slice.take() {
-                    new_slice = Some(crate::pmut::combine_slice(s, a.into_range()));
-                } else {
-                    new_slice = Some(a.into_range());
-                }
-            });
-            new_slice.unwrap()
-        }
-
         let num_aabbs=self.num_aabbs;
-        let ret=foo(self.vistr_mut());
+        let ret=crate::query::tools::get_elements_mut(self.vistr_mut());
         assert_eq!(ret.len(),num_aabbs);
         ret
     }
@@ -268,22 +262,8 @@ impl<'a, T: Aabb> Tree<'a, T> {
     ///```
     #[must_use]
     pub fn get_elements(&self)->&[T]{
-
-        fn foo<'a,T:Aabb>(v:Vistr<'a,Node<T>>)->&'a [T]{
-            let mut new_slice = None;
-
-            v.dfs_preorder(|a| {
-                if let Some(s) = new_slice.take() {
-                    new_slice = Some(crate::util::combine_slice(s, &a.range));
-                } else {
-                    new_slice = Some(&a.range);
-                }
-            });
-            new_slice.unwrap()
-        }
-
         let num_aabbs=self.num_aabbs;
-        let ret=foo(self.vistr());
+        let ret=crate::query::tools::get_elements(self.vistr());
         assert_eq!(ret.len(),num_aabbs);
         ret
     }
@@ -0,0 +1,145 @@
+//! Statistics about the shape of a tree
+//!
+//! Useful for logging the quality of a tree and deciding whether to tune its height.
+
+use crate::inner_prelude::*;
+use num_traits::AsPrimitive;
+
+///Statistics about the nodes at one level of the tree.
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct LevelStats {
+    ///The number of nodes at this level.
+    pub num_nodes: usize,
+    ///The total number of elements in the nodes at this level.
+    pub num_elements: usize,
+    ///The largest number of elements in a single node at this level.
+    pub max_elements: usize,
+    ///The number of nodes at this level that have no elements.
+    pub num_empty: usize,
+    ///The average width of `cont` over the non empty nodes at this level.
+    pub average_cont_width: f64,
+    ///The largest width of `cont` at this level.
+    pub max_cont_width: f64,
+}
+
+///Statistics about a whole tree, as returned by [`Tree::stats`].
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct TreeStats {
+    ///The number of elements in every node in pre-order.
+    pub elements_per_node: Vec<usize>,
+    ///Statistics for every level, starting at the root.
+    pub levels: Vec<LevelStats>,
+    ///The number of elements that are in non leaf nodes because they straddle a divider.
+    pub num_non_leaf_elements: usize,
+    ///The number of leaves that have no elements.
+    pub num_empty_leaves: usize,
+    ///The average of `min(left,right)/max(left,right)` over the non leaf nodes,
+    ///where `left` and `right` are the number of elements in each subtree.
+    ///Nodes without any elements in either subtree are skipped.
+    ///
+    ///A perfectly balanced tree has a balance of one.
+    pub balance: f64,
+}
+
+impl TreeStats {
+    ///The number of leaves.
+    #[must_use]
+    pub fn num_leaves(&self) -> usize {
+        self.levels.last().map(|a| a.num_nodes).unwrap_or(0)
+    }
+}
+
+struct Acc {
+    stats: TreeStats,
+    balance_sum: f64,
+    balance_count: usize,
+}
+
+fn recc<T: Aabb>(acc: &mut Acc, vistr: Vistr<Node<T>>, depth: usize) -> usize
+where
+    T::Num: AsPrimitive<f64>,
+{
+    let (nn, rest) = vistr.next();
+    let num = nn.range.len();
+    acc.stats.elements_per_node.push(num);
+
+    let level = &mut acc.stats.levels[depth];
+    level.num_nodes += 1;
+    level.num_elements += num;
+    level.max_elements = level.max_elements.max(num);
+    if num == 0 {
+        level.num_empty += 1;
+    } else {
+        let width = nn.cont.end.as_() - nn.cont.start.as_();
+        level.average_cont_width += width;
+        level.max_cont_width = level.max_cont_width.max(width);
+    }
+
+    match rest {
+        Some([left, right]) => {
+            acc.stats.num_non_leaf_elements += num;
+            let l = recc(acc, left, depth + 1);
+            let r = recc(acc, right, depth + 1);
+            if l + r > 0 {
+                acc.balance_sum += l.min(r) as f64 / l.max(r) as f64;
+                acc.balance_count += 1;
+            }
+            num + l + r
+        }
+        None => {
+            if num == 0 {
+                acc.stats.num_empty_leaves += 1;
+            }
+            num
+        }
+    }
+}
+
+impl<'a, T: Aabb> Tree<'a, T>
+where
+    T::Num: AsPrimitive<f64>,
+{
+    ///Compute statistics about the shape of the tree.
+    ///
+    /// # Examples
+    ///
+    ///```
+    /// let mut bots = [axgeom::rect(0,10,0,10);100];
+    /// let tree = broccoli::new(&mut bots);
+    /// let stats = tree.stats();
+    ///
+    /// assert_eq!(stats.levels.len(),tree.get_height());
+    /// assert_eq!(stats.elements_per_node.len(),tree.num_nodes());
+    /// assert_eq!(stats.elements_per_node.iter().sum::<usize>(),100);
+    ///```
+    #[must_use]
+    pub fn stats(&self) -> TreeStats {
+        let height = self.get_height();
+        let mut acc = Acc {
+            stats: TreeStats {
+                elements_per_node: Vec::with_capacity(self.num_nodes()),
+                levels: (0..height).map(|_| LevelStats::default()).collect(),
+                ..TreeStats::default()
+            },
+            balance_sum: 0.0,
+            balance_count: 0,
+        };
+
+        recc(&mut acc, self.vistr(), 0);
+
+        for level in acc.stats.levels.iter_mut() {
+            let non_empty = level.num_nodes - level.num_empty;
+            if non_empty > 0 {
+                level.average_cont_width /= non_empty as f64;
+            }
+        }
+
+        acc.stats.balance = if acc.balance_count == 0 {
+            1.0
+        } else {
+            acc.balance_sum / acc.balance_count as f64
+        };
+
+        acc.stats
+    }
+}
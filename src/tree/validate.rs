@@ -0,0 +1,224 @@
+//! Check the invariants of a tree
+//!
+//! Unlike [`crate::query::assert_tree_invariants`], the checks here return an error
+//! instead of panicking, so they can be used in release builds, for example after
+//! rebuilding a tree with [`Tree::from_raw_parts`].
+
+use crate::inner_prelude::*;
+use core::fmt;
+
+///An invariant of the tree.
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub enum TreeInvariant {
+    ///The elements of a node are sorted by the start of their range along the
+    ///axis that the children of the node are divided on.
+    Sorted,
+    ///The `cont` of a node is the extent of its elements along the axis it is divided on,
+    ///or the default range if the node has no elements.
+    Cont,
+    ///The elements of a non leaf node touch its divider,
+    ///and the divider is the start of one of them.
+    OnDivider,
+    ///The elements of a subtree are on the same side of the dividers of
+    ///the ancestors of the subtree as the subtree itself.
+    SideOfDivider,
+    ///The divider of a non leaf node is `None` if and only if its subtree has no elements.
+    ///The divider of a leaf is always `None`.
+    Div,
+    ///The `bounds` of a node is the bounding rect of the elements of its subtree.
+    Bounds,
+    ///The tree holds the number of elements it claims to have.
+    NumAabbs,
+}
+
+impl fmt::Display for TreeInvariant {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        let s = match self {
+            TreeInvariant::Sorted => "elements are not sorted",
+            TreeInvariant::Cont => "cont is not the extent of the elements",
+            TreeInvariant::OnDivider => "elements do not lie on the divider",
+            TreeInvariant::SideOfDivider => "element on the wrong side of a divider",
+            TreeInvariant::Div => "div does not match whether the subtree is empty",
+            TreeInvariant::Bounds => "bounds is not the bounding rect of the subtree",
+            TreeInvariant::NumAabbs => "wrong number of elements",
+        };
+        f.write_str(s)
+    }
+}
+
+///A broken invariant of a tree, as returned by [`Tree::validate`].
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub struct TreeInvariantError {
+    ///The index of the node in pre-order, as in [`Tree::get_nodes`].
+    pub node: usize,
+    ///The depth of the node, where the root has a depth of zero.
+    pub depth: usize,
+    ///The invariant that was broken.
+    pub property: TreeInvariant,
+}
+
+impl fmt::Display for TreeInvariantError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(
+            f,
+            "{} at node {} (depth {})",
+            self.property, self.node, self.depth
+        )
+    }
+}
+
+//The dividers of the ancestors of a subtree along one axis.
+//Elements must end before `hi` and start after `lo`.
+#[derive(Copy, Clone)]
+struct Limits<N> {
+    lo: Option<N>,
+    hi: Option<N>,
+}
+
+impl<N: Num> Limits<N> {
+    fn contains(&self, r: &Range<N>) -> bool {
+        self.lo.map(|lo| r.start > lo).unwrap_or(true)
+            && self.hi.map(|hi| r.end < hi).unwrap_or(true)
+    }
+}
+
+//The number of elements in a subtree and their bounding rect.
+type Subtree<N> = (usize, Option<Rect<N>>);
+
+struct Checker {
+    index: usize,
+}
+
+impl Checker {
+    fn recc<A: Axis, T: Aabb>(
+        &mut self,
+        axis: A,
+        vistr: Vistr<Node<T>>,
+        depth: usize,
+        limits: [Limits<T::Num>; 2],
+    ) -> Result<Subtree<T::Num>, TreeInvariantError> {
+        let index = self.index;
+        self.index += 1;
+        let err = |property| TreeInvariantError {
+            node: index,
+            depth,
+            property,
+        };
+
+        let (nn, rest) = vistr.next();
+        let axis_next = axis.next();
+
+        let sorted = nn.range.windows(2).all(|w| {
+            w[0].get().get_range(axis_next).start <= w[1].get().get_range(axis_next).start
+        });
+        if !sorted {
+            return Err(err(TreeInvariant::Sorted));
+        }
+
+        let mut bounds: Option<Rect<T::Num>> = None;
+        for a in nn.range.iter() {
+            let r = a.get();
+            if !limits[0].contains(&r.x) || !limits[1].contains(&r.y) {
+                return Err(err(TreeInvariant::SideOfDivider));
+            }
+            match &mut bounds {
+                Some(b) => {
+                    crate::query::tools::grow_range(&mut b.x, &r.x);
+                    crate::query::tools::grow_range(&mut b.y, &r.y);
+                }
+                None => bounds = Some(*r),
+            }
+        }
+
+        let cont = match &bounds {
+            Some(b) => *b.get_range(axis),
+            None => Range {
+                start: Default::default(),
+                end: Default::default(),
+            },
+        };
+        if nn.cont != cont {
+            return Err(err(TreeInvariant::Cont));
+        }
+
+        let mut num = nn.range.len();
+        match rest {
+            Some([left, right]) => {
+                if let Some(div) = nn.div {
+                    let on_divider = nn
+                        .range
+                        .iter()
+                        .all(|a| a.get().get_range(axis).contains(div));
+                    let from_element = nn
+                        .range
+                        .iter()
+                        .any(|a| a.get().get_range(axis).start == div);
+                    if !on_divider || !from_element {
+                        return Err(err(TreeInvariant::OnDivider));
+                    }
+                }
+
+                let index_axis = if axis.is_xaxis() { 0 } else { 1 };
+                let (mut llimits, mut rlimits) = (limits, limits);
+                if let Some(div) = nn.div {
+                    llimits[index_axis].hi = Some(div);
+                    rlimits[index_axis].lo = Some(div);
+                }
+
+                let (lnum, lbounds) = self.recc(axis_next, left, depth + 1, llimits)?;
+                let (rnum, rbounds) = self.recc(axis_next, right, depth + 1, rlimits)?;
+                num += lnum + rnum;
+
+                for b in lbounds.iter().chain(rbounds.iter()) {
+                    match &mut bounds {
+                        Some(a) => {
+                            crate::query::tools::grow_range(&mut a.x, &b.x);
+                            crate::query::tools::grow_range(&mut a.y, &b.y);
+                        }
+                        None => bounds = Some(*b),
+                    }
+                }
+
+                if nn.div.is_some() != (num > 0) {
+                    return Err(err(TreeInvariant::Div));
+                }
+            }
+            None => {
+                if nn.div.is_some() {
+                    return Err(err(TreeInvariant::Div));
+                }
+            }
+        }
+
+        if nn.bounds != bounds {
+            return Err(err(TreeInvariant::Bounds));
+        }
+
+        Ok((num, bounds))
+    }
+}
+
+impl<'a, T: Aabb> Tree<'a, T> {
+    ///Check every invariant of the tree, returning the first one that is broken.
+    ///
+    /// # Examples
+    ///
+    ///```
+    /// let mut bots = [axgeom::rect(0,10,0,10),axgeom::rect(20,30,5,15)];
+    /// let tree = broccoli::new(&mut bots);
+    /// assert!(tree.validate().is_ok());
+    ///```
+    pub fn validate(&self) -> Result<(), TreeInvariantError> {
+        let none = Limits { lo: None, hi: None };
+        let mut checker = Checker { index: 0 };
+        let (num, _) = checker.recc(default_axis(), self.vistr(), 0, [none, none])?;
+        if num != self.num_aabbs() {
+            return Err(TreeInvariantError {
+                node: 0,
+                depth: 0,
+                property: TreeInvariant::NumAabbs,
+            });
+        }
+        Ok(())
+    }
+}
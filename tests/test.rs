@@ -18,6 +18,50 @@ fn create_bbox_mut<'a, N: Num, T>(
         .collect()
 }
 
+///Convenience function to create the rect of the `i`th element of a test set.
+///The rects are spread over a small area, so many of them overlap.
+fn create_rect(i: isize) -> Rect<isize> {
+    let x = (i * 37) % 301;
+    let y = (i * 13) % 253;
+    rect(x, x + 5 + i % 7, y, y + 3 + i % 5)
+}
+
+///Test sets that are hard on the pruning of queries.
+///
+///* every element is the same, so every divider goes through every element.
+///* points and thin rects on a small lattice, so dividers land exactly on element edges.
+///* most elements share one rect on the right, so the subtrees right of it are empty.
+fn create_edge_case_rects() -> Vec<Vec<Rect<isize>>> {
+    let same = vec![rect(10, 20, 10, 20); 500];
+
+    let lattice = (0..500isize)
+        .map(|i| {
+            let x = i % 23;
+            let y = (i / 23) % 19;
+            rect(x, x + i % 2, y, y + i % 3 % 2)
+        })
+        .collect();
+
+    let right_empty = (0..500isize)
+        .map(|i| {
+            if i % 5 == 0 {
+                rect(i % 40, i % 40 + 3, i % 17, i % 17 + 3)
+            } else {
+                rect(100, 110, 0, 20)
+            }
+        })
+        .collect();
+
+    vec![same, lattice, right_empty]
+}
+
+///The dividers of every node of the tree, so that queries can be placed exactly on them.
+fn dividers<T: Aabb>(tree: &broccoli::Tree<T>) -> Vec<T::Num> {
+    let mut divs = Vec::new();
+    tree.vistr().dfs_preorder(|n| divs.extend(n.div));
+    divs
+}
+
 #[test]
 fn test_tie_knearest() {
     use broccoli::*;
@@ -43,6 +87,557 @@ fn test_tie_knearest() {
     broccoli::query::knearest::assert_k_nearest_mut(&mut tree, vec2(15, 30), 2, handler);
 }
 
+#[test]
+fn test_knearest_filter() {
+    use broccoli::*;
+
+    let mut bots: Vec<_> = (0..100isize)
+        .map(|i| bbox(rect(i * 3, i * 3 + 2, i, i + 5), i % 3 == 0))
+        .collect();
+
+    let mut tree = broccoli::new(&mut bots);
+
+    let handler = &mut broccoli::query::knearest::default_rect_knearest(&tree);
+    let mut res = tree.k_nearest_filter_mut(vec2(40, 20), 3, handler, |a| a.inner, Some(30 * 30));
+    assert_eq!(res.len(), 3);
+    for group in res.iter() {
+        for a in group.iter() {
+            assert!(a.bot.inner);
+            assert!(a.mag <= 30 * 30);
+        }
+    }
+
+    for &max in [None, Some(0), Some(10 * 10), Some(100 * 100)].iter() {
+        let handler = &mut broccoli::query::knearest::default_rect_knearest(&tree);
+        broccoli::query::knearest::assert_k_nearest_filter_mut(
+            &mut tree,
+            vec2(40, 20),
+            3,
+            handler,
+            |a| a.inner,
+            max,
+        );
+    }
+}
+
+#[test]
+fn test_knn_graph() {
+    let mut bots: Vec<_> = (0..200isize).map(create_rect).collect();
+
+    let mut tree = broccoli::new(&mut bots);
+    let mut handler = broccoli::query::knearest::default_rect_knearest(&tree);
+    let point = |a: &Rect<isize>| vec2(a.x.start, a.y.start);
+
+    for &k in [0, 1, 3, 10].iter() {
+        broccoli::query::knearest::assert_knn_graph_mut(&mut tree, k, &mut handler, point);
+    }
+
+    let graph = tree.knn_graph_mut(3, &mut handler, point);
+    assert_eq!(graph.len(), 200);
+    for (i, n) in graph.iter().enumerate() {
+        assert_eq!(n.len(), 3);
+        assert!(!n.contains(&i));
+    }
+}
+
+#[test]
+fn test_closest_pair_and_farthest() {
+    use broccoli::*;
+
+    let mut bots: Vec<_> = (0..200isize).map(|i| bbox(create_rect(i), i)).collect();
+
+    let mut tree = broccoli::new(&mut bots);
+
+    let handler = &mut broccoli::query::closest_pair::default_rect_closest_pair(&tree);
+    broccoli::query::closest_pair::assert_closest_pair_mut(&mut tree, handler);
+
+    for &p in [vec2(0, 0), vec2(50, 25), vec2(-100, 300)].iter() {
+        let handler = &mut broccoli::query::farthest::default_rect_farthest(&tree);
+        broccoli::query::farthest::assert_farthest_mut(&mut tree, p, handler);
+    }
+
+    let mut bots: [BBox<isize, ()>; 1] = [bbox(rect(0, 10, 0, 10), ())];
+    let mut tree = broccoli::new(&mut bots);
+    let handler = &mut broccoli::query::closest_pair::default_rect_closest_pair(&tree);
+    assert!(tree.closest_pair_mut(handler).is_none());
+}
+
+#[test]
+fn test_containing_point() {
+    use broccoli::*;
+
+    let mut bots: Vec<_> = (0..200isize).map(|i| bbox(create_rect(i), ())).collect();
+
+    let mut tree = broccoli::new(&mut bots);
+
+    for x in (-5..110).step_by(3) {
+        for y in (-5..60).step_by(2) {
+            broccoli::query::rect::assert_for_all_containing_point_mut(&mut tree, vec2(x, y));
+
+            let mut a = 0;
+            tree.for_all_containing_point(vec2(x, y), |_| a += 1);
+            let mut b = 0;
+            tree.for_all_intersect_rect(&rect(x, x, y, y), |r| {
+                if r.get().contains_point(vec2(x, y)) {
+                    b += 1
+                }
+            });
+            assert_eq!(a, b);
+        }
+    }
+}
+
+#[test]
+fn test_containing_point_edge_cases() {
+    use broccoli::*;
+
+    for rects in create_edge_case_rects() {
+        let mut bots: Vec<_> = rects.into_iter().map(|r| bbox(r, ())).collect();
+        let mut tree = broccoli::new(&mut bots);
+
+        let mut points: Vec<_> = tree
+            .get_elements()
+            .iter()
+            .flat_map(|a| {
+                vec![
+                    vec2(a.rect.x.start, a.rect.y.start),
+                    vec2(a.rect.x.end, a.rect.y.end),
+                ]
+            })
+            .collect();
+        for d in dividers(&tree) {
+            points.push(vec2(d, d));
+            points.push(vec2(d, 15));
+            points.push(vec2(15, d));
+        }
+
+        for p in points {
+            broccoli::query::rect::assert_for_all_containing_point_mut(&mut tree, p);
+        }
+    }
+}
+
+#[test]
+fn test_convex() {
+    use broccoli::query::convex::{ConvexRegion, HalfPlane};
+    use broccoli::*;
+
+    let mut bots: Vec<_> = (0..500isize).map(|i| bbox(create_rect(i), ())).collect();
+
+    let mut tree = broccoli::new(&mut bots);
+
+    let regions = [
+        ConvexRegion::from_points(&[vec2(0, 0), vec2(100, 20), vec2(60, 90)]),
+        ConvexRegion::from_points(&[
+            vec2(150, 100),
+            vec2(250, 100),
+            vec2(250, 200),
+            vec2(150, 200),
+        ]),
+        ConvexRegion::from_points(&[vec2(40, 40)]),
+        //A cone pointing to the right.
+        ConvexRegion::from_half_planes(vec![
+            HalfPlane {
+                normal: vec2(-1, 2),
+                offset: 100,
+            },
+            HalfPlane {
+                normal: vec2(-1, -2),
+                offset: -100,
+            },
+        ]),
+    ];
+
+    for region in regions.iter() {
+        broccoli::query::convex::assert_for_all_intersect_convex_mut(&mut tree, region);
+
+        let mut a = 0;
+        tree.for_all_intersect_convex(region, |_| a += 1);
+
+        let b = std::sync::atomic::AtomicUsize::new(0);
+        tree.for_all_intersect_convex_par(region, |_| {
+            b.fetch_add(1, std::sync::atomic::Ordering::Relaxed);
+        });
+        assert_eq!(a, b.into_inner());
+    }
+}
+
+#[test]
+fn test_segment() {
+    use broccoli::query::segment::Segment;
+    use broccoli::*;
+
+    let mut bots: Vec<_> = (0..500isize).map(|i| bbox(create_rect(i), ())).collect();
+
+    let mut tree = broccoli::new(&mut bots);
+
+    let segs = [
+        Segment {
+            a: vec2(-10, -10),
+            b: vec2(300, 250),
+        },
+        Segment {
+            a: vec2(150, 0),
+            b: vec2(150, 250),
+        },
+        Segment {
+            a: vec2(0, 100),
+            b: vec2(300, 100),
+        },
+        Segment {
+            a: vec2(200, 30),
+            b: vec2(20, 200),
+        },
+        Segment {
+            a: vec2(40, 40),
+            b: vec2(40, 40),
+        },
+    ];
+
+    for seg in segs.iter() {
+        let handler = &mut broccoli::query::segment::default_rect_segment(&tree);
+        broccoli::query::segment::assert_for_all_intersect_segment_mut(&mut tree, seg, handler);
+
+        let mut a = 0;
+        tree.for_all_intersect_segment(seg, handler, |_| a += 1);
+        let mut b = 0;
+        tree.for_all_intersect_rect(&seg.bounding_rect(), |r| {
+            if seg.intersects_rect(r.get()) {
+                b += 1;
+            }
+        });
+        assert_eq!(a, b);
+    }
+}
+
+#[cfg(feature = "svg")]
+#[test]
+fn test_svg() {
+    use broccoli::query::svg::Svg;
+    use broccoli::*;
+
+    let mut bots: Vec<_> = (0..200isize).map(create_rect).collect();
+
+    let tree = broccoli::new(&mut bots);
+
+    let svg = Svg::new(rect(0, 310, 0, 260))
+        .with_rect(rect(10, 50, 10, 50))
+        .with_ray(axgeom::Ray {
+            point: vec2(0, 0),
+            dir: vec2(1, 1),
+        })
+        .render(&tree);
+
+    let num_dividers = tree.get_nodes().iter().filter(|a| a.div.is_some()).count();
+    assert_eq!(svg.matches("<line").count(), num_dividers + 1);
+    assert_eq!(svg.matches("<circle").count(), 1);
+    assert!(svg.matches("<rect").count() >= 200 + 2);
+
+    let svg = Svg::new(rect(0, 310, 0, 260))
+        .with_elements(false)
+        .render(&tree);
+    assert!(svg.matches("<rect").count() < 200);
+}
+
+///Checks the bounds of every subtree against the elements in it,
+///and returns the bounds of the whole tree.
+fn check_subtree_bounds<T: Aabb<Num = isize>>(vistr: Vistr<Node<T>>) -> Option<Rect<isize>> {
+    let (nn, rest) = vistr.next();
+    let mut elems: Vec<Rect<isize>> = nn.range.iter().map(|a| *a.get()).collect();
+    if let Some([left, right]) = rest {
+        elems.extend(check_subtree_bounds(left));
+        elems.extend(check_subtree_bounds(right));
+    }
+    let expected = elems.split_first().map(|(first, rest)| {
+        let mut r = *first;
+        for a in rest.iter() {
+            r.x.start = r.x.start.min(a.x.start);
+            r.x.end = r.x.end.max(a.x.end);
+            r.y.start = r.y.start.min(a.y.start);
+            r.y.end = r.y.end.max(a.y.end);
+        }
+        r
+    });
+    assert_eq!(nn.bounds(), expected);
+    expected
+}
+
+#[test]
+fn test_subtree_bounds() {
+    use broccoli::*;
+
+    let mut bots: Vec<_> = (0..1000isize).map(|i| bbox(create_rect(i), ())).collect();
+
+    let mut tree = broccoli::new(&mut bots);
+
+    let all = check_subtree_bounds(tree.vistr()).unwrap();
+    assert_eq!(tree.get_nodes()[0].bounds(), Some(all));
+
+    //Queries that prune using the bounds.
+    for &(x, y) in [(-20, -20), (0, 250), (150, 100), (320, 40)].iter() {
+        let r = rect(x, x + 15, y, y + 10);
+        broccoli::query::rect::assert_for_all_intersect_rect_mut(&mut tree, &r);
+        broccoli::query::rect::assert_for_all_in_rect_mut(&mut tree, &r);
+
+        let handler = &mut broccoli::query::knearest::default_rect_knearest(&tree);
+        broccoli::query::knearest::assert_k_nearest_mut(&mut tree, vec2(x, y), 3, handler);
+
+        let ray = axgeom::Ray {
+            point: vec2(x, y),
+            dir: vec2(1, 2),
+        };
+        let handler = &mut broccoli::query::raycast::default_rect_raycast(&tree);
+        broccoli::query::raycast::assert_raycast(&mut tree, ray, handler);
+    }
+}
+
+#[test]
+fn test_subtree_bounds_edge_cases() {
+    use broccoli::*;
+
+    let mut num_empty = 0;
+    for rects in create_edge_case_rects() {
+        let mut bots: Vec<_> = rects
+            .into_iter()
+            .enumerate()
+            .map(|(i, r)| bbox(r, i))
+            .collect();
+        let mut tree = broccoli::new(&mut bots);
+
+        check_subtree_bounds(tree.vistr());
+        num_empty += tree
+            .get_nodes()
+            .iter()
+            .filter(|n| n.bounds().is_none())
+            .count();
+
+        //Queries that prune using the bounds, placed exactly on the dividers.
+        for d in dividers(&tree) {
+            for &(x, y) in [(d, d), (d, 15), (15, d), (d - 10, d - 10)].iter() {
+                let r = rect(x, x + 10, y, y + 10);
+                broccoli::query::rect::assert_for_all_intersect_rect_mut(&mut tree, &r);
+                broccoli::query::rect::assert_for_all_in_rect_mut(&mut tree, &r);
+
+                //The edge cases are full of ties, so only the check that allows them applies.
+                #[cfg(feature = "testing")]
+                {
+                    let handler = &mut broccoli::query::knearest::default_rect_knearest(&tree);
+                    broccoli::testing::check_k_nearest(&mut tree, vec2(x, y), 3, handler);
+                }
+
+                let ray = axgeom::Ray {
+                    point: vec2(x, y),
+                    dir: vec2(1, 2),
+                };
+                let handler = &mut broccoli::query::raycast::default_rect_raycast(&tree);
+                broccoli::query::raycast::assert_raycast(&mut tree, ray, handler);
+            }
+        }
+    }
+
+    //The sets have to include empty subtrees for the test to cover them.
+    assert!(num_empty > 0);
+}
+
+#[test]
+fn test_aggregate() {
+    use broccoli::query::aggregate;
+    use broccoli::*;
+
+    let mut bots: Vec<_> = (0..500isize)
+        .map(|i| bbox(create_rect(i), 0usize))
+        .collect();
+
+    let mut tree = broccoli::new(&mut bots);
+
+    fn grow(a: &mut Rect<isize>, b: &Rect<isize>) {
+        a.x.start = a.x.start.min(b.x.start);
+        a.x.end = a.x.end.max(b.x.end);
+        a.y.start = a.y.start.min(b.y.start);
+        a.y.end = a.y.end.max(b.y.end);
+    }
+
+    //The number of elements and their bounding rect.
+    let mut handler = aggregate::from_closure(
+        &tree,
+        (),
+        |_, elems| {
+            let mut r: Option<Rect<isize>> = None;
+            for a in elems.iter() {
+                r = Some(match r {
+                    Some(mut r) => {
+                        grow(&mut r, &a.rect);
+                        r
+                    }
+                    None => a.rect,
+                });
+            }
+            (elems.len(), r)
+        },
+        |_, a, b| {
+            let r = match (a.1, b.1) {
+                (Some(mut x), Some(y)) => {
+                    grow(&mut x, &y);
+                    Some(x)
+                }
+                (x, None) => x,
+                (None, y) => y,
+            };
+            (a.0 + b.0, r)
+        },
+    );
+
+    let aggs = aggregate::build(&tree, &mut handler);
+    assert_eq!(aggs.root().0, 500);
+    assert_eq!(aggs.get().len(), tree.num_nodes());
+
+    let query = rect(100, 150, 60, 90);
+    let mut visited = 0;
+    let mut found = Vec::new();
+    aggs.for_all_pruned_mut(
+        &mut tree,
+        |(_, r)| match r {
+            Some(r) => r.intersects_rect(&query),
+            None => false,
+        },
+        |a| {
+            visited += 1;
+            if a.rect.intersects_rect(&query) {
+                found.push(a.rect);
+                *a.unpack_inner() += 1;
+            }
+        },
+    );
+    assert!(visited < 500);
+
+    let mut expected: Vec<_> = tree
+        .get_elements_mut()
+        .iter()
+        .filter(|a| a.rect.intersects_rect(&query))
+        .map(|a| a.rect)
+        .collect();
+    found.sort_by_key(|a| (a.x.start, a.y.start));
+    expected.sort_by_key(|a| (a.x.start, a.y.start));
+    assert_eq!(found, expected);
+}
+
+#[test]
+fn test_nbody_builder() {
+    use broccoli::pmut::PMut;
+    use broccoli::query::nbody;
+    use broccoli::*;
+
+    #[derive(Copy, Clone, Debug)]
+    struct Body {
+        id: usize,
+        pos: Vec2<f64>,
+        mass: f64,
+        force: Vec2<f64>,
+    }
+
+    fn check<F: Fn(Vec2<f64>, f64, f64) -> Vec2<f64> + Clone + Send + Sync>(
+        bodies: &[Body],
+        builder: nbody::NbodyBuilder<f64, F>,
+        mode: usize,
+        radius: f64,
+        tol: f64,
+    ) {
+        //wide aabbs straddle the dividers while their bodies can lie far from them.
+        let create = || -> Vec<_> {
+            bodies
+                .iter()
+                .map(|b| {
+                    let r = if b.id % 2 == 0 { radius } else { 0.0 };
+                    bbox(rect(b.pos.x - r, b.pos.x + r, b.pos.y - r, b.pos.y + r), *b)
+                })
+                .collect()
+        };
+        let body = |a: &BBox<f64, Body>| (a.inner.pos, a.inner.mass);
+        let apply = |a: PMut<BBox<f64, Body>>, f: Vec2<f64>| {
+            let b = a.unpack_inner();
+            b.force = vec2(b.force.x + f.x, b.force.y + f.y);
+        };
+
+        let mut naive = create();
+        builder
+            .clone()
+            .naive_mut(PMut::new(naive.as_mut_slice()), body, apply);
+
+        let mut bots = create();
+        let mut tree = broccoli::new(&mut bots);
+        let mut masses = vec![Default::default(); tree.num_nodes()];
+        match mode {
+            0 => {
+                builder.nbody_mut(tree, body, apply);
+            }
+            1 => {
+                builder.nbody_mut_par(tree, body, apply);
+            }
+            2 => builder.nbody_with_masses_mut(&mut tree, &mut masses, body, apply),
+            _ => builder.nbody_with_masses_mut_par(&mut tree, &mut masses, body, apply),
+        }
+        //the tree reorders the elements.
+        bots.sort_by_key(|a| a.inner.id);
+
+        let mut max = 0.0f64;
+        let mut err = 0.0f64;
+        for (a, b) in naive.iter().zip(bots.iter()) {
+            let f = a.inner.force;
+            let g = b.inner.force;
+            max = max.max((f.x * f.x + f.y * f.y).sqrt());
+            err = err.max(((f.x - g.x).powi(2) + (f.y - g.y).powi(2)).sqrt());
+        }
+        assert!(
+            err <= max * tol,
+            "error {} too large relative to {} mode {} radius {} tol {}",
+            err,
+            max,
+            mode,
+            radius,
+            tol
+        );
+    }
+
+    let bodies: Vec<_> = (0..2000)
+        .map(|i| {
+            let x = ((i * 37) % 1009) as f64;
+            let y = ((i * 61) % 997) as f64;
+            let mass = if i % 3 == 0 {
+                -1.0
+            } else {
+                1.0 + (i % 5) as f64
+            };
+            Body {
+                id: i,
+                pos: vec2(x, y),
+                mass,
+                force: vec2(0.0, 0.0),
+            }
+        })
+        .collect();
+
+    for mode in 0..4 {
+        for &radius in [0.0, 300.0].iter() {
+            check(
+                &bodies,
+                nbody::gravity(1.0, 1.0).with_theta(0.0),
+                mode,
+                radius,
+                1e-9,
+            );
+            check(&bodies, nbody::gravity(1.0, 1.0), mode, radius, 0.05);
+            check(
+                &bodies,
+                nbody::coulomb(1.0, 1.0).with_theta(0.0),
+                mode,
+                radius,
+                1e-9,
+            );
+            check(&bodies, nbody::coulomb(1.0, 1.0), mode, radius, 0.05);
+        }
+    }
+}
+
 #[test]
 fn test_tie_raycast() {
     use broccoli::*;
@@ -167,3 +762,874 @@ fn test_send_sync_tree() {
     let (p1, p2) = (&t1, &t2);
     rayon::join(|| p1, || p2);
 }
+
+#[test]
+fn test_stats() {
+    use broccoli::*;
+
+    let mut bots: Vec<_> = (0..1000isize).map(|i| bbox(create_rect(i), ())).collect();
+
+    let tree = broccoli::new(&mut bots);
+    let stats = tree.stats();
+
+    let nodes = tree.get_nodes();
+    assert_eq!(stats.levels.len(), tree.get_height());
+    assert_eq!(
+        stats.elements_per_node,
+        nodes.iter().map(|a| a.range.len()).collect::<Vec<_>>()
+    );
+    assert_eq!(
+        stats.levels.iter().map(|a| a.num_elements).sum::<usize>(),
+        1000
+    );
+    for (i, level) in stats.levels.iter().enumerate() {
+        assert_eq!(level.num_nodes, 1 << i);
+        assert!(level.average_cont_width <= level.max_cont_width);
+    }
+    assert_eq!(stats.num_leaves(), 1 << (tree.get_height() - 1));
+
+    let non_leaf = nodes
+        .iter()
+        .filter(|a| a.div.is_some())
+        .map(|a| a.range.len())
+        .sum::<usize>();
+    assert_eq!(stats.num_non_leaf_elements, non_leaf);
+    assert_eq!(
+        stats.num_empty_leaves,
+        stats.levels.last().unwrap().num_empty
+    );
+    assert!(stats.balance > 0.5 && stats.balance <= 1.0);
+}
+
+#[test]
+fn test_validate() {
+    use broccoli::validate::{TreeInvariant, TreeInvariantError};
+    use broccoli::*;
+
+    let mut bots: Vec<_> = (0..1000isize).map(|i| bbox(create_rect(i), ())).collect();
+
+    let tree = broccoli::new(&mut bots);
+    assert_eq!(tree.validate(), Ok(()));
+
+    //Break one node, check that it is reported, and then restore it.
+    let num_aabbs = tree.num_aabbs();
+    let mut inner = tree.into_inner();
+
+    let index = inner
+        .get_nodes()
+        .iter()
+        .position(|a| a.div.is_some() && !a.range.is_empty())
+        .unwrap();
+
+    let nodes = inner.get_nodes_mut();
+    let div = nodes[index].div.take();
+    let tree = unsafe { Tree::from_raw_parts(inner, num_aabbs) };
+    let e = tree.validate().unwrap_err();
+    assert_eq!(e.node, index);
+    assert!(e.property == TreeInvariant::Div || e.property == TreeInvariant::OnDivider);
+
+    let mut inner = tree.into_inner();
+    let nodes = inner.get_nodes_mut();
+    nodes[index].div = div;
+    nodes[index].cont.end += 1;
+    let tree = unsafe { Tree::from_raw_parts(inner, num_aabbs) };
+    let e: TreeInvariantError = tree.validate().unwrap_err();
+    assert_eq!(e.node, index);
+    assert_eq!(e.property, TreeInvariant::Cont);
+
+    let mut inner = tree.into_inner();
+    inner.get_nodes_mut()[index].cont.end -= 1;
+    let tree = unsafe { Tree::from_raw_parts(inner, num_aabbs + 1) };
+    assert_eq!(
+        tree.validate().unwrap_err().property,
+        TreeInvariant::NumAabbs
+    );
+}
+
+#[cfg(feature = "testing")]
+#[test]
+fn test_random_queries() {
+    use broccoli::testing::check_random;
+
+    //Empty and tiny trees, then larger ones, then one huge one.
+    let sizes = [0, 1, 2, 3, 7, 30, 100, 400, 2000];
+    let cases = sizes.iter().cycle().take(sizes.len() * 3).chain(&[20_000]);
+    for (seed, &num) in cases.enumerate() {
+        check_random::<i64>(seed as u64, num);
+        check_random::<f64>(seed as u64, num);
+    }
+}
+
+#[test]
+fn test_build_checked() {
+    use broccoli::build::TreeBuilder;
+    use broccoli::*;
+
+    let mut bots: Vec<_> = (0..100)
+        .map(|i| {
+            let x = i as f32 * 3.0;
+            bbox(rect(x, x + 5.0, 0.0, 5.0), i)
+        })
+        .collect();
+    bots[10].rect.x.start = f32::NAN;
+    bots[20].rect.y.end = f32::NAN;
+    bots[30].rect.y.start = 10.0;
+
+    let e = TreeBuilder::new(&mut bots).build_checked().err().unwrap();
+    assert_eq!(e.indices, vec![10, 20, 30]);
+
+    //Nothing was reordered.
+    assert!(bots.iter().enumerate().all(|(i, a)| a.inner == i));
+
+    bots[10].rect.x.start = 30.0;
+    bots[20].rect.y.end = 5.0;
+    bots[30].rect.y.start = 0.0;
+    let tree = TreeBuilder::new(&mut bots).build_checked().unwrap();
+    assert_eq!(tree.validate(), Ok(()));
+}
+
+#[test]
+fn test_notnan() {
+    use broccoli::notnan::NotNan;
+    use broccoli::*;
+
+    let n = |a: f64| NotNan::new(a).unwrap();
+    assert!(NotNan::new(f64::NAN).is_none());
+    assert!(n(1.0) < n(2.0));
+    assert_eq!(n(3.0).max(n(-1.0)), n(3.0));
+
+    let mut bots: Vec<_> = (0..300)
+        .map(|i| {
+            let r = create_rect(i).inner_as::<f64>();
+            bbox(rect(n(r.x.start), n(r.x.end), n(r.y.start), n(r.y.end)), ())
+        })
+        .collect();
+
+    let mut tree = broccoli::new(&mut bots);
+    assert_eq!(tree.validate(), Ok(()));
+    broccoli::query::colfind::assert_query(&mut tree);
+
+    let handler = &mut broccoli::query::knearest::default_rect_knearest(&tree);
+    broccoli::query::knearest::assert_k_nearest_mut(&mut tree, vec2(n(40.0), n(20.0)), 3, handler);
+
+    let ray = Ray {
+        point: vec2(n(-10.0), n(30.0)),
+        dir: vec2(n(1.0), n(0.5)),
+    };
+    let handler = &mut broccoli::query::raycast::default_rect_raycast(&tree);
+    broccoli::query::raycast::assert_raycast(&mut tree, ray, handler);
+}
+
+#[test]
+#[should_panic]
+fn test_notnan_arithmetic() {
+    use broccoli::notnan::NotNan;
+    let inf = NotNan::new(f32::INFINITY).unwrap();
+    let _ = inf - inf;
+}
+
+#[test]
+fn test_permutation_edge_cases() {
+    use broccoli::build::TreeBuilder;
+    use broccoli::*;
+
+    //The permuting build moves the elements with its own select and sort,
+    //so it has to end up with the same nodes as the normal build.
+    for rects in create_edge_case_rects() {
+        for &par in [false, true].iter() {
+            let mut bots: Vec<_> = rects.iter().enumerate().map(|(i, &r)| bbox(r, i)).collect();
+            let mut bots2 = bots.clone();
+
+            let mut builder = TreeBuilder::new(&mut bots);
+            let (tree, perm) = if par {
+                builder.build_with_permutation_par()
+            } else {
+                builder.build_with_permutation_seq()
+            };
+            assert_eq!(tree.validate(), Ok(()));
+            for (i, a) in tree.get_elements().iter().enumerate() {
+                assert_eq!(a.inner, perm.original_index(i));
+                assert_eq!(a.rect, rects[a.inner]);
+            }
+
+            let tree2 = broccoli::new(&mut bots2);
+            let nodes = |t: &Tree<BBox<isize, usize>>| -> Vec<_> {
+                t.get_nodes()
+                    .iter()
+                    .map(|n| (n.div, n.cont, n.range.len()))
+                    .collect()
+            };
+            assert_eq!(nodes(&tree), nodes(&tree2));
+        }
+    }
+}
+
+#[test]
+fn test_permutation() {
+    use broccoli::build::TreeBuilder;
+    use broccoli::*;
+
+    for &par in [false, true].iter() {
+        let mut bots: Vec<_> = (0..1000isize)
+            .map(|i| bbox(create_rect(i), (i as usize, 0usize)))
+            .collect();
+        let original: Vec<_> = bots.iter().map(|a| a.rect).collect();
+
+        let mut builder = TreeBuilder::new(&mut bots);
+        let (mut tree, perm) = if par {
+            builder.build_with_permutation_par()
+        } else {
+            builder.build_with_permutation_seq()
+        };
+        assert_eq!(tree.validate(), Ok(()));
+        assert_eq!(perm.len(), 1000);
+
+        for (i, a) in tree.get_elements().iter().enumerate() {
+            assert_eq!(a.inner.0, perm.original_index(i));
+            assert_eq!(perm.tree_index(a.inner.0), i);
+            assert_eq!(a.rect, original[a.inner.0]);
+        }
+
+        broccoli::query::colfind::assert_query(&mut tree);
+        tree.find_colliding_pairs_mut(|a, b| {
+            a.unpack_inner().1 += 1;
+            b.unpack_inner().1 += 1;
+        });
+
+        perm.unpermute(&mut bots);
+        assert!(bots.iter().enumerate().all(|(i, a)| a.inner.0 == i));
+        assert!(bots.iter().map(|a| a.rect).eq(original.iter().copied()));
+        assert!(bots.iter().any(|a| a.inner.1 > 0));
+
+        perm.permute(&mut bots);
+        assert!(bots
+            .iter()
+            .enumerate()
+            .all(|(i, a)| a.inner.0 == perm.original_index(i)));
+    }
+}
+
+#[test]
+fn test_rebuild_owned() {
+    use broccoli::container::*;
+    use broccoli::*;
+
+    for &par in [false, true].iter() {
+        let bots: Vec<_> = (0..1000isize).map(|i| bbox(create_rect(i), i)).collect();
+
+        let mut tree = TreeOwned::new(bots.clone().into_boxed_slice());
+        let nodes = tree.as_tree().get_nodes().as_ptr() as usize;
+        let shift = |bots: &mut [BBox<isize, isize>]| {
+            for a in bots.iter_mut() {
+                a.rect.x.start += a.inner % 50;
+                a.rect.x.end += a.inner % 50;
+            }
+        };
+        if par {
+            tree.rebuild_par(shift);
+        } else {
+            tree.rebuild(shift);
+        }
+        assert_eq!(tree.as_tree().get_nodes().as_ptr() as usize, nodes);
+        assert_eq!(tree.as_tree().validate(), Ok(()));
+        colfind::assert_query(tree.as_tree_mut());
+
+        //A panic during a rebuild leaves an empty tree that the next rebuild replaces.
+        let res = std::panic::catch_unwind(std::panic::AssertUnwindSafe(|| {
+            tree.rebuild(|_| panic!("rebuild failed"))
+        }));
+        assert!(res.is_err());
+        assert_eq!(tree.as_tree().validate(), Ok(()));
+        tree.rebuild(|_| {});
+        tree.rebuild(|_| {});
+        assert_eq!(tree.as_tree().validate(), Ok(()));
+        colfind::assert_query(tree.as_tree_mut());
+
+        let mut inner = tree.into_inner().into_vec();
+        inner.sort_unstable_by_key(|a| a.inner);
+        assert!(inner
+            .iter()
+            .zip(bots.iter())
+            .all(|(a, b)| a.rect.x.start == b.rect.x.start + b.inner % 50));
+
+        let mut tree = TreeIndOwned::new(bots.clone().into_boxed_slice(), |a| a.rect);
+        let nodes = tree.as_tree().get_nodes().as_ptr() as usize;
+        let shift = |a: &mut BBox<isize, isize>| {
+            a.rect.y.start += a.inner % 30;
+            a.rect.y.end += a.inner % 30;
+            a.rect
+        };
+        if par {
+            tree.rebuild_par(shift);
+        } else {
+            tree.rebuild(shift);
+        }
+        assert_eq!(tree.as_tree().get_nodes().as_ptr() as usize, nodes);
+        assert_eq!(tree.as_tree().validate(), Ok(()));
+        colfind::assert_query(tree.as_tree_mut());
+
+        let inner = tree.into_inner();
+        assert!(inner
+            .iter()
+            .zip(bots.iter())
+            .all(|(a, b)| a.inner == b.inner && a.rect.y.start == b.rect.y.start + b.inner % 30));
+    }
+}
+
+#[test]
+fn test_owned_vec() {
+    use broccoli::container::*;
+    use broccoli::*;
+
+    let make = |i: isize| bbox(create_rect(i), i);
+
+    for &par in [false, true].iter() {
+        let mut tree = TreeOwnedVec::new(Vec::with_capacity(2000));
+        assert!(tree.is_empty());
+        let capacity = tree.capacity();
+
+        for i in 0..1000 {
+            tree.push(make(i));
+        }
+        if par {
+            tree.update_par();
+        }
+        assert_eq!(tree.as_tree().validate(), Ok(()));
+        assert_eq!(tree.as_tree().num_aabbs(), 1000);
+        colfind::assert_query(tree.as_tree_mut());
+        let nodes = tree.as_tree().get_nodes().as_ptr() as usize;
+
+        tree.retain(|a| a.inner % 3 != 0);
+        let removed = tree.swap_remove(0);
+        assert_ne!(removed.inner % 3, 0);
+        for i in 1000..1300 {
+            tree.push(make(i));
+        }
+        if par {
+            tree.update_par();
+        }
+        assert_eq!(tree.len(), 666 - 1 + 300);
+        assert_eq!(tree.as_tree().validate(), Ok(()));
+        assert_eq!(tree.as_tree().num_aabbs(), tree.len());
+        assert_eq!(tree.as_tree().get_nodes().as_ptr() as usize, nodes);
+        colfind::assert_query(tree.as_tree_mut());
+        assert_eq!(tree.capacity(), capacity);
+
+        let mut ids: Vec<_> = tree.into_inner().iter().map(|a| a.inner).collect();
+        ids.sort_unstable();
+        let expected: Vec<_> = (0..1300)
+            .filter(|&i| (i >= 1000 || i % 3 != 0) && i != removed.inner)
+            .collect();
+        assert_eq!(ids, expected);
+    }
+}
+
+#[test]
+fn test_colliding_pairs_index() {
+    use broccoli::container::*;
+    use broccoli::*;
+
+    let mut bots: Vec<_> = (0..1000isize)
+        .map(|i| bbox(create_rect(i), 0usize))
+        .collect();
+
+    let mut naive = Vec::new();
+    colfind::query_naive_mut(pmut::PMut::new(&mut bots), |a, b| {
+        let base = a.rect.x.start * 1000 + a.rect.y.start;
+        let other = b.rect.x.start * 1000 + b.rect.y.start;
+        naive.push((base.min(other), base.max(other)));
+    });
+    naive.sort_unstable();
+
+    let key = |bots: &[BBox<isize, usize>], a: &ColPairIndex<()>| {
+        let a1 = bots[a.first as usize].rect;
+        let b1 = bots[a.second as usize].rect;
+        let base = a1.x.start * 1000 + a1.y.start;
+        let other = b1.x.start * 1000 + b1.y.start;
+        (base.min(other), base.max(other))
+    };
+
+    for &par in [false, true].iter() {
+        let mut base = TreeIndBase::new(&mut bots, |a| a.rect);
+        let mut tree = base.build();
+        let mut pairs = if par {
+            tree.collect_colliding_pairs_index_par(|_, _| Some(()))
+        } else {
+            tree.collect_colliding_pairs_index(|_, _| Some(()))
+        };
+
+        let mut found: Vec<_> = pairs.get().iter().map(|a| key(&bots, a)).collect();
+        found.sort_unstable();
+        assert_eq!(found, naive);
+
+        //The indices still refer to the same elements in a clone.
+        let mut cloned = bots.clone();
+        pairs.for_every_pair_mut(&mut cloned, |a, b, ()| {
+            a.inner += 1;
+            b.inner += 1;
+        });
+        assert_eq!(
+            cloned.iter().map(|a| a.inner).sum::<usize>(),
+            naive.len() * 2
+        );
+
+        //Remove every even element, moving the rest down.
+        pairs.remap(|i| if i % 2 == 0 { None } else { Some(i / 2) });
+        let odd: Vec<_> = bots.iter().skip(1).step_by(2).cloned().collect();
+
+        let mut base = TreeIndBase::new(&mut bots, |a| a.rect);
+        let mut tree = base.build();
+        let all = tree.collect_colliding_pairs_index(|_, _| Some(()));
+        let mut expected: Vec<_> = all
+            .get()
+            .iter()
+            .filter(|a| a.first % 2 == 1 && a.second % 2 == 1)
+            .map(|a| key(&bots, a))
+            .collect();
+        expected.sort_unstable();
+        let mut found: Vec<_> = pairs.get().iter().map(|a| key(&odd, a)).collect();
+        found.sort_unstable();
+        assert_eq!(found, expected);
+    }
+}
+
+#[test]
+fn test_colored_pairs() {
+    use broccoli::container::*;
+    use broccoli::*;
+
+    let mut bots: Vec<_> = (0..1000isize)
+        .map(|i| bbox(create_rect(i), 0usize))
+        .collect();
+    //An element that collides with many others.
+    bots.push(bbox(rect(0, 100, 0, 100), 0));
+
+    let mut base = TreeIndBase::new(&mut bots, |a| a.rect);
+    let mut tree = base.build();
+    let pairs = tree.collect_colliding_pairs_index(|_, _| Some(0usize));
+    let num_pairs = pairs.get().len();
+
+    let mut degree = vec![0; bots.len()];
+    for a in pairs.get() {
+        degree[a.first as usize] += 1;
+        degree[a.second as usize] += 1;
+    }
+    let max_degree = *degree.iter().max().unwrap();
+    assert!(max_degree > 50);
+
+    let mut colored = pairs.clone().into_colored();
+    assert_eq!(colored, pairs.clone().into_colored());
+    assert!(colored.num_batches() >= max_degree);
+    assert!(colored.num_batches() < 2 * max_degree);
+    assert_eq!(colored.batches().map(|a| a.len()).sum::<usize>(), num_pairs);
+
+    for batch in colored.batches() {
+        let mut seen = vec![false; bots.len()];
+        for a in batch {
+            assert!(!seen[a.first as usize] && !seen[a.second as usize]);
+            seen[a.first as usize] = true;
+            seen[a.second as usize] = true;
+        }
+    }
+
+    let mut expected = bots.clone();
+    colored.for_every_pair_mut(&mut expected, |a, b, c| {
+        a.inner += 1;
+        b.inner += 1;
+        *c += 1;
+    });
+    colored.for_every_pair_mut_par(&mut bots, |a, b, c| {
+        a.inner += 1;
+        b.inner += 1;
+        *c += 1;
+    });
+    assert!(bots
+        .iter()
+        .zip(expected.iter())
+        .all(|(a, b)| a.inner == b.inner));
+    assert!(bots.iter().zip(degree.iter()).all(|(a, &d)| a.inner == d));
+    assert!(colored.into_inner().get().iter().all(|a| a.extra == 2));
+}
+
+#[test]
+fn test_colliding_pairs_retain_extend_dedup() {
+    use broccoli::container::*;
+    use broccoli::*;
+
+    let mut bots: Vec<_> = (0..1000isize)
+        .map(|i| bbox(create_rect(i), (i as usize, 0usize)))
+        .collect();
+
+    let mut naive = Vec::new();
+    colfind::query_naive_mut(pmut::PMut::new(&mut bots), |a, b| {
+        let (a, b) = (a.inner.0, b.inner.0);
+        naive.push((a.min(b), a.max(b)));
+    });
+    naive.sort_unstable();
+    let keep = |a: &(usize, usize), b: &(usize, usize)| a.0 % 5 != 4 && b.0 % 5 != 4;
+    let retained: Vec<_> = naive
+        .iter()
+        .copied()
+        .filter(|&(a, b)| a % 5 != 4 && b % 5 != 4)
+        .collect();
+    fn ids<'a>(
+        a: impl IntoIterator<Item = &'a ColPair<'a, BBox<isize, (usize, usize)>, ()>>,
+    ) -> Vec<(usize, usize)> {
+        let mut v: Vec<_> = a
+            .into_iter()
+            .map(|a| {
+                let (x, y) = (a.first.inner.0, a.second.inner.0);
+                (x.min(y), x.max(y))
+            })
+            .collect();
+        v.sort_unstable();
+        v
+    }
+
+    let mut base = TreeIndBase::new(&mut bots, |a| a.rect);
+    let mut pairs = base.build().collect_colliding_pairs(|_, _| Some(()));
+    let other = base.build_par().collect_colliding_pairs(|_, _| Some(()));
+    pairs.extend_from(other);
+    assert_eq!(pairs.get(&bots).len(), naive.len() * 2);
+    pairs.dedup();
+    assert_eq!(ids(pairs.get(&bots)), naive);
+    pairs.retain(&bots, |a, b, ()| keep(&a.inner, &b.inner));
+    assert_eq!(ids(pairs.get(&bots)), retained);
+
+    let mut base = TreeIndBase::new(&mut bots, |a| a.rect);
+    let mut pairs = base
+        .build_par()
+        .collect_colliding_pairs_par(|_, _| Some(()));
+    let other = base.build().collect_colliding_pairs_par(|_, _| Some(()));
+    pairs.extend_from(other);
+    pairs.dedup();
+    assert_eq!(ids(pairs.get(&bots).iter().flatten()), naive);
+
+    //No two groups share an element.
+    let mut group_of = vec![None; bots.len()];
+    for (g, group) in pairs.get(&bots).iter().enumerate() {
+        for a in group {
+            for e in [a.first.inner.0, a.second.inner.0].iter() {
+                assert_eq!(*group_of[*e].get_or_insert(g), g);
+            }
+        }
+    }
+
+    pairs.retain(&bots, |a, b, ()| keep(&a.inner, &b.inner));
+    assert_eq!(ids(pairs.get(&bots).iter().flatten()), retained);
+
+    pairs.for_every_pair_mut_par(&mut bots, |a, b, ()| {
+        a.inner.1 += 1;
+        b.inner.1 += 1;
+    });
+    assert_eq!(
+        bots.iter().map(|a| a.inner.1).sum::<usize>(),
+        retained.len() * 2
+    );
+}
+
+#[test]
+fn test_multi_query() {
+    use broccoli::query::multi::*;
+    use broccoli::*;
+    use std::sync::Mutex;
+
+    let mut bots: Vec<_> = (0..1000isize)
+        .map(|i| bbox(create_rect(i), (i as usize, 0usize)))
+        .collect();
+
+    let circles = [
+        Circle {
+            center: vec2(30, 30),
+            radius: 20,
+        },
+        Circle {
+            center: vec2(150, 150),
+            radius: 30,
+        },
+        Circle {
+            center: vec2(260, 60),
+            radius: 25,
+        },
+    ];
+    let naive_circle = |c: &Circle<isize>| -> Vec<usize> {
+        bots.iter()
+            .filter(|a| c.intersects_rect(&a.rect))
+            .map(|a| a.inner.0)
+            .collect()
+    };
+    let expected: Vec<_> = circles.iter().map(naive_circle).collect();
+    assert!(expected.iter().all(|a| !a.is_empty()));
+
+    let mut tree = broccoli::new(&mut bots);
+    let mut handler = knearest::default_rect_knearest(&tree);
+    let mut segment_handler = segment::default_rect_segment(&tree);
+
+    //Handlers that must never see an element returned by the first circle.
+    let not_first = |a: &BBox<isize, (usize, usize)>| assert!(!expected[0].contains(&a.inner.0));
+    let mut checked_handler = knearest::from_closure(
+        &tree,
+        (),
+        |_, _, _| None,
+        |_, point, a| {
+            not_first(&a);
+            a.rect.distance_squared_to_point(point).unwrap_or(0)
+        },
+        |_, point, a| (point.x - a) * (point.x - a),
+        |_, point, a| (point.y - a) * (point.y - a),
+    );
+    let mut checked_segment_handler = segment::from_closure(&tree, (), |_, _, a| {
+        not_first(a);
+        true
+    });
+
+    let mut multi = tree.multi_query();
+
+    let mut first = Vec::new();
+    multi
+        .for_all_intersect_circle_mut(&circles[0], |a| first.push(a))
+        .unwrap();
+
+    //Overlaps the first circle.
+    let res = multi.for_all_intersect_rect_mut(&rect(0, 40, 0, 40), |_| panic!());
+    assert_eq!(res, Err(AlreadyQueriedErr));
+
+    //The k nearest and segment queries skip the elements of the first circle.
+    let mut near = multi.k_nearest_mut(vec2(30, 30), 1, &mut checked_handler);
+    let mut crossing = Vec::new();
+    multi.for_all_intersect_segment_mut(
+        &segment::Segment {
+            a: vec2(10, 30),
+            b: vec2(120, 30),
+        },
+        &mut checked_segment_handler,
+        |a| crossing.push(a),
+    );
+    assert!(!near.is_empty());
+    assert!(!crossing.is_empty());
+
+    let found = Mutex::new(vec![Vec::new(); 2]);
+    multi
+        .for_all_intersect_circles_par(&circles[1..], |i, a| {
+            found.lock().unwrap()[i].push(a.inner.0)
+        })
+        .unwrap();
+
+    //Both circles were already queried.
+    let res = multi.for_all_intersect_circles_par(&circles[1..2], |_, _| panic!());
+    assert_eq!(res, Err(AlreadyQueriedErr));
+
+    let mut second = multi.k_nearest_mut(vec2(290, 240), 3, &mut handler);
+    let mut third = Vec::new();
+    multi.for_all_intersect_segment_mut(
+        &segment::Segment {
+            a: vec2(100, 240),
+            b: vec2(200, 240),
+        },
+        &mut segment_handler,
+        |a| third.push(a),
+    );
+    assert!(!third.is_empty());
+    let total = first.len() + near.total_len() + crossing.len() + second.total_len() + third.len();
+
+    //All the references are usable at the same time.
+    for a in first.iter_mut() {
+        a.borrow_mut().unpack_inner().1 += 1;
+    }
+    for a in second.iter() {
+        for b in a.iter_mut() {
+            b.bot.borrow_mut().unpack_inner().1 += 1;
+        }
+    }
+    for a in third.iter_mut().chain(crossing.iter_mut()) {
+        a.borrow_mut().unpack_inner().1 += 1;
+    }
+    for a in near.iter() {
+        for b in a.iter_mut() {
+            b.bot.borrow_mut().unpack_inner().1 += 1;
+        }
+    }
+
+    let mut first: Vec<_> = first.iter().map(|a| a.inner.0).collect();
+    first.sort_unstable();
+    assert_eq!(first, expected[0]);
+    for (mut a, b) in found.into_inner().unwrap().into_iter().zip(&expected[1..]) {
+        a.sort_unstable();
+        assert_eq!(&a, b);
+    }
+
+    //Every element was handed out by at most one query.
+    assert!(bots.iter().all(|a| a.inner.1 <= 1));
+    assert_eq!(bots.iter().map(|a| a.inner.1).sum::<usize>(), total);
+}
+
+#[test]
+fn test_rect_par() {
+    use broccoli::*;
+    use std::sync::Mutex;
+
+    let mut bots: Vec<_> = (0..5000isize)
+        .map(|i| bbox(create_rect(i), i as usize))
+        .collect();
+
+    let rects = [
+        rect(100, 300, 50, 400),
+        rect(0, 700, 0, 700),
+        rect(-10, 0, -10, 0),
+        rect(250, 251, 0, 600),
+    ];
+    let sorted = |mut a: Vec<usize>| {
+        a.sort_unstable();
+        a
+    };
+
+    let mut tree = broccoli::new_par(&mut bots);
+    for r in rects.iter() {
+        let mut naive = [Vec::new(), Vec::new(), Vec::new()];
+        for a in tree.get_elements().iter() {
+            if r.get_intersect_rect(&a.rect).is_some() {
+                naive[0].push(a.inner);
+            }
+            if r.contains_rect(&a.rect) {
+                naive[1].push(a.inner);
+            } else {
+                naive[2].push(a.inner);
+            }
+        }
+        let naive: Vec<_> = naive.iter().cloned().map(sorted).collect();
+
+        let found = Mutex::new(Vec::new());
+        tree.for_all_intersect_rect_par(r, |a| found.lock().unwrap().push(a.inner));
+        assert_eq!(sorted(found.into_inner().unwrap()), naive[0]);
+
+        let found = Mutex::new(Vec::new());
+        tree.for_all_in_rect_par(r, |a| found.lock().unwrap().push(a.inner));
+        assert_eq!(sorted(found.into_inner().unwrap()), naive[1]);
+
+        let found = Mutex::new(Vec::new());
+        tree.for_all_not_in_rect_par(r, |a| found.lock().unwrap().push(a.inner));
+        assert_eq!(sorted(found.into_inner().unwrap()), naive[2]);
+
+        let mut found = Vec::new();
+        tree.for_all_not_in_rect(r, |a| found.push(a.inner));
+        assert_eq!(sorted(found), naive[2]);
+
+        let found = Mutex::new(Vec::new());
+        tree.for_all_intersect_rect_par_mut(r, |a| found.lock().unwrap().push(a.inner));
+        assert_eq!(sorted(found.into_inner().unwrap()), naive[0]);
+
+        let found = Mutex::new(Vec::new());
+        tree.for_all_in_rect_par_mut(r, |a| found.lock().unwrap().push(a.inner));
+        assert_eq!(sorted(found.into_inner().unwrap()), naive[1]);
+
+        let found = Mutex::new(Vec::new());
+        tree.for_all_not_in_rect_par_mut(r, |a| found.lock().unwrap().push(a.inner));
+        assert_eq!(sorted(found.into_inner().unwrap()), naive[2]);
+    }
+}
+
+#[test]
+fn test_count_any_find_rect() {
+    use broccoli::*;
+
+    let mut bots: Vec<_> = (0..1000isize)
+        .map(|i| bbox(create_rect(i), i as usize))
+        .collect();
+
+    let rects = [
+        rect(100, 200, 50, 150),
+        rect(-10, 400, -10, 400),
+        rect(-10, -5, -10, -5),
+        rect(150, 151, 0, 300),
+        rect(20, 90, 200, 260),
+    ];
+
+    let tree = broccoli::new(&mut bots);
+    for r in rects.iter() {
+        let elems = tree.get_elements();
+        let naive_in = elems.iter().filter(|a| r.contains_rect(&a.rect)).count();
+        let naive_any = elems
+            .iter()
+            .any(|a| r.get_intersect_rect(&a.rect).is_some());
+
+        assert_eq!(tree.count_in_rect(r), naive_in);
+        assert_eq!(tree.any_intersect_rect(r), naive_any);
+
+        match tree.find_first_intersect_rect(r) {
+            Some(a) => assert!(r.get_intersect_rect(&a.rect).is_some()),
+            None => assert!(!naive_any),
+        }
+    }
+}
+
+#[test]
+fn test_count_any_find_rect_edge_cases() {
+    use broccoli::*;
+
+    for rects in create_edge_case_rects() {
+        let mut bots: Vec<_> = rects.into_iter().map(|r| bbox(r, ())).collect();
+        let tree = broccoli::new(&mut bots);
+
+        let mut queries = vec![
+            rect(-100, 200, -100, 200),
+            rect(10, 20, 10, 20),
+            rect(100, 110, 0, 20),
+        ];
+        for d in dividers(&tree) {
+            queries.push(rect(d, d + 10, -5, 30));
+            queries.push(rect(d - 10, d, d - 10, d));
+            queries.push(rect(-100, d, -100, 200));
+            queries.push(rect(d, 200, -100, 200));
+        }
+
+        let elems = tree.get_elements();
+        for r in queries.iter() {
+            let naive_in = elems.iter().filter(|a| r.contains_rect(&a.rect)).count();
+            let naive_any = elems
+                .iter()
+                .any(|a| r.get_intersect_rect(&a.rect).is_some());
+
+            assert_eq!(tree.count_in_rect(r), naive_in);
+            assert_eq!(tree.any_intersect_rect(r), naive_any);
+            assert_eq!(tree.find_first_intersect_rect(r).is_some(), naive_any);
+        }
+    }
+}
+
+#[test]
+fn test_iter_rect() {
+    use broccoli::*;
+
+    let mut bots: Vec<_> = (0..1000isize)
+        .map(|i| bbox(create_rect(i), i as usize))
+        .collect();
+
+    let rects = [
+        rect(100, 200, 50, 150),
+        rect(-10, 400, -10, 400),
+        rect(-10, -5, -10, -5),
+        rect(150, 151, 0, 300),
+        rect(20, 90, 200, 260),
+    ];
+
+    let tree = broccoli::new(&mut bots);
+    for r in rects.iter() {
+        //The iterators visit the elements in the same order as the closure versions.
+        let mut expected = Vec::new();
+        tree.for_all_in_rect(r, |a| expected.push(a.inner));
+        let found: Vec<_> = tree.iter_in_rect(r).map(|a| a.inner).collect();
+        assert_eq!(found, expected);
+
+        let mut expected = Vec::new();
+        tree.for_all_intersect_rect(r, |a| expected.push(a.inner));
+        let found: Vec<_> = tree.iter_intersect_rect(r).map(|a| a.inner).collect();
+        assert_eq!(found, expected);
+
+        let first: Vec<_> = tree.iter_intersect_rect(r).take(3).collect();
+        assert_eq!(first.len(), expected.len().min(3));
+        assert_eq!(
+            tree.iter_intersect_rect(r).next().map(|a| a.inner),
+            tree.find_first_intersect_rect(r).map(|a| a.inner)
+        );
+    }
+}